@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::path::Path;
+use std::time::Instant;
+
+use ansi_term::Color::{Green, Red};
+use anyhow::Result;
+use dune_lib::chat::ChatComponent;
+use dune_lib::item::Book;
+use fs_err as fs;
+use serde_json::{Value, json};
+
+use crate::find_item::{Found, describe, find};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum BookFormat {
+    Markdown,
+    Json,
+    Text,
+}
+
+/// A book and everywhere a copy of it was found.
+struct LibraryBook<'a> {
+    book: &'a Book,
+    copies: Vec<&'a Found>,
+}
+
+fn collect(found: &[Found]) -> Vec<LibraryBook<'_>> {
+    let mut library: Vec<LibraryBook> = Vec::new();
+    let mut indices = HashMap::new();
+    for i in found {
+        let Some(book) = &i.stack.data.book else {
+            continue;
+        };
+        let pages: Vec<_> = book.pages.iter().map(|x| x.to_json().to_string()).collect();
+        let key = (book.title.clone(), book.author.clone(), pages);
+        let index = *indices.entry(key).or_insert_with(|| {
+            library.push(LibraryBook {
+                book,
+                copies: Vec::new(),
+            });
+            library.len() - 1
+        });
+        library[index].copies.push(i);
+    }
+    library.sort_by(|a, b| (&a.book.title, &a.book.author).cmp(&(&b.book.title, &b.book.author)));
+    library
+}
+
+fn title(book: &Book) -> &str {
+    book.title.as_deref().unwrap_or("Untitled")
+}
+
+fn write_markdown(out: &mut String, library: &[LibraryBook]) -> Result<()> {
+    writeln!(out, "# Library\n")?;
+    for i in library {
+        writeln!(
+            out,
+            "## {}\n",
+            ChatComponent::text(title(i.book)).to_markdown()
+        )?;
+        match &i.book.author {
+            Some(x) => writeln!(out, "by {}\n", ChatComponent::text(x).to_markdown())?,
+            None => writeln!(out, "not signed\n")?,
+        }
+        for copy in &i.copies {
+            writeln!(out, "- `{}`", describe(copy))?;
+        }
+        for (index, page) in i.book.pages.iter().enumerate() {
+            writeln!(out, "\n### Page {}\n\n{}", index + 1, page.to_markdown())?;
+        }
+        writeln!(out, "\n---\n")?;
+    }
+    Ok(())
+}
+
+fn write_text(out: &mut String, library: &[LibraryBook]) -> Result<()> {
+    for i in library {
+        write!(out, "{}", title(i.book))?;
+        if let Some(author) = &i.book.author {
+            write!(out, " by {}", author)?;
+        }
+        writeln!(out)?;
+        for copy in &i.copies {
+            writeln!(out, "  at {}", describe(copy))?;
+        }
+        for (index, page) in i.book.pages.iter().enumerate() {
+            writeln!(out, "--- page {} ---\n{}", index + 1, page)?;
+        }
+        writeln!(out, "{}\n", "=".repeat(80))?;
+    }
+    Ok(())
+}
+
+fn to_json(library: &[LibraryBook]) -> Value {
+    let books = library.iter().map(|i| {
+        let copies: Vec<_> = i
+            .copies
+            .iter()
+            .map(|x| {
+                json!({
+                    "dimension": x.holder.dimension,
+                    "position": x.holder.position,
+                    "holder": x.holder.id,
+                    "inside": x.inside.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({
+            "title": i.book.title,
+            "author": i.book.author,
+            "pages": i.book.pages.iter().map(ChatComponent::to_json).collect::<Vec<_>>(),
+            "copies": copies,
+        })
+    });
+    Value::Array(books.collect())
+}
+
+/// Writes every book of a world, once for all its copies, to `books.md`,
+/// `books.json` or `books.txt`.
+pub fn export(path: String, format: BookFormat) -> Result<()> {
+    let time = Instant::now();
+    let (found, errors_count) = find(Path::new(&path), &|x| x.data.book.is_some())?;
+    let library = collect(&found);
+
+    let mut out = String::with_capacity(64 * 1024);
+    let file = match format {
+        BookFormat::Markdown => {
+            write_markdown(&mut out, &library)?;
+            "books.md"
+        }
+        BookFormat::Json => {
+            out = serde_json::to_string_pretty(&to_json(&library))?;
+            "books.json"
+        }
+        BookFormat::Text => {
+            write_text(&mut out, &library)?;
+            "books.txt"
+        }
+    };
+    fs::write(file, out)?;
+
+    println!(
+        "output written to `{}` in {:?}\n{}={}\n{}={} ({} copies)",
+        file,
+        time.elapsed(),
+        Red.paint("errors count"),
+        errors_count,
+        Green.paint("books count"),
+        library.len(),
+        found.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ansi_term::Color::{Green, Red, Yellow};
+use anyhow::Result;
+use dune_lib::world::chunk::SignText;
+use dune_lib::world::diff::{BlockEntityChange, RegionDiff, diff_dimension};
+use dune_lib::world::scan::Dimension;
+
+/// The dimensions of both worlds by name. The world missing one gets it at the
+/// same place as the other, without regions.
+fn pair_dimensions(old: &Path, new: &Path) -> Result<Vec<(Dimension, Dimension)>> {
+    let old_dimensions = Dimension::find_all(old)?;
+    let new_dimensions = Dimension::find_all(new)?;
+    let counterpart = |dimension: &Dimension, from: &Path, to: &Path| Dimension {
+        name: dimension.name.clone(),
+        path: to.join(dimension.path.strip_prefix(from).unwrap_or(Path::new(""))),
+    };
+
+    let mut result = Vec::new();
+    for i in &new_dimensions {
+        let old_dimension = match old_dimensions.iter().find(|x| x.name == i.name) {
+            Some(x) => x.clone(),
+            None => counterpart(i, new, old),
+        };
+        result.push((old_dimension, i.clone()));
+    }
+    for i in &old_dimensions {
+        if !new_dimensions.iter().any(|x| x.name == i.name) {
+            result.push((i.clone(), counterpart(i, old, new)));
+        }
+    }
+    Ok(result)
+}
+
+fn sign_text(text: &SignText) -> String {
+    let lines = text.messages.each_ref().map(|x| x.to_string());
+    lines.join(" | ")
+}
+
+fn print_details(region: &RegionDiff) {
+    for (x, z) in &region.added {
+        println!("  chunk {} {}: {}", x, z, Green.paint("added"));
+    }
+    for (x, z) in &region.removed {
+        println!("  chunk {} {}: {}", x, z, Red.paint("removed"));
+    }
+    for chunk in &region.changed {
+        println!(
+            "  chunk {} {}: {} blocks changed",
+            chunk.x, chunk.z, chunk.changed_blocks
+        );
+        for i in &chunk.blocks {
+            let [x, y, z] = i.position;
+            println!("    {} {} {}: {} -> {}", x, y, z, i.old, i.new);
+        }
+        for i in &chunk.block_entities {
+            match i {
+                BlockEntityChange::Added { position, id } => {
+                    let [x, y, z] = position;
+                    println!("    {} {} {}: {} {}", x, y, z, Green.paint("+"), id);
+                }
+                BlockEntityChange::Removed { position, id } => {
+                    let [x, y, z] = position;
+                    println!("    {} {} {}: {} {}", x, y, z, Red.paint("-"), id);
+                }
+                BlockEntityChange::Items {
+                    position,
+                    id,
+                    deltas,
+                } => {
+                    let [x, y, z] = position;
+                    let deltas: Vec<_> = deltas
+                        .iter()
+                        .map(|(item, count)| format!("{:+} {}", count, item))
+                        .collect();
+                    println!("    {} {} {}: {} {}", x, y, z, id, deltas.join(", "));
+                }
+                BlockEntityChange::Sign { position, old, new } => {
+                    let [x, y, z] = position;
+                    println!("    {} {} {}: sign", x, y, z);
+                    for (side, a, b) in [
+                        ("front", &old.front, &new.front),
+                        ("back", &old.back, &new.back),
+                    ] {
+                        if a != b {
+                            println!("      {}: `{}` -> `{}`", side, sign_text(a), sign_text(b));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Prints what changed between two copies of a world, a line per region, and
+/// every change if `details`.
+pub fn print(old: String, new: String, details: bool) -> Result<()> {
+    let time = Instant::now();
+    let (old, new) = (Path::new(&old), Path::new(&new));
+    let mut errors_count = 0;
+    let mut changed_count = 0;
+    for (old_dimension, new_dimension) in pair_dimensions(old, new)? {
+        let regions = diff_dimension(&old_dimension, &new_dimension, details, 0, |report| {
+            for e in &report.errors {
+                eprintln!("error in file {}: {:#}", report.path.display(), e);
+            }
+            errors_count += report.errors.len();
+        })?;
+
+        for region in &regions {
+            let blocks: usize = region.changed.iter().map(|x| x.changed_blocks).sum();
+            let block_entities: usize = region.changed.iter().map(|x| x.block_entities.len()).sum();
+            println!(
+                "{} r.{}.{}.mca --- {} changed, {} added, {} removed, {} unchanged --- {} blocks, {} block entities",
+                new_dimension.name,
+                region.x,
+                region.z,
+                Yellow.paint(region.changed.len().to_string()),
+                Green.paint(region.added.len().to_string()),
+                Red.paint(region.removed.len().to_string()),
+                region.unchanged,
+                blocks,
+                block_entities,
+            );
+            if details {
+                print_details(region);
+            }
+            changed_count += region.changed.len() + region.added.len() + region.removed.len();
+        }
+    }
+
+    println!(
+        "done in {:?}\n{}={}\n{}={}",
+        time.elapsed(),
+        Red.paint("errors count"),
+        errors_count,
+        Green.paint("chunks changed"),
+        changed_count,
+    );
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use ansi_term::Color::{Green, Red};
+use anyhow::{Result, bail};
+use dune_common::nbt;
+use dune_lib::item::{Id, ItemStack, enchantment_id, item_id};
+use dune_lib::world::chunk::Chunk;
+use dune_lib::world::entity::{Entity, EntityChunk, EntityKind};
+use dune_lib::world::player::{
+    find_user_cache, format_uuid, player_files, read_player, read_user_cache,
+};
+use dune_lib::world::scan::{
+    ChunkVisitor, Dimension, EntityVisitor, RegionReport, scan, scan_entities,
+};
+use dune_lib::{Enchantment, Item};
+
+/// What to look for, every filter that is set has to match.
+pub struct ItemFilter {
+    pub item: Option<Id<Item>>,
+    pub enchantment: Option<Id<Enchantment>>,
+    /// Part of the custom name, in any case.
+    pub name: Option<String>,
+}
+
+impl ItemFilter {
+    /// Ids and enchantments can be given without `minecraft:`, the ones the
+    /// tables don't know are compared as they are.
+    pub fn new(
+        item: Option<String>,
+        enchantment: Option<String>,
+        name: Option<String>,
+    ) -> Result<ItemFilter> {
+        if item.is_none() && enchantment.is_none() && name.is_none() {
+            bail!("nothing to look for, give an item, an enchantment or a name");
+        }
+        let id = |x: String| match x.contains(':') {
+            true => x,
+            false => format!("minecraft:{}", x),
+        };
+        Ok(ItemFilter {
+            item: item.map(|x| item_id(&id(x))),
+            enchantment: enchantment.map(|x| enchantment_id(&id(x))),
+            name: name.map(|x| x.to_lowercase()),
+        })
+    }
+
+    pub fn matches(&self, stack: &ItemStack) -> bool {
+        if self.item.as_ref().is_some_and(|x| *x != stack.item) {
+            return false;
+        }
+        if let Some(enchantment) = &self.enchantment {
+            let mut all = stack
+                .data
+                .enchantments
+                .iter()
+                .chain(&stack.data.stored_enchantments);
+            if !all.any(|x| x.enchantment == *enchantment) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.name {
+            let custom = stack.data.name.as_deref().unwrap_or_default();
+            if !custom.to_lowercase().contains(name) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// What holds the items, like a chest, an armor stand or a player.
+#[derive(Clone)]
+pub struct Holder {
+    pub dimension: String,
+    pub position: [i32; 3],
+    /// Like `minecraft:chest`.
+    pub id: String,
+}
+
+#[derive(Clone)]
+pub struct Found {
+    pub holder: Holder,
+    /// The containers the item is in, like a shulker box in the chest.
+    pub inside: Vec<Id<Item>>,
+    pub stack: ItemStack,
+}
+
+type Matcher<'a> = &'a (dyn Fn(&ItemStack) -> bool + Sync);
+
+#[derive(Clone)]
+struct FindVisitor<'a> {
+    matches: Matcher<'a>,
+    found: Vec<Found>,
+}
+
+impl FindVisitor<'_> {
+    fn search<'i>(
+        &mut self,
+        holder: &Holder,
+        inside: &mut Vec<Id<Item>>,
+        items: impl IntoIterator<Item = &'i ItemStack>,
+    ) {
+        for stack in items {
+            if (self.matches)(stack) {
+                self.found.push(Found {
+                    holder: holder.clone(),
+                    inside: inside.clone(),
+                    stack: stack.clone(),
+                });
+            }
+            if !stack.data.contents.is_empty() {
+                inside.push(stack.item.clone());
+                self.search(holder, inside, &stack.data.contents);
+                inside.pop();
+            }
+        }
+    }
+
+    fn search_entity(&mut self, dimension: &Dimension, entity: &Entity) {
+        for i in &entity.passengers {
+            self.search_entity(dimension, i);
+        }
+        let holder = Holder {
+            dimension: dimension.name.clone(),
+            position: entity.position.map(|x| x.floor() as i32),
+            id: entity.id.to_string(),
+        };
+        let inside = &mut Vec::new();
+        match &entity.kind {
+            EntityKind::ItemFrame(frame) => self.search(&holder, inside, &frame.item),
+            EntityKind::ArmorStand(stand) => {
+                let items = stand.armor.iter().chain(&stand.hands).flatten();
+                self.search(&holder, inside, items);
+            }
+            EntityKind::Item(item) => self.search(&holder, inside, [item]),
+            EntityKind::StorageMinecart(storage) => self.search(&holder, inside, &storage.items),
+            EntityKind::Villager(_) | EntityKind::Other => {}
+        }
+    }
+}
+
+impl ChunkVisitor for FindVisitor<'_> {
+    fn visit(&mut self, dimension: &Dimension, chunk: &Chunk) -> Result<()> {
+        for i in &chunk.block_entities {
+            let holder = Holder {
+                dimension: dimension.name.clone(),
+                position: [i.position.x, i.position.y, i.position.z],
+                id: i.id.clone(),
+            };
+            self.search(&holder, &mut Vec::new(), i.kind.items());
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.found.extend(other.found);
+    }
+}
+
+impl EntityVisitor for FindVisitor<'_> {
+    fn visit(&mut self, dimension: &Dimension, chunk: &EntityChunk) -> Result<()> {
+        for i in &chunk.entities {
+            self.search_entity(dimension, i);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.found.extend(other.found);
+    }
+}
+
+/// Searches the containers, entities and players of a world, and the containers
+/// in them. Errors are printed as they're found, and counted.
+pub fn find(world: &Path, matches: Matcher) -> Result<(Vec<Found>, usize)> {
+    let dimensions = Dimension::find_all(world)?;
+    let mut errors_count = 0;
+    let mut on_region = |report: RegionReport| {
+        for e in &report.errors {
+            eprintln!("error in file {}: {:#}", report.path.display(), e);
+        }
+        errors_count += report.errors.len();
+    };
+
+    let visitor = FindVisitor {
+        matches,
+        found: Vec::new(),
+    };
+    let mut visitor = scan(&dimensions, 0, visitor, &mut on_region)?;
+    visitor = scan_entities(&dimensions, 0, visitor, &mut on_region)?;
+
+    // without the names, the players are shown by uuid
+    let names = match find_user_cache(world).map(|x| (read_user_cache(&x), x)) {
+        Some((Ok(x), _)) => x,
+        Some((Err(e), path)) => {
+            eprintln!("error in file {}: {:#}", path.display(), e);
+            errors_count += 1;
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    };
+    for (path, uuid) in player_files(world)? {
+        let player = match nbt::read_file(&path).and_then(|x| read_player(&x.data, uuid)) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("error in file {}: {:#}", path.display(), e);
+                errors_count += 1;
+                continue;
+            }
+        };
+        let name = match player.uuid {
+            Some(x) => names.get(&x).cloned().unwrap_or_else(|| format_uuid(x)),
+            None => path.display().to_string(),
+        };
+        let mut holder = Holder {
+            dimension: player.dimension,
+            position: player.position.map(|x| x.floor() as i32),
+            id: format!("player {}", name),
+        };
+        visitor.search(&holder, &mut Vec::new(), &player.inventory);
+        holder.id = format!("ender chest of {}", name);
+        visitor.search(&holder, &mut Vec::new(), &player.ender_items);
+    }
+
+    visitor.found.sort_by(|a, b| {
+        (&a.holder.dimension, a.holder.position).cmp(&(&b.holder.dimension, b.holder.position))
+    });
+    Ok((visitor.found, errors_count))
+}
+
+/// Where an item is, like `1 2 3 in minecraft:overworld: minecraft:chest > ShulkerBox`.
+pub fn describe(found: &Found) -> String {
+    let [x, y, z] = found.holder.position;
+    let mut result = format!(
+        "{} {} {} in {}: {}",
+        x, y, z, found.holder.dimension, found.holder.id
+    );
+    for container in &found.inside {
+        result += &format!(" > {}", container);
+    }
+    result
+}
+
+pub fn print(path: String, filter: ItemFilter) -> Result<()> {
+    let time = Instant::now();
+    let (found, errors_count) = find(Path::new(&path), &|x| filter.matches(x))?;
+    let mut total = 0;
+    for i in &found {
+        println!("{} > {}", describe(i), i.stack);
+        total += i.stack.count as u64;
+    }
+
+    println!(
+        "done in {:?}\n{}={}\n{}={} in {} stacks",
+        time.elapsed(),
+        Red.paint("errors count"),
+        errors_count,
+        Green.paint("items count"),
+        total,
+        found.len(),
+    );
+    Ok(())
+}
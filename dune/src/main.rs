@@ -1,5 +1,12 @@
+mod books;
+mod diff;
+mod find_item;
 mod launchers;
+mod map;
+mod players;
+mod schematic;
 mod signs;
+mod trades;
 
 use std::collections::HashMap;
 use std::fmt::Write as FmtWrite;
@@ -8,20 +15,23 @@ use std::time::Instant;
 
 use ansi_term::Color::{Cyan, Green, Purple};
 use anyhow::{Result, anyhow, bail};
-use bumpalo::Bump;
-use bumpalo::collections::{String as BString, Vec as BVec};
+use books::BookFormat;
 use chrono::Local;
 use clap::Parser;
-use dune_common::nbt::{self, Tag};
-use dune_data::protocol::{InventorySlot, InventorySlotData};
 use dune_lib::chat::parse_chat;
-use dune_lib::events::{EventSubscriber, Position, TradeListResponse, UseEntity, UseEntityKind};
+use dune_lib::client;
+use dune_lib::events::{
+    ChatMessage, EventSubscriber, PlayerPosition, Position, Trades, UseEntity, UseEntityKind,
+};
 use dune_lib::record::record_to_file;
 use dune_lib::replay::play;
-use dune_lib::{Enchantment, Item, client};
+use dune_lib::trade::VillagerTrades;
+use dune_lib::world::map::MapOptions;
+use find_item::ItemFilter;
 use fs_err as fs;
 use launchers::{AuthDataExt, get_access_token};
-use log::{LevelFilter, info, warn};
+use log::{LevelFilter, info};
+use schematic::SchematicFormat;
 use serde_derive::Deserialize;
 use simple_logger::SimpleLogger;
 
@@ -35,9 +45,91 @@ struct Args {
 #[derive(clap::Subcommand)]
 enum Action {
     Record(RecordCommand),
-    Replay { option: String },
-    Client { option: Option<String> },
-    Signs { path: String },
+    Replay {
+        option: String,
+    },
+    Client {
+        option: Option<String>,
+    },
+    Signs {
+        path: String,
+    },
+    Trades {
+        path: String,
+    },
+    /// Writes every book found in a world to a file.
+    Books {
+        path: String,
+        #[arg(short, long, value_enum, default_value_t = BookFormat::Markdown)]
+        format: BookFormat,
+    },
+    /// Draws the regions of a world from above to png files.
+    Map {
+        path: String,
+        #[arg(short, long, default_value = "map")]
+        out: String,
+        /// Shows signs and containers.
+        #[arg(short, long)]
+        markers: bool,
+        /// How many times smaller the overview is than the tiles.
+        #[arg(short, long, default_value_t = 4)]
+        scale: usize,
+    },
+    /// Compares two copies of a world, like a backup and the world now.
+    Diff {
+        old: String,
+        new: String,
+        /// Lists every changed block and block entity.
+        #[arg(short, long)]
+        details: bool,
+    },
+    /// Prints the settings of a world and where its players are.
+    Players {
+        path: String,
+        /// A name or a uuid, to list what that player carries.
+        player: Option<String>,
+    },
+    /// Cuts a box out of a world, with its block entities and entities.
+    Schematic {
+        path: String,
+        /// One corner, both are included.
+        #[arg(
+            long,
+            num_args = 3,
+            required = true,
+            allow_negative_numbers = true,
+            value_names = ["X", "Y", "Z"]
+        )]
+        from: Vec<i32>,
+        /// The opposite corner.
+        #[arg(
+            long,
+            num_args = 3,
+            required = true,
+            allow_negative_numbers = true,
+            value_names = ["X", "Y", "Z"]
+        )]
+        to: Vec<i32>,
+        /// Like `the_nether` or `minecraft:the_nether`.
+        #[arg(short, long, default_value = "minecraft:overworld")]
+        dimension: String,
+        #[arg(short, long, value_enum, default_value_t = SchematicFormat::Sponge)]
+        format: SchematicFormat,
+        /// `schematic.schem` or `schematic.nbt` by default.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Looks for items in the containers, entities and players of a world.
+    FindItem {
+        path: String,
+        /// Like `diamond` or `minecraft:diamond`.
+        item: Option<String>,
+        #[arg(short, long)]
+        enchantment: Option<String>,
+        /// Part of the custom name.
+        #[arg(short, long)]
+        name: Option<String>,
+    },
 }
 #[derive(Parser)]
 struct RecordCommand {
@@ -69,143 +161,13 @@ impl EventHandler {
     }
 }
 
-// TODO: remove
-#[allow(unused)]
-#[derive(Debug)]
-struct EnchantmentData {
-    enchantment: Enchantment,
-    level: u8,
-}
-
-// TODO: remove
-#[allow(unused)]
-#[derive(Debug)]
-struct InventorySlotAttrs<'i> {
-    enchantments: BVec<'i, EnchantmentData>,
-}
-
-// TODO: remove
-#[allow(unused)]
-#[derive(Debug)]
-struct InventorySlotUnpacked<'i> {
-    item_id: Item,
-    count: u8,
-    attrs: Option<InventorySlotAttrs<'i>>,
-}
-
-fn get_item_opt(item: Option<InventorySlot>) -> Option<InventorySlotData> {
-    item?.data
-}
-fn check_map_empty(map: HashMap<&str, Tag>) {
-    if !map.is_empty() {
-        warn!("item nbt map not empty after deserializing: {:?}", map);
-    }
-}
-fn deserialize_item_nbt<'b>(
-    bump: &'b Bump,
-    mut map: HashMap<&str, Tag>,
-) -> Result<InventorySlotAttrs<'b>> {
-    let mut enchantments = BVec::new_in(bump);
-    if let Some(list) = map.remove("StoredEnchantments") {
-        let list = list.list()?;
-        enchantments.reserve(list.len());
-
-        for i in list {
-            let mut i = i.compound()?;
-
-            let level = i
-                .remove("lvl")
-                .ok_or_else(|| anyhow!("'lvl' attr not found"))?
-                .short()?;
-            let id = i
-                .remove("id")
-                .ok_or_else(|| anyhow!("'id' attr not found"))?
-                .string()?;
-            let id = Enchantment::from(id)?;
-
-            enchantments.push(EnchantmentData {
-                enchantment: id,
-                level: level.try_into()?,
-            });
-
-            check_map_empty(i);
-        }
-    }
-
-    check_map_empty(map);
-    Ok(InventorySlotAttrs { enchantments })
-}
-fn get_item<'b>(
-    bump: &'b Bump,
-    item: Option<InventorySlot>,
-) -> Result<Option<InventorySlotUnpacked<'b>>> {
-    let item = match get_item_opt(item) {
-        Some(x) => x,
-        None => return Ok(None),
-    };
-    let attrs = match item.nbt {
-        Some(buffer) => {
-            let r = nbt::read(buffer, bump)?;
-            let attrs = deserialize_item_nbt(bump, r.tag.compound()?)?;
-            Some(attrs)
-        }
-        None => None,
-    };
-
-    let id = item.item_id.try_into()?;
-    let item_id = Item::from_1_18_2(id)?;
-    Ok(Some(InventorySlotUnpacked {
-        item_id,
-        count: item.count,
-        attrs,
-    }))
-}
-
-fn to_roman(number: u8) -> &'static str {
-    match number {
-        0 => "",
-        1 => "I",
-        2 => "II",
-        3 => "III",
-        4 => "IV",
-        5 => "V",
-        _ => unreachable!(),
-    }
-}
-
-macro_rules! some_or_return {
-    ($e:expr) => {
-        match $e {
-            Some(x) => x,
-            None => return Ok(()),
-        }
-    };
-}
-fn print_item(out: &mut BString, name: &str, item: Option<InventorySlotUnpacked>) -> Result<()> {
-    let x = some_or_return!(item);
-    write!(out, "{}: {:>2}x {:?}", name, x.count, x.item_id)?;
-
-    if let Some(attrs) = x.attrs {
-        write!(out, "(")?;
-        for i in attrs.enchantments {
-            write!(out, "{:?}", i.enchantment)?;
-            if i.level != 0 {
-                write!(out, " {}", to_roman(i.level))?;
-            }
-        }
-        write!(out, ")")?;
-    }
-
-    writeln!(out)?;
-
-    Ok(())
-}
-
 impl EventSubscriber for EventHandler {
-    fn on_chat(&mut self, message: &str) -> Result<()> {
-        // println!("chat: {}", message);
-        let c = parse_chat(message)?;
-        println!("{}", c);
+    fn on_chat(&mut self, message: ChatMessage) -> Result<()> {
+        // println!("chat: {:?}", message);
+        match message {
+            ChatMessage::Json(message) => println!("{}", parse_chat(message)?),
+            ChatMessage::Player { message, .. } => println!("{}", message),
+        }
         Ok(())
     }
     fn player_info(&mut self, name: &str, uuid: u128) -> Result<()> {
@@ -213,31 +175,18 @@ impl EventSubscriber for EventHandler {
         self.player_uuid = uuid;
         Ok(())
     }
-    fn position(&mut self, pos: Position) -> Result<()> {
-        self.player_position = pos;
+    fn position(&mut self, pos: PlayerPosition) -> Result<()> {
+        self.player_position = pos.apply(self.player_position);
         Ok(())
     }
-    fn trades(&mut self, trades: TradeListResponse) -> Result<()> {
-        let bump = &mut Bump::with_capacity(4096);
-
+    fn trades(&mut self, trades: Trades) -> Result<()> {
         let last_entity = self
             .last_entity_interact
             .ok_or_else(|| anyhow!("use entity wasn't set before using it"))?;
 
-        let out = &mut BString::with_capacity_in(1024, bump);
+        let out = &mut String::with_capacity(1024);
         writeln!(out, "trades at {:?}:", last_entity)?;
-        for i in trades.trades {
-            let in1 = get_item(bump, Some(i.input_item_1))?;
-            print_item(out, "in1", in1)?;
-
-            let in2 = get_item(bump, Some(i.input_item_2))?;
-            print_item(out, "in2", in2)?;
-
-            let out_item = get_item(bump, Some(i.output_item))?;
-            print_item(out, "out", out_item)?;
-
-            writeln!(out)?;
-        }
+        trades::write_trades(out, &VillagerTrades::from_protocol(&trades)?)?;
         writeln!(
             out,
             "------------------------------------------------------------------------------------------------------------"
@@ -426,6 +375,43 @@ fn main_impl() -> Result<()> {
         }
         Action::Client { option } => do_client(config, auth_data_ext, option),
         Action::Signs { path } => signs::print(path),
+        Action::Trades { path } => trades::print(path),
+        Action::Books { path, format } => books::export(path, format),
+        Action::Map {
+            path,
+            out,
+            markers,
+            scale,
+        } => map::render(
+            path,
+            out,
+            MapOptions {
+                markers,
+                overview_scale: scale,
+                threads: 0,
+            },
+        ),
+        Action::Diff { old, new, details } => diff::print(old, new, details),
+        Action::Players { path, player } => players::print(path, player),
+        Action::Schematic {
+            path,
+            from,
+            to,
+            dimension,
+            format,
+            out,
+        } => {
+            let corner = |x: Vec<i32>| {
+                <[i32; 3]>::try_from(x).map_err(|_| anyhow!("expected 3 coordinates"))
+            };
+            schematic::export(path, dimension, corner(from)?, corner(to)?, format, out)
+        }
+        Action::FindItem {
+            path,
+            item,
+            enchantment,
+            name,
+        } => find_item::print(path, ItemFilter::new(item, enchantment, name)?),
     }
 }
 
@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ansi_term::Color::{Green, Red};
+use anyhow::Result;
+use dune_lib::world::map::{MapOptions, render_dimension};
+use dune_lib::world::scan::Dimension;
+
+/// Renders every dimension of a world to `out/<namespace>_<name>`.
+pub fn render(path: String, out: String, options: MapOptions) -> Result<()> {
+    let dimensions = Dimension::find_all(Path::new(&path))?;
+    let time = Instant::now();
+    let mut errors_count = 0;
+    let mut regions_count = 0;
+    for dimension in &dimensions {
+        let dir = Path::new(&out).join(dimension.name.replace([':', '/'], "_"));
+        render_dimension(dimension, &dir, &options, |report| {
+            for e in &report.errors {
+                eprintln!("error in file {}: {:#}", report.path.display(), e);
+            }
+            errors_count += report.errors.len();
+            regions_count += 1;
+
+            println!(
+                "{:>4}/{} --- {} {:<20} --- {:>4} chunks --- {:?}",
+                report.done,
+                report.total,
+                dimension.name,
+                report
+                    .path
+                    .file_name()
+                    .unwrap_or(report.path.as_os_str())
+                    .to_string_lossy(),
+                report.chunks,
+                report.elapsed,
+            );
+        })?;
+    }
+
+    println!(
+        "maps written to `{}` in {:?}\n{}={}\n{}={}",
+        out,
+        time.elapsed(),
+        Red.paint("errors count"),
+        errors_count,
+        Green.paint("regions count"),
+        regions_count,
+    );
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ansi_term::Color::{Green, Red, Yellow};
+use anyhow::Result;
+use dune_common::nbt;
+use dune_lib::world::level::{Level, game_mode_name, read_level_file};
+use dune_lib::world::player::{
+    Player, find_user_cache, format_uuid, parse_uuid, player_files, read_player, read_user_cache,
+};
+
+fn print_level(level: &Level) {
+    println!(
+        "{} ({}, data version {})",
+        Green.paint(&level.name),
+        level.version.as_deref().unwrap_or("unknown version"),
+        level.data_version.unwrap_or_default(),
+    );
+    let seed = level.seed.map_or("unknown".to_string(), |x| x.to_string());
+    let [x, y, z] = level.spawn;
+    println!(
+        "seed {} --- spawn at {} {} {} --- day {}, tick {} --- played {} ticks --- {}{}",
+        seed,
+        x,
+        y,
+        z,
+        level.day_time / 24000 + 1,
+        level.day_time % 24000,
+        level.time,
+        game_mode_name(level.game_mode),
+        if level.hardcore { ", hardcore" } else { "" },
+    );
+    println!("datapacks: {}", level.enabled_datapacks.join(", "));
+    if !level.disabled_datapacks.is_empty() {
+        println!(
+            "disabled datapacks: {}",
+            level.disabled_datapacks.join(", ")
+        );
+    }
+    let rules: Vec<_> = level
+        .game_rules
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    println!("game rules: {}\n", rules.join(", "));
+}
+
+fn print_player(name: &str, player: &Player, items: bool) {
+    let [x, y, z] = player.position;
+    println!(
+        "{} in {} at {:.1} {:.1} {:.1} --- {} --- {} health, {} food --- level {} ({} xp)",
+        Yellow.paint(name),
+        player.dimension,
+        x,
+        y,
+        z,
+        game_mode_name(player.game_mode),
+        player.health,
+        player.food_level,
+        player.xp_level,
+        player.xp_total,
+    );
+    if !items {
+        return;
+    }
+    let abilities = &player.abilities;
+    println!(
+        "  flying={} may_fly={} instabuild={} invulnerable={} may_build={}",
+        abilities.flying,
+        abilities.may_fly,
+        abilities.instabuild,
+        abilities.invulnerable,
+        abilities.may_build
+    );
+    for (title, stacks) in [
+        ("inventory", &player.inventory),
+        ("ender chest", &player.ender_items),
+    ] {
+        println!("  {}:", title);
+        for i in stacks {
+            println!("    {}", i);
+        }
+    }
+}
+
+/// Prints the settings of a world and where its players are. Given a name or
+/// a uuid, only that player is printed, with what they carry.
+pub fn print(path: String, player: Option<String>) -> Result<()> {
+    let world = Path::new(&path);
+    match read_level_file(world) {
+        Ok(level) => print_level(&level),
+        Err(e) => eprintln!("{}: can't read level.dat: {:#}", Red.paint("error"), e),
+    }
+
+    let names = match find_user_cache(world) {
+        Some(x) => read_user_cache(&x)?,
+        None => HashMap::new(),
+    };
+    let wanted = player.as_ref().map(|x| (x.to_lowercase(), parse_uuid(x)));
+    let mut count = 0;
+    for (path, uuid) in player_files(world)? {
+        let name = match uuid {
+            Some(x) => match names.get(&x) {
+                Some(name) => format!("{} ({})", name, format_uuid(x)),
+                None => format_uuid(x),
+            },
+            None => path.display().to_string(),
+        };
+        if let Some((wanted_name, wanted_uuid)) = &wanted {
+            let by_name = uuid
+                .and_then(|x| names.get(&x))
+                .is_some_and(|x| x.to_lowercase() == *wanted_name);
+            if !by_name && (wanted_uuid.is_none() || *wanted_uuid != uuid) {
+                continue;
+            }
+        }
+        match nbt::read_file(&path).and_then(|x| read_player(&x.data, uuid)) {
+            Ok(player) => print_player(&name, &player, wanted.is_some()),
+            Err(e) => eprintln!("error in file {}: {:#}", path.display(), e),
+        }
+        count += 1;
+    }
+
+    println!("\n{}={}", Green.paint("players count"), count);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Result, anyhow, bail};
+use dune_common::nbt::{Compression, write_file};
+use dune_lib::world::scan::Dimension;
+use dune_lib::world::schematic::{MAX_STRUCTURE_VOLUME, Schematic, volume};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SchematicFormat {
+    /// A Sponge schematic, for WorldEdit and similar.
+    Sponge,
+    /// A vanilla structure, for structure blocks.
+    Structure,
+}
+
+/// Writes the blocks between `from` and `to` in `dimension` to `out`, or to
+/// `<name>.schem` or `<name>.nbt` if it isn't given.
+pub fn export(
+    path: String,
+    dimension: String,
+    from: [i32; 3],
+    to: [i32; 3],
+    format: SchematicFormat,
+    out: Option<String>,
+) -> Result<()> {
+    let time = Instant::now();
+    let dimensions = Dimension::find_all(Path::new(&path))?;
+    let dimension = dimensions
+        .iter()
+        .find(|x| x.name == dimension || x.name == format!("minecraft:{}", dimension))
+        .ok_or_else(|| anyhow!("unknown dimension {}", dimension))?;
+
+    // before reading the world, the structure would be refused anyway
+    if matches!(format, SchematicFormat::Structure) && volume(from, to) > MAX_STRUCTURE_VOLUME {
+        bail!(
+            "the box has {} blocks, more than the {} a structure can have, export it as a sponge schematic",
+            volume(from, to),
+            MAX_STRUCTURE_VOLUME
+        );
+    }
+    let schematic = Schematic::from_world(dimension, from, to)?;
+    let (root, extension) = match format {
+        SchematicFormat::Sponge => (schematic.to_sponge(), "schem"),
+        SchematicFormat::Structure => (schematic.to_structure()?, "nbt"),
+    };
+    let out = out.unwrap_or_else(|| format!("schematic.{}", extension));
+    write_file(&out, &root, Compression::Gzip)?;
+
+    let [width, height, length] = schematic.size;
+    println!(
+        "{}x{}x{} blocks, {} block entities and {} entities written to `{}` in {:?}",
+        width,
+        height,
+        length,
+        schematic.block_entities.len(),
+        schematic.entities.len(),
+        out,
+        time.elapsed(),
+    );
+    Ok(())
+}
@@ -1,194 +1,159 @@
-use std::ffi::OsStr;
-use std::fs::{self, DirEntry, File};
+use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::time::Instant;
 
 use ansi_term::Color::{Green, Purple, Red};
 use anyhow::Result;
-use bumpalo::Bump;
-use dune_lib::world::anvil::{CHUNKS_PER_REGION, Region};
-use dune_lib::world::chunk::{BlockEntityKind, Chunk, read_chunk};
+use dune_lib::events::PositionInt;
+use dune_lib::world::chunk::{BlockEntityKind, Chunk, Sign, SignText};
+use dune_lib::world::scan::{ChunkVisitor, Dimension, scan};
+
+#[derive(Clone)]
+struct FoundSign {
+    dimension: String,
+    position: PositionInt,
+    sign: Sign,
+}
 
-struct SignsPrinter {
-    out: BufWriter<File>,
+#[derive(Clone, Default)]
+struct SignsVisitor {
+    signs: Vec<FoundSign>,
     max: usize,
-    signs_count: u32,
-    total_signs_count: u32,
-    errors_count: u32,
 }
 
-fn do_print(context: &mut SignsPrinter, chunk: Chunk) -> Result<()> {
-    const DASHES80: &str =
-        "--------------------------------------------------------------------------------";
-
-    for i in chunk.block_entities {
-        match i.kind {
-            BlockEntityKind::Sign(sign) => {
-                if sign.text.iter().all(String::is_empty) {
-                    continue;
-                }
-                context.max = sign
-                    .text
-                    .iter()
-                    .map(String::len)
-                    .max()
-                    .unwrap_or(context.max);
-
-                writeln!(
-                    context.out,
-                    "/tp {} {} {}\n{:^80}\n{:^80}\n{:^80}\n{:^80}\n{}\n",
-                    i.position.x,
-                    i.position.y,
-                    i.position.z,
-                    sign.text[0],
-                    sign.text[1],
-                    sign.text[2],
-                    sign.text[3],
-                    DASHES80
-                )?;
-                context.signs_count += 1;
-                context.total_signs_count += 1;
-            }
-            BlockEntityKind::Storage(chest) => {
-                if chest.items.is_empty() {
-                    continue;
-                }
-                println!("{:?}", chest.items);
+impl ChunkVisitor for SignsVisitor {
+    fn visit(&mut self, dimension: &Dimension, chunk: &Chunk) -> Result<()> {
+        for i in &chunk.block_entities {
+            let BlockEntityKind::Sign(sign) = &i.kind else {
+                continue;
+            };
+            if sign.front.is_empty() && sign.back.is_empty() {
+                continue;
             }
-            _ => {}
+            let messages = sign.front.messages.iter().chain(&sign.back.messages);
+            let longest = messages.map(|x| x.to_string().len()).max();
+            self.max = self.max.max(longest.unwrap_or(0));
+            self.signs.push(FoundSign {
+                dimension: dimension.name.clone(),
+                position: i.position,
+                sign: Sign::clone(sign),
+            });
         }
+        Ok(())
     }
-    Ok(())
-}
 
-fn print_chunk(
-    context: &mut SignsPrinter,
-    region: &mut Region,
-    tmp: &mut Vec<u8>,
-    bump: &Bump,
-    index: usize,
-) -> Result<()> {
-    let data = region.get_chunk(tmp, index)?;
-    if data.is_empty() {
-        return Ok(());
+    fn merge(&mut self, other: Self) {
+        self.signs.extend(other.signs);
+        self.max = self.max.max(other.max);
     }
-
-    let chunk = read_chunk(data, bump)?;
-    do_print(context, chunk)
 }
 
-fn print_region(
-    context: &mut SignsPrinter,
-    tmp: &mut Vec<u8>,
-    bump: &Bump,
-    path: &Path,
-) -> Result<()> {
-    let mut region = Region::load(path, false)?;
-    context.signs_count = 0;
-    for i in 0..CHUNKS_PER_REGION {
-        if let Err(e) = print_chunk(context, &mut region, tmp, bump, i) {
-            context.errors_count += 1;
-            eprintln!("error in file {}: {}", path.display(), e);
-            break;
-        }
+/// A world, or a single region folder, which is taken to be the overworld.
+fn find_dimensions(path: &Path) -> Result<Vec<Dimension>> {
+    if path.file_name().unwrap_or_default() == "region" && !path.join("region").is_dir() {
+        let parent = path.parent().unwrap_or(Path::new("."));
+        return Ok(vec![Dimension {
+            name: "minecraft:overworld".to_string(),
+            path: parent.to_path_buf(),
+        }]);
     }
-
-    Ok(())
+    Dimension::find_all(path)
 }
 
-fn parse_file_path((path, _): &(PathBuf, u64)) -> (i32, i32) {
-    let mut it = path
-        .file_name()
-        .unwrap_or_default()
-        .to_str()
-        .unwrap_or_default()
-        .split('.');
-    it.next();
-    let x = it.next().unwrap_or_default().parse().unwrap_or(i32::MIN);
-    let z = it.next().unwrap_or_default().parse().unwrap_or(i32::MIN);
-    (x, z)
-}
-fn get_paths(path: String) -> Result<Vec<(PathBuf, u64)>> {
-    let mut files = Vec::new();
-    let get = |i| -> Result<_> {
-        let i: DirEntry = i?;
-        let i = i.path();
-        let size = i.metadata()?.len();
-        Ok((i, size))
-    };
-    let mut zero_size_files = 0;
-    for i in fs::read_dir(path)? {
-        let i = match get(i) {
-            Ok(x) => x,
-            Err(e) => {
-                eprintln!("can't read path: {}", e);
-                continue;
-            }
-        };
-        if i.1 == 0 {
-            zero_size_files += 1;
-            continue;
-        }
-
-        files.push(i);
+/// The side is only written for signs with text on both.
+fn write_sign_text(out: &mut impl Write, side: Option<&str>, text: &SignText) -> Result<()> {
+    let mut labels: Vec<&str> = side.into_iter().collect();
+    if text.color != "black" {
+        labels.push(text.color);
     }
-    files.sort_by_cached_key(parse_file_path);
-
-    if zero_size_files > 0 {
-        println!("ignoring {} zero size files", zero_size_files);
+    if text.glowing {
+        labels.push("glowing");
     }
-
-    Ok(files)
+    if !labels.is_empty() {
+        writeln!(out, "{:^80}", format!("[{}]", labels.join(", ")))?;
+    }
+    for message in &text.messages {
+        writeln!(out, "{:^80}", message.to_string())?;
+    }
+    Ok(())
 }
 
-pub fn print(path: String) -> Result<()> {
-    let mut tmp = Vec::new();
-    let bump = &mut Bump::with_capacity(128 * 1024); // 128kb based on experimentation
-    let mut context = SignsPrinter {
-        out: BufWriter::new(File::create("out.txt")?),
-        max: 0,
-        signs_count: 0,
-        total_signs_count: 0,
-        errors_count: 0,
-    };
+fn write_signs(out: &mut impl Write, signs: &[FoundSign]) -> Result<()> {
+    const DASHES80: &str =
+        "--------------------------------------------------------------------------------";
 
-    let files = get_paths(path)?;
-    let files_count = files.len();
-    for (index, (path, file_size)) in files.into_iter().enumerate() {
-        let time = Instant::now();
-        if path.extension() != Some(OsStr::new("mca")) {
-            continue;
+    for found in signs {
+        let PositionInt { x, y, z } = found.position;
+        if found.dimension == "minecraft:overworld" {
+            write!(out, "/tp {} {} {}", x, y, z)?;
+        } else {
+            write!(
+                out,
+                "/execute in {} run tp {} {} {}",
+                found.dimension, x, y, z
+            )?;
         }
+        let sign = &found.sign;
+        match (sign.hanging, sign.waxed) {
+            (false, false) => writeln!(out)?,
+            (true, false) => writeln!(out, " (hanging)")?,
+            (false, true) => writeln!(out, " (waxed)")?,
+            (true, true) => writeln!(out, " (hanging, waxed)")?,
+        }
+        if sign.back.is_empty() {
+            write_sign_text(out, None, &sign.front)?;
+        } else {
+            write_sign_text(out, Some("front"), &sign.front)?;
+            write_sign_text(out, Some("back"), &sign.back)?;
+        }
+        writeln!(out, "{}\n", DASHES80)?;
+    }
+    Ok(())
+}
 
-        if let Err(e) = print_region(&mut context, &mut tmp, bump, &path) {
-            context.errors_count += 1;
-            eprintln!("can't read path: {}", e);
-            continue;
+pub fn print(path: String) -> Result<()> {
+    let dimensions = find_dimensions(Path::new(&path))?;
+    let time = Instant::now();
+    let mut errors_count = 0;
+    let mut visitor = scan(&dimensions, 0, SignsVisitor::default(), |report| {
+        for e in &report.errors {
+            eprintln!("error in file {}: {:#}", report.path.display(), e);
         }
+        errors_count += report.errors.len();
 
         println!(
-            "{:>4}/{} --- {:<20} --- {:>4} signs --- {:>10} --- {:?}",
-            index + 1,
-            files_count,
-            path.file_name()
-                .unwrap_or(path.as_os_str())
+            "{:>4}/{} --- {:<20} --- {:>4} chunks --- {:>10} --- {:?}",
+            report.done,
+            report.total,
+            report
+                .path
+                .file_name()
+                .unwrap_or(report.path.as_os_str())
                 .to_string_lossy(),
-            context.signs_count,
-            humansize::format_size(file_size, humansize::BINARY),
-            time.elapsed(),
+            report.chunks,
+            humansize::format_size(report.size, humansize::BINARY),
+            report.elapsed,
         );
+    })?;
+
+    visitor.signs.sort_by(|a, b| {
+        let key = |x: &FoundSign| (x.position.x, x.position.z, x.position.y);
+        (&a.dimension, key(a)).cmp(&(&b.dimension, key(b)))
+    });
+    let mut out = BufWriter::new(File::create("out.txt")?);
+    write_signs(&mut out, &visitor.signs)?;
+    out.flush()?;
 
-        bump.reset();
-    }
     println!(
-        "output written to `out.txt`\n{}={}\n{}={}\n{}={}",
+        "output written to `out.txt` in {:?}\n{}={}\n{}={}\n{}={}",
+        time.elapsed(),
         Purple.paint("max sign length"),
-        context.max,
+        visitor.max,
         Red.paint("errors count"),
-        context.errors_count,
+        errors_count,
         Green.paint("total signs count"),
-        context.total_signs_count,
+        visitor.signs.len(),
     );
 
     Ok(())
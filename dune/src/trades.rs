@@ -0,0 +1,122 @@
+use std::fmt::Write as FmtWrite;
+use std::path::Path;
+use std::time::Instant;
+
+use ansi_term::Color::{Green, Red};
+use anyhow::Result;
+use dune_lib::trade::VillagerTrades;
+use dune_lib::world::entity::{Entity, EntityChunk, EntityKind};
+use dune_lib::world::scan::{Dimension, EntityVisitor, scan_entities};
+use fs_err as fs;
+
+pub fn write_trades(out: &mut String, trades: &VillagerTrades) -> Result<()> {
+    for i in &trades.offers {
+        writeln!(out, "  {}", i)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+struct FoundVillager {
+    dimension: String,
+    position: [f64; 3],
+    profession: String,
+    name: Option<String>,
+    trades: VillagerTrades,
+}
+
+#[derive(Clone, Default)]
+struct TradesVisitor {
+    villagers: Vec<FoundVillager>,
+}
+
+impl TradesVisitor {
+    fn add(&mut self, dimension: &Dimension, entity: &Entity) -> Result<()> {
+        // villagers are often kept in boats or minecarts
+        for i in &entity.passengers {
+            self.add(dimension, i)?;
+        }
+        let EntityKind::Villager(villager) = &entity.kind else {
+            return Ok(());
+        };
+        if villager.offers.is_empty() {
+            return Ok(());
+        }
+        let profession = villager.data.profession;
+        self.villagers.push(FoundVillager {
+            dimension: dimension.name.clone(),
+            position: entity.position,
+            profession: profession
+                .strip_prefix("minecraft:")
+                .unwrap_or(profession)
+                .to_string(),
+            name: entity.custom_name.clone(),
+            trades: VillagerTrades::from_world(villager),
+        });
+        Ok(())
+    }
+}
+
+impl EntityVisitor for TradesVisitor {
+    fn visit(&mut self, dimension: &Dimension, chunk: &EntityChunk) -> Result<()> {
+        for i in &chunk.entities {
+            self.add(dimension, i)?;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.villagers.extend(other.villagers);
+    }
+}
+
+/// Writes the trades of every villager in the world to `trades.txt`.
+pub fn print(path: String) -> Result<()> {
+    let dimensions = Dimension::find_all(Path::new(&path))?;
+    let time = Instant::now();
+    let mut errors_count = 0;
+    let mut visitor = scan_entities(&dimensions, 0, TradesVisitor::default(), |report| {
+        for e in &report.errors {
+            eprintln!("error in file {}: {:#}", report.path.display(), e);
+        }
+        errors_count += report.errors.len();
+    })?;
+
+    visitor.villagers.sort_by(|a, b| {
+        (&a.dimension, &a.profession)
+            .cmp(&(&b.dimension, &b.profession))
+            .then_with(|| {
+                // positions can be NaN in broken saves
+                let [a, b] = [a.position, b.position];
+                a[0].total_cmp(&b[0])
+                    .then(a[1].total_cmp(&b[1]))
+                    .then(a[2].total_cmp(&b[2]))
+            })
+    });
+    let mut out = String::with_capacity(64 * 1024);
+    for i in &visitor.villagers {
+        let [x, y, z] = i.position.map(|x| x.floor() as i32);
+        write!(
+            out,
+            "{} level {} at {} {} {} in {}",
+            i.profession, i.trades.level, x, y, z, i.dimension
+        )?;
+        if let Some(name) = &i.name {
+            write!(out, " ({})", name)?;
+        }
+        writeln!(out)?;
+        write_trades(&mut out, &i.trades)?;
+        writeln!(out)?;
+    }
+    fs::write("trades.txt", out)?;
+
+    println!(
+        "output written to `trades.txt` in {:?}\n{}={}\n{}={}",
+        time.elapsed(),
+        Red.paint("errors count"),
+        errors_count,
+        Green.paint("villagers count"),
+        visitor.villagers.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,533 @@
+use std::fmt::{self, Display};
+use std::str;
+
+use byteorder::{BE, ReadBytesExt};
+use serde::de::value::{BorrowedStrDeserializer, MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, forward_to_deserialize_any};
+
+use crate::nbt::owned::{Compound, OwnedTag};
+use crate::nbt::{Format, skip_impl};
+
+/// Keys used to tell int and long arrays apart from lists when deserializing
+/// through `deserialize_any`, see [`IntArray`](crate::nbt::IntArray).
+pub(super) const INT_ARRAY: &str = "__dune_nbt_int_array";
+pub(super) const LONG_ARRAY: &str = "__dune_nbt_long_array";
+
+/// An error from (de)serializing nbt with serde, with the path to the value it
+/// happened at, like `block_entities[3].Items[2].Count`.
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+    path: Option<String>,
+}
+impl Error {
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    fn with_path(mut self, path: &[Segment]) -> Error {
+        if self.path.is_none() && !path.is_empty() {
+            let mut result = String::new();
+            for i in path {
+                match i {
+                    Segment::Key(x) if result.is_empty() => result += x,
+                    Segment::Key(x) => {
+                        result.push('.');
+                        result += x;
+                    }
+                    Segment::Index(x) => result += &format!("[{}]", x),
+                }
+            }
+            self.path = Some(result);
+        }
+        self
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error {
+            message: msg.to_string(),
+            path: None,
+        }
+    }
+}
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        de::Error::custom(msg)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        de::Error::custom(e)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+enum Segment<'de> {
+    Key(&'de str),
+    Index(usize),
+}
+
+/// Deserializes binary nbt straight from the buffer, without building a tree
+/// first. Strings and byte arrays borrow from the input.
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+    /// The type of the value that's deserialized next.
+    tag: u8,
+    path: Vec<Segment<'de>>,
+}
+
+impl<'de> Deserializer<'de> {
+    fn take(&mut self, size: usize) -> Result<&'de [u8]> {
+        if size > self.input.len() {
+            return Err(de::Error::custom("unexpected end of nbt"));
+        }
+        let (result, rest) = self.input.split_at(size);
+        self.input = rest;
+        Ok(result)
+    }
+    fn read_size(&mut self) -> Result<usize> {
+        Ok(self.input.read_i32::<BE>()?.max(0) as usize)
+    }
+    fn read_str(&mut self) -> Result<&'de str> {
+        let size = self.input.read_u16::<BE>()?;
+        let bytes = self.take(size as usize)?;
+        str::from_utf8(bytes).map_err(de::Error::custom)
+    }
+    fn skip(&mut self, tag: u8) -> Result<()> {
+        skip_impl(&mut self.input, tag).map_err(de::Error::custom)
+    }
+
+    fn with_element<T>(
+        &mut self,
+        segment: Segment<'de>,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        self.path.push(segment);
+        let result = f(self).map_err(|e| e.with_path(&self.path));
+        self.path.pop();
+        result
+    }
+}
+
+struct ListAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    kind: u8,
+    index: usize,
+    size: usize,
+}
+impl<'a, 'de> ListAccess<'a, 'de> {
+    /// Skips what the visitor didn't read.
+    fn finish(self) -> Result<()> {
+        for _ in self.index..self.size {
+            self.de.skip(self.kind)?;
+        }
+        Ok(())
+    }
+}
+impl<'de> SeqAccess<'de> for &mut ListAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.index == self.size {
+            return Ok(None);
+        }
+        let kind = self.kind;
+        let index = self.index;
+        self.index += 1;
+        self.de
+            .with_element(Segment::Index(index), |de| {
+                de.tag = kind;
+                seed.deserialize(de)
+            })
+            .map(Some)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.size - self.index)
+    }
+}
+
+/// Byte, int and long arrays, read one number at a time.
+struct ArrayAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    kind: u8,
+    index: usize,
+    size: usize,
+}
+impl<'a, 'de> ArrayAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Result<Self> {
+        let kind = de.tag;
+        let size = de.read_size()?;
+        Ok(ArrayAccess {
+            de,
+            kind,
+            index: 0,
+            size,
+        })
+    }
+    fn finish(self) -> Result<()> {
+        let item_size = match self.kind {
+            7 => 1,
+            11 => 4,
+            _ => 8,
+        };
+        self.de.take((self.size - self.index) * item_size)?;
+        Ok(())
+    }
+}
+impl<'de> SeqAccess<'de> for &mut ArrayAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.index == self.size {
+            return Ok(None);
+        }
+        let kind = self.kind;
+        let index = self.index;
+        self.index += 1;
+        self.de
+            .with_element(Segment::Index(index), |de| match kind {
+                7 => seed.deserialize(de.input.read_i8()?.into_deserializer()),
+                11 => seed.deserialize(de.input.read_i32::<BE>()?.into_deserializer()),
+                _ => seed.deserialize(de.input.read_i64::<BE>()?.into_deserializer()),
+            })
+            .map(Some)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.size - self.index)
+    }
+}
+
+/// An int or long array as a map with a single, special key, so self describing
+/// types like [`OwnedTag`] can tell it apart from a list.
+struct MarkedArrayAccess<'a, 'de> {
+    array: ArrayAccess<'a, 'de>,
+    key_read: bool,
+}
+impl<'de> MapAccess<'de> for &mut MarkedArrayAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.key_read {
+            return Ok(None);
+        }
+        self.key_read = true;
+        let key = if self.array.kind == 11 {
+            INT_ARRAY
+        } else {
+            LONG_ARRAY
+        };
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(SeqAccessDeserializer::new(&mut self.array))
+    }
+}
+
+struct CompoundAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    /// The type of the value after the key that was just read.
+    kind: u8,
+    /// Set between reading a key and its value.
+    key: Option<&'de str>,
+    done: bool,
+}
+impl<'a, 'de> CompoundAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        CompoundAccess {
+            de,
+            kind: 0,
+            key: None,
+            done: false,
+        }
+    }
+    /// Skips what the visitor didn't read.
+    fn finish(self) -> Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        if self.key.is_some() {
+            self.de.skip(self.kind)?;
+        }
+        loop {
+            let kind = self.de.input.read_u8()?;
+            if kind == 0 {
+                return Ok(());
+            }
+            self.de.read_str()?;
+            self.de.skip(kind)?;
+        }
+    }
+}
+impl<'de> MapAccess<'de> for &mut CompoundAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.kind = self.de.input.read_u8()?;
+        if self.kind == 0 {
+            // tag_end
+            self.done = true;
+            return Ok(None);
+        }
+        let key = self.de.read_str()?;
+        self.key = Some(key);
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let kind = self.kind;
+        let Some(key) = self.key.take() else {
+            return Err(de::Error::custom("value read before its key"));
+        };
+        self.de.with_element(Segment::Key(key), |de| {
+            de.tag = kind;
+            seed.deserialize(de)
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.tag {
+            1 => visitor.visit_i8(self.input.read_i8()?),
+            2 => visitor.visit_i16(self.input.read_i16::<BE>()?),
+            3 => visitor.visit_i32(self.input.read_i32::<BE>()?),
+            4 => visitor.visit_i64(self.input.read_i64::<BE>()?),
+            5 => visitor.visit_f32(self.input.read_f32::<BE>()?),
+            6 => visitor.visit_f64(self.input.read_f64::<BE>()?),
+            7 => {
+                let size = self.read_size()?;
+                visitor.visit_borrowed_bytes(self.take(size)?)
+            }
+            8 => visitor.visit_borrowed_str(self.read_str()?),
+            9 => {
+                let kind = self.input.read_u8()?;
+                let size = self.read_size()?;
+                let mut access = ListAccess {
+                    de: self,
+                    kind,
+                    index: 0,
+                    size,
+                };
+                let result = visitor.visit_seq(&mut access)?;
+                access.finish()?;
+                Ok(result)
+            }
+            10 => {
+                let mut access = CompoundAccess::new(self);
+                let result = visitor.visit_map(&mut access)?;
+                access.finish()?;
+                Ok(result)
+            }
+            11 | 12 => {
+                let mut access = MarkedArrayAccess {
+                    array: ArrayAccess::new(self)?,
+                    key_read: false,
+                };
+                let result = visitor.visit_map(&mut access)?;
+                access.array.finish()?;
+                Ok(result)
+            }
+            x => Err(de::Error::custom(format!("unknown tag {}", x))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.tag {
+            1 => visitor.visit_bool(self.input.read_i8()? != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.tag {
+            7 | 11 | 12 => {
+                let mut access = ArrayAccess::new(self)?;
+                let result = visitor.visit_seq(&mut access)?;
+                access.finish()?;
+                Ok(result)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // nbt has no null, a missing key is a `None`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == INT_ARRAY || name == LONG_ARRAY {
+            return self.deserialize_seq(visitor);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.tag {
+            // unit variants
+            8 => visitor.visit_enum(BorrowedStrDeserializer::new(self.read_str()?)),
+            // `{Variant: value}`
+            10 => {
+                let mut access = CompoundAccess::new(self);
+                let result = visitor.visit_enum(MapAccessDeserializer::new(&mut access))?;
+                access.finish()?;
+                Ok(result)
+            }
+            x => Err(de::Error::custom(format!(
+                "expected a string or a compound for an enum, found tag {}",
+                x
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.skip(self.tag)?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier
+    }
+}
+
+/// Deserializes `T` from a binary root compound. With the [`Format::Named`] format
+/// the root name is skipped.
+pub fn from_slice<'de, T: Deserialize<'de>>(input: &'de [u8], format: Format) -> Result<T> {
+    let mut de = Deserializer {
+        input,
+        tag: 0,
+        path: Vec::new(),
+    };
+    de.tag = de.input.read_u8()?;
+    if de.tag != 10 {
+        return Err(de::Error::custom(format!(
+            "expected the stream to start with a compound tag, found {}",
+            de.tag
+        )));
+    }
+    if format == Format::Named {
+        de.read_str()?;
+    }
+    T::deserialize(&mut de)
+}
+
+// owned tags
+
+struct OwnedTagVisitor;
+
+impl<'de> Visitor<'de> for OwnedTagVisitor {
+    type Value = OwnedTag;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an nbt tag")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Byte(v as i8))
+    }
+    fn visit_i8<E: de::Error>(self, v: i8) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Byte(v))
+    }
+    fn visit_i16<E: de::Error>(self, v: i16) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Short(v))
+    }
+    fn visit_i32<E: de::Error>(self, v: i32) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Int(v))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Long(v))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<OwnedTag, E> {
+        i64::try_from(v)
+            .map(OwnedTag::Long)
+            .map_err(|_| E::custom(format!("{} doesn't fit in a long", v)))
+    }
+    fn visit_f32<E: de::Error>(self, v: f32) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Float(v))
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::Double(v))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::String(v.to_string()))
+    }
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::String(v))
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::ByteArray(v.to_vec()))
+    }
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<OwnedTag, E> {
+        Ok(OwnedTag::ByteArray(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<OwnedTag, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(x) = seq.next_element()? {
+            values.push(x);
+        }
+        Ok(OwnedTag::List(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<OwnedTag, A::Error> {
+        let mut result = Compound::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                INT_ARRAY => return Ok(OwnedTag::IntArray(map.next_value()?)),
+                LONG_ARRAY => return Ok(OwnedTag::LongArray(map.next_value()?)),
+                _ => {
+                    let value = map.next_value()?;
+                    result.insert(key, value);
+                }
+            }
+        }
+        Ok(OwnedTag::Compound(result))
+    }
+}
+
+impl<'de> Deserialize<'de> for OwnedTag {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(OwnedTagVisitor)
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use bumpalo::Bump;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use serde::Deserialize;
+
+use crate::nbt::owned::{OwnedRootTag, read_owned};
+use crate::nbt::write::write;
+use crate::nbt::{Format, RootTag, from_slice, read};
+
+/// How a standalone nbt file is compressed. `level.dat`, player data and
+/// structures are gzipped, a few files like `servers.dat` aren't compressed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zlib,
+}
+impl Compression {
+    /// Guesses the compression from the first bytes. Uncompressed nbt starts with
+    /// a compound tag, which can't be mistaken for a gzip or zlib header.
+    pub fn detect(data: &[u8]) -> Compression {
+        match data {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            // the zlib header is a multiple of 31, deflate with a 32k window starts with 0x78
+            [0x78, flags, ..] if (0x7800 | *flags as u16).is_multiple_of(31) => Compression::Zlib,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// The decompressed content of a standalone nbt file, and how it was compressed so
+/// it can be saved back the same way.
+#[derive(Debug)]
+pub struct NbtFile {
+    pub compression: Compression,
+    pub data: Vec<u8>,
+}
+impl NbtFile {
+    pub fn from_bytes(data: Vec<u8>) -> Result<NbtFile> {
+        let compression = Compression::detect(&data);
+        let data = match compression {
+            Compression::None => data,
+            Compression::Gzip => decompress(GzDecoder::new(&data[..]), data.len())?,
+            Compression::Zlib => decompress(ZlibDecoder::new(&data[..]), data.len())?,
+        };
+        Ok(NbtFile { compression, data })
+    }
+
+    pub fn read<'b>(&self, bump: &'b Bump) -> Result<RootTag<'b>> {
+        read(&self.data[..], bump)
+    }
+    pub fn read_owned(&self) -> Result<OwnedRootTag> {
+        read_owned(&self.data[..])
+    }
+    pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T> {
+        Ok(from_slice(&self.data, Format::Named)?)
+    }
+}
+
+fn decompress<R: Read>(mut reader: R, size: usize) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(size * 4);
+    reader.read_to_end(&mut result)?;
+    Ok(result)
+}
+
+/// Reads a standalone nbt file, decompressing it if needed.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<NbtFile> {
+    let path = path.as_ref();
+    let data = fs::read(path).with_context(|| format!("can't read {}", path.display()))?;
+    NbtFile::from_bytes(data).with_context(|| format!("can't decompress {}", path.display()))
+}
+
+/// Writes `root` as a standalone file, compressed with `compression`.
+pub fn write_compressed<W: Write>(
+    writer: W,
+    root: &OwnedRootTag,
+    compression: Compression,
+) -> Result<()> {
+    match compression {
+        Compression::None => write(writer, root),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            write(&mut encoder, root)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(writer, flate2::Compression::default());
+            write(&mut encoder, root)?;
+            encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// Saves `root` to `path`. Pass the [`NbtFile::compression`] the file was read
+/// with to keep it the same.
+pub fn write_file<P: AsRef<Path>>(
+    path: P,
+    root: &OwnedRootTag,
+    compression: Compression,
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut out = Vec::with_capacity(4096);
+    write_compressed(&mut out, root, compression)?;
+    fs::write(path, out).with_context(|| format!("can't write {}", path.display()))
+}
@@ -0,0 +1,678 @@
+mod de;
+mod file;
+mod owned;
+mod ser;
+mod snbt;
+mod write;
+
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::io::Read;
+use std::str;
+
+use anyhow::{Result, anyhow};
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BVec;
+use byteorder::{BE, ReadBytesExt};
+
+use crate::ReadSkip;
+pub use crate::nbt::de::{Deserializer, Error, from_slice};
+pub use crate::nbt::file::{Compression, NbtFile, read_file, write_compressed, write_file};
+pub use crate::nbt::owned::{Compound, OwnedRootTag, OwnedTag, read_owned, read_owned_network};
+pub use crate::nbt::ser::{IntArray, LongArray, to_tag, to_writer, to_writer_network};
+pub use crate::nbt::snbt::parse_snbt;
+pub use crate::nbt::write::{write, write_network};
+
+#[derive(Debug)]
+pub enum Tag<'n> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'n [u8]),
+    String(&'n str),
+    List(BVec<'n, Tag<'n>>),
+    Compound(HashMap<&'n str, Tag<'n>>),
+    // TODO: use HashMap with bumpalo?
+    IntArray(BVec<'n, i32>),
+    LongArray(BVec<'n, i64>),
+}
+
+#[derive(Debug)]
+pub struct RootTag<'n> {
+    pub name: &'n str,
+    pub tag: Tag<'n>,
+}
+
+/// How the root compound is laid out. Files, and the protocol before 1.20.2, give
+/// it a name; newer protocol versions leave the name out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Named,
+    Network,
+}
+impl Format {
+    pub fn read<R: Read>(self, reader: R, bump: &Bump) -> Result<RootTag<'_>> {
+        match self {
+            Format::Named => read(reader, bump),
+            Format::Network => read_network(reader, bump),
+        }
+    }
+    pub fn read_option<R: Read>(self, reader: R, bump: &Bump) -> Result<Option<RootTag<'_>>> {
+        read_option_impl(reader, self, bump)
+    }
+    pub fn skip<R: ReadSkip>(self, reader: R) -> Result<()> {
+        match self {
+            Format::Named => skip(reader),
+            Format::Network => skip_network(reader),
+        }
+    }
+    pub fn skip_option<R: ReadSkip>(self, reader: R) -> Result<bool> {
+        skip_option_impl(reader, self)
+    }
+}
+
+macro_rules! get_variant {
+    ($obj:expr, $var:ident) => {{
+        use Tag::*;
+        match $obj {
+            $var(x) => Ok(x),
+            _ => Err(anyhow!(
+                "expected tag {}, found {:?}",
+                $obj.tag_name(),
+                $obj
+            )),
+        }
+    }};
+}
+impl<'n> Tag<'n> {
+    fn tag_name(&self) -> &str {
+        match self {
+            Tag::Byte(_) => "byte",
+            Tag::Short(_) => "short",
+            Tag::Int(_) => "int",
+            Tag::Long(_) => "long",
+            Tag::Float(_) => "float",
+            Tag::Double(_) => "double",
+            Tag::ByteArray(_) => "byte_array",
+            Tag::String(_) => "string",
+            Tag::List(_) => "list",
+            Tag::Compound(_) => "compound",
+            Tag::IntArray(_) => "int_array",
+            Tag::LongArray(_) => "long_array",
+        }
+    }
+
+    pub fn compound(self) -> Result<HashMap<&'n str, Tag<'n>>> {
+        get_variant!(self, Compound)
+    }
+    pub fn list(self) -> Result<BVec<'n, Tag<'n>>> {
+        get_variant!(self, List)
+    }
+    pub fn string(self) -> Result<&'n str> {
+        get_variant!(self, String)
+    }
+    pub fn byte(self) -> Result<i8> {
+        get_variant!(self, Byte)
+    }
+    pub fn short(self) -> Result<i16> {
+        get_variant!(self, Short)
+    }
+    pub fn int(self) -> Result<i32> {
+        get_variant!(self, Int)
+    }
+}
+
+impl<'n> Display for RootTag<'n> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> FmtResult<()> {
+        f.write_str(&pretty_print(self)?)
+    }
+}
+
+// read
+
+fn read_string<'n, R: Read>(reader: &mut R, bump: &'n Bump) -> Result<&'n str> {
+    let size = reader.read_u16::<BE>()?;
+    let result = bump.alloc_slice_fill_default(size as usize);
+    reader.read_exact(result)?;
+    let s = str::from_utf8(result)?;
+    Ok(s)
+}
+
+fn read_impl<'n, R: Read>(mut reader: &mut R, tag: u8, bump: &'n Bump) -> Result<Tag<'n>> {
+    let result = match tag {
+        1 => Tag::Byte(reader.read_i8()?),
+        2 => Tag::Short(reader.read_i16::<BE>()?),
+        3 => Tag::Int(reader.read_i32::<BE>()?),
+        4 => Tag::Long(reader.read_i64::<BE>()?),
+        5 => Tag::Float(reader.read_f32::<BE>()?),
+        6 => Tag::Double(reader.read_f64::<BE>()?),
+        7 => {
+            let size = reader.read_i32::<BE>()? as usize;
+            let bytes = bump.alloc_slice_fill_default(size);
+            reader.read_exact(bytes)?;
+            Tag::ByteArray(bytes)
+        }
+        8 => Tag::String(read_string(&mut reader, bump)?),
+        9 => {
+            let kind = reader.read_u8()?;
+            let size = reader.read_i32::<BE>()? as usize;
+            let mut values = BVec::with_capacity_in(size, bump);
+            for _ in 0..size {
+                let v = read_impl(reader, kind, bump)?;
+                values.push(v);
+            }
+            Tag::List(values)
+        }
+        10 => {
+            let mut map = HashMap::new();
+            loop {
+                let kind = reader.read_u8()?;
+                if kind == 0 {
+                    // tag_end
+                    break;
+                }
+                let name = read_string(reader, bump)?;
+                let tag = read_impl(reader, kind, bump)?;
+                map.insert(name, tag);
+            }
+            Tag::Compound(map)
+        }
+        11 => {
+            let size = reader.read_i32::<BE>()? as usize;
+            let mut values = BVec::with_capacity_in(size, bump);
+            for _ in 0..size {
+                values.push(reader.read_i32::<BE>()?);
+            }
+            Tag::IntArray(values)
+        }
+        12 => {
+            let size = reader.read_i32::<BE>()? as usize;
+            let mut values = BVec::with_capacity_in(size, bump);
+            for _ in 0..size {
+                values.push(reader.read_i64::<BE>()?);
+            }
+            Tag::LongArray(values)
+        }
+        _ => return Err(anyhow!("unknown tag {}", tag)),
+    };
+    Ok(result)
+}
+
+fn check_root(tag: u8) -> Result<()> {
+    if tag != 10 {
+        return Err(anyhow!(
+            "expected the stream to start with a compound tag, found {}",
+            tag
+        ));
+    }
+    Ok(())
+}
+
+fn read_start<R: Read>(mut reader: R, tag: u8, format: Format, bump: &Bump) -> Result<RootTag<'_>> {
+    let reader = &mut reader;
+    check_root(tag)?;
+    let name = match format {
+        Format::Named => read_string(reader, bump)?,
+        Format::Network => "",
+    };
+    let tag = read_impl(reader, tag, bump)?;
+    Ok(RootTag { name, tag })
+}
+
+fn read_option_impl<R: Read>(
+    mut reader: R,
+    format: Format,
+    bump: &Bump,
+) -> Result<Option<RootTag<'_>>> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    if tag == 0 {
+        Ok(None)
+    } else {
+        let t = read_start(reader, tag, format, bump)?;
+        Ok(Some(t))
+    }
+}
+
+pub fn read_option<R: Read>(reader: R, bump: &Bump) -> Result<Option<RootTag<'_>>> {
+    read_option_impl(reader, Format::Named, bump)
+}
+
+pub fn read<R: Read>(mut reader: R, bump: &Bump) -> Result<RootTag<'_>> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    read_start(reader, tag, Format::Named, bump)
+}
+
+/// Reads a root compound without a name, as sent over the network since 1.20.2.
+/// The returned root has an empty name.
+pub fn read_network<R: Read>(mut reader: R, bump: &Bump) -> Result<RootTag<'_>> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    read_start(reader, tag, Format::Network, bump)
+}
+
+pub fn read_network_option<R: Read>(reader: R, bump: &Bump) -> Result<Option<RootTag<'_>>> {
+    read_option_impl(reader, Format::Network, bump)
+}
+
+// skip
+
+fn skip_string<R: ReadSkip>(reader: &mut R) -> Result<()> {
+    let size = reader.read_u16::<BE>()? as usize;
+    reader.skip_all(size)?;
+    Ok(())
+}
+
+fn skip_impl<R: ReadSkip>(reader: &mut R, tag: u8) -> Result<()> {
+    match tag {
+        1 => reader.skip_all(1)?, // tag_byte
+        2 => reader.skip_all(2)?, // tag_short
+        3 => reader.skip_all(4)?, // tag_int
+        4 => reader.skip_all(8)?, // tag_long
+        5 => reader.skip_all(4)?, // tag_float
+        6 => reader.skip_all(8)?, // tag_double
+        7 => {
+            // tag_byte_array
+            let size = reader.read_i32::<BE>()? as usize;
+            reader.skip_all(size)?;
+        }
+        8 => {
+            // tag_string
+            skip_string(reader)?;
+        }
+        9 => {
+            // tag_list
+            let kind = reader.read_u8()?;
+            let size = reader.read_i32::<BE>()?;
+            for _ in 0..size {
+                skip_impl(reader, kind)?;
+            }
+        }
+        10 => {
+            // tag_compound
+            loop {
+                let kind = reader.read_u8()?;
+                if kind == 0 {
+                    // tag_end
+                    break;
+                }
+                skip_string(reader)?;
+                skip_impl(reader, kind)?;
+            }
+        }
+        11 => {
+            // tag_int_array
+            let size = reader.read_i32::<BE>()?;
+            for _ in 0..size {
+                reader.skip_all(4)?;
+            }
+        }
+        12 => {
+            // tag_long_array
+            let size = reader.read_i32::<BE>()?;
+            for _ in 0..size {
+                reader.skip_all(8)?;
+            }
+        }
+        _ => return Err(anyhow!("unknown tag {}", tag)),
+    }
+    Ok(())
+}
+
+fn skip_start<R: ReadSkip>(reader: &mut R, tag: u8, format: Format) -> Result<()> {
+    check_root(tag)?;
+    if format == Format::Named {
+        skip_string(reader)?;
+    }
+    skip_impl(reader, tag)?;
+    Ok(())
+}
+
+fn skip_option_impl<R: ReadSkip>(mut reader: R, format: Format) -> Result<bool> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    let r = if tag == 0 {
+        false
+    } else {
+        skip_start(reader, tag, format)?;
+        true
+    };
+    Ok(r)
+}
+
+pub fn skip_option<R: ReadSkip>(reader: R) -> Result<bool> {
+    skip_option_impl(reader, Format::Named)
+}
+
+pub fn skip<R: ReadSkip>(mut reader: R) -> Result<()> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    skip_start(reader, tag, Format::Named)
+}
+
+/// Like [`skip`], for a root compound without a name.
+pub fn skip_network<R: ReadSkip>(mut reader: R) -> Result<()> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    skip_start(reader, tag, Format::Network)
+}
+
+pub fn skip_network_option<R: ReadSkip>(reader: R) -> Result<bool> {
+    skip_option_impl(reader, Format::Network)
+}
+
+// print
+
+fn print_indent(output: &mut String, indent: usize) {
+    let space = "                                                                ";
+    output.push_str(&space[..indent]);
+    // maybe don't have more than 64 spaces of indentation?
+}
+
+type FmtResult<T> = std::result::Result<T, std::fmt::Error>;
+
+fn print_compound(
+    output: &mut String,
+    tag: &Tag,
+    name: Option<&str>,
+    indent: usize,
+) -> FmtResult<()> {
+    let map = match tag {
+        Tag::Compound(x) => x,
+        _ => unreachable!(),
+    };
+    let entries = if map.len() == 1 { "entry" } else { "entries" };
+    match name {
+        Some(x) => write!(output, "'{}' -> {} {}", x, map.len(), entries)?,
+        None => write!(output, "{} {}", map.len(), entries)?,
+    }
+    *output += " {";
+
+    for (key, value) in map {
+        output.push('\n');
+        print_indent(output, indent + 4);
+        write!(output, "'{}' -> ", key)?;
+        print_impl(output, value, indent + 4)?;
+    }
+    output.push('\n');
+    print_indent(output, indent);
+    output.push('}');
+
+    Ok(())
+}
+
+fn print_impl(output: &mut String, tag: &Tag, indent: usize) -> FmtResult<()> {
+    match tag {
+        Tag::Byte(x) => write!(output, "{}b", x)?,
+        Tag::Short(x) => write!(output, "{}s", x)?,
+        Tag::Int(x) => write!(output, "{}i", x)?,
+        Tag::Long(x) => write!(output, "{}l", x)?,
+        Tag::Float(x) => write!(output, "{}f", x)?,
+        Tag::Double(x) => write!(output, "{}d", x)?,
+        Tag::ByteArray(x) => write!(output, "{} bytes", x.len())?,
+        Tag::String(x) => write!(output, "'{}'", x)?,
+        Tag::List(x) => {
+            if x.is_empty() {
+                *output += "[]";
+            } else {
+                *output += "[\n";
+                for i in x {
+                    print_indent(output, indent + 4);
+                    print_impl(output, i, indent + 4)?;
+                    output.push('\n');
+                }
+                print_indent(output, indent);
+                *output += "]";
+            }
+        }
+        Tag::Compound(_) => print_compound(output, tag, None, indent)?,
+        Tag::IntArray(x) => write!(output, "{} ints", x.len())?,
+        Tag::LongArray(x) => write!(output, "{} longs", x.len())?,
+    }
+    Ok(())
+}
+
+fn pretty_print(root: &RootTag) -> FmtResult<String> {
+    let mut result = String::with_capacity(4096);
+    print_compound(&mut result, &root.tag, Some(root.name), 0)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::nbt::{
+        Compression, Format, IntArray, NbtFile, OwnedRootTag, OwnedTag, from_slice, parse_snbt,
+        read, read_option, read_owned, read_owned_network, to_tag, to_writer, write,
+        write_compressed, write_network,
+    };
+
+    #[test]
+    fn hello_world() {
+        const DATA: &[u8] = include_bytes!("../../../tests/hello_world.nbt");
+        let bump = Bump::new();
+
+        let tag = read(DATA, &bump).unwrap();
+        let _ = tag.to_string();
+    }
+
+    #[test]
+    fn write_back() {
+        const DATA: &[u8] = include_bytes!("../../../tests/hello_world.nbt");
+        let bump = Bump::new();
+
+        let root = read_owned(DATA).unwrap();
+        assert_eq!(root, OwnedRootTag::from(&read(DATA, &bump).unwrap()));
+
+        let mut out = Vec::new();
+        write(&mut out, &root).unwrap();
+        assert_eq!(out, DATA);
+    }
+
+    #[test]
+    fn write_nameless() {
+        let mut compound = crate::nbt::Compound::new();
+        compound.insert("a".to_string(), OwnedTag::Short(7));
+        compound.insert("b".to_string(), OwnedTag::List(vec![]));
+
+        let mut out = Vec::new();
+        write_network(&mut out, &OwnedTag::Compound(compound)).unwrap();
+        assert_eq!(
+            out,
+            [10, 2, 0, 1, b'a', 0, 7, 9, 0, 1, b'b', 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn read_nameless() {
+        const DATA: &[u8] = &[10, 1, 0, 1, b'a', 7, 0];
+        let bump = Bump::new();
+
+        let root = Format::Network.read(DATA, &bump).unwrap();
+        assert_eq!(root.name, "");
+        let a = root.tag.compound().unwrap().remove("a").unwrap();
+        assert_eq!(a.byte().unwrap(), 7);
+
+        let mut rest = DATA;
+        Format::Network.skip(&mut rest).unwrap();
+        assert!(rest.is_empty());
+
+        // the same bytes make no sense with a root name
+        assert!(read(DATA, &bump).is_err());
+
+        let mut out = Vec::new();
+        write_network(&mut out, &read_owned_network(DATA).unwrap()).unwrap();
+        assert_eq!(out, DATA);
+    }
+
+    #[test]
+    fn snbt() {
+        const TEXT: &str = r#"{Count: 1b, id: "minecraft:stone", tag: {Damage: 3s, 'a"b': 'it\'s',
+            Pos: [1.5d, -2.0d], Ids: [I; 1, -2], Seen: [L; 3L], Raw: [B; 1b, 2b], on: true,
+            Time: 100L, Speed: 0.5f, Name: stone, UUID: 3.0}}"#;
+        let bump = Bump::new();
+
+        let tag = parse_snbt(TEXT, &bump).unwrap();
+        let printed = tag.to_snbt();
+        assert_eq!(
+            printed,
+            r#"{Count:1b,id:"minecraft:stone",tag:{Damage:3s,Ids:[I;1,-2],Name:"stone",Pos:[1.5d,-2.0d],Raw:[B;1b,2b],Seen:[L;3L],Speed:0.5f,Time:100L,UUID:3.0d,'a"b':"it's",on:1b}}"#
+        );
+        assert_eq!(parse_snbt(&printed, &bump).unwrap().to_snbt(), printed);
+
+        let tag = parse_snbt("[1e5, 2.5E-1, -1e3]", &bump).unwrap();
+        assert_eq!(tag.to_snbt(), "[100000.0d,0.25d,-1000.0d]");
+        let tag = parse_snbt("[1e5f, NaNf, Infinityf, -Infinityf]", &bump).unwrap();
+        let printed = tag.to_snbt();
+        assert_eq!(printed, "[100000.0f,NaNf,Infinityf,-Infinityf]");
+        assert_eq!(parse_snbt(&printed, &bump).unwrap().to_snbt(), printed);
+        let tag = parse_snbt("[NaNd, -Infinityd]", &bump).unwrap();
+        assert_eq!(tag.to_snbt(), "[NaNd,-Infinityd]");
+        // without a suffix they're strings, like in the game
+        let tag = parse_snbt("[NaN, e, inf]", &bump).unwrap();
+        assert_eq!(tag.to_snbt(), r#"["NaN","e","inf"]"#);
+    }
+
+    #[test]
+    fn snbt_errors() {
+        let bump = Bump::new();
+
+        assert!(parse_snbt("[1b, 2s]", &bump).is_err());
+        assert!(parse_snbt("[B; 1000]", &bump).is_err());
+        assert!(parse_snbt("{a: 1", &bump).is_err());
+        assert!(parse_snbt("{a: 1} b", &bump).is_err());
+        assert!(parse_snbt("\"abc", &bump).is_err());
+    }
+
+    #[test]
+    fn hello_world_snbt() {
+        const DATA: &[u8] = include_bytes!("../../../tests/hello_world.nbt");
+        let bump = Bump::new();
+
+        let root = read(DATA, &bump).unwrap();
+        assert_eq!(root.to_snbt(), r#"{name:"Bananrama"}"#);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item<'x> {
+        id: &'x str,
+        #[serde(rename = "Count")]
+        count: u8,
+        tag: Option<OwnedTag>,
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct BlockEntity<'x> {
+        #[serde(borrow, rename = "Items")]
+        items: Vec<Item<'x>>,
+        #[serde(rename = "UUID")]
+        uuid: IntArray,
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Chunk<'x> {
+        #[serde(rename = "DataVersion")]
+        data_version: i32,
+        #[serde(borrow)]
+        block_entities: Vec<BlockEntity<'x>>,
+    }
+
+    #[test]
+    fn serde() {
+        let item = |id, count| Item {
+            id,
+            count,
+            tag: None,
+        };
+        let chunk = Chunk {
+            data_version: 3465,
+            block_entities: vec![BlockEntity {
+                items: vec![
+                    item("minecraft:stone", 64),
+                    Item {
+                        tag: Some(OwnedTag::Compound(crate::nbt::Compound::from([(
+                            "Damage".to_string(),
+                            OwnedTag::Int(3),
+                        )]))),
+                        ..item("minecraft:iron_pickaxe", 1)
+                    },
+                ],
+                uuid: IntArray(vec![1, 2, 3, 4]),
+            }],
+        };
+
+        let mut out = Vec::new();
+        to_writer(&mut out, "", &chunk).unwrap();
+        let back: Chunk = from_slice(&out, Format::Named).unwrap();
+        assert_eq!(back, chunk);
+
+        // the same bytes, through the owned tree
+        let tag: OwnedTag = from_slice(&out, Format::Named).unwrap();
+        assert_eq!(tag, read_owned(&out[..]).unwrap().tag);
+        assert_eq!(to_tag(&tag).unwrap(), tag);
+    }
+
+    #[test]
+    fn serde_error_path() {
+        let bump = Bump::new();
+        let item = r#"{id: "minecraft:stone", Count: 1b}"#;
+        let text = format!(
+            r#"{{DataVersion: 1, block_entities: [{{Items: [], UUID: [I; 1]}},
+                {{Items: [{item}, {item}, {{id: "minecraft:dirt", Count: "many"}}], UUID: [I; 1]}}]}}"#
+        );
+        let tag = OwnedTag::from(&parse_snbt(&text, &bump).unwrap());
+        let mut out = Vec::new();
+        write_network(&mut out, &tag).unwrap();
+
+        let e = from_slice::<Chunk>(&out, Format::Network).unwrap_err();
+        assert_eq!(e.path(), Some("block_entities[1].Items[2].Count"));
+
+        let all: OwnedTag = from_slice(&out, Format::Network).unwrap();
+        assert_eq!(all, tag);
+    }
+
+    #[test]
+    fn serde_borrow_bytes() {
+        #[derive(Deserialize)]
+        struct Raw<'x> {
+            raw: &'x [u8],
+        }
+        let bump = Bump::new();
+        let tag = OwnedTag::from(&parse_snbt("{raw: [B; 1b, 2b]}", &bump).unwrap());
+        let mut out = Vec::new();
+        write_network(&mut out, &tag).unwrap();
+
+        let raw: Raw = from_slice(&out, Format::Network).unwrap();
+        assert_eq!(raw.raw, [1, 2]);
+        assert_eq!(raw.raw.as_ptr(), out[out.len() - 3..].as_ptr());
+    }
+
+    #[test]
+    fn compression() {
+        const DATA: &[u8] = include_bytes!("../../../tests/hello_world.nbt");
+        let root = read_owned(DATA).unwrap();
+
+        for compression in [Compression::None, Compression::Gzip, Compression::Zlib] {
+            let mut out = Vec::new();
+            write_compressed(&mut out, &root, compression).unwrap();
+
+            let file = NbtFile::from_bytes(out).unwrap();
+            assert_eq!(file.compression, compression);
+            assert_eq!(file.data, DATA);
+            assert_eq!(file.read_owned().unwrap(), root);
+        }
+    }
+
+    #[test]
+    fn option() {
+        const DATA: &[u8] = &[0];
+        let bump = Bump::new();
+
+        let tag = read_option(DATA, &bump).unwrap();
+        assert!(tag.is_none());
+    }
+}
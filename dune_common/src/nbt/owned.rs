@@ -0,0 +1,257 @@
+use std::io::Read;
+
+use anyhow::{Result, anyhow};
+use byteorder::{BE, ReadBytesExt};
+use indexmap::IndexMap;
+
+use crate::nbt::{RootTag, Tag};
+
+pub type Compound = IndexMap<String, OwnedTag>;
+
+/// An owned, editable version of [`Tag`]. Compounds keep the order their keys were
+/// read in, so a file can be written back the way it was.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<OwnedTag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedRootTag {
+    pub name: String,
+    pub tag: OwnedTag,
+}
+
+macro_rules! get_variant {
+    ($obj:expr, $var:ident) => {{
+        use OwnedTag::*;
+        match $obj {
+            $var(x) => Ok(x),
+            _ => Err(anyhow!(
+                "expected tag {}, found {}",
+                stringify!($var),
+                $obj.tag_name()
+            )),
+        }
+    }};
+}
+impl OwnedTag {
+    pub fn id(&self) -> u8 {
+        match self {
+            OwnedTag::Byte(_) => 1,
+            OwnedTag::Short(_) => 2,
+            OwnedTag::Int(_) => 3,
+            OwnedTag::Long(_) => 4,
+            OwnedTag::Float(_) => 5,
+            OwnedTag::Double(_) => 6,
+            OwnedTag::ByteArray(_) => 7,
+            OwnedTag::String(_) => 8,
+            OwnedTag::List(_) => 9,
+            OwnedTag::Compound(_) => 10,
+            OwnedTag::IntArray(_) => 11,
+            OwnedTag::LongArray(_) => 12,
+        }
+    }
+    fn tag_name(&self) -> &str {
+        match self {
+            OwnedTag::Byte(_) => "byte",
+            OwnedTag::Short(_) => "short",
+            OwnedTag::Int(_) => "int",
+            OwnedTag::Long(_) => "long",
+            OwnedTag::Float(_) => "float",
+            OwnedTag::Double(_) => "double",
+            OwnedTag::ByteArray(_) => "byte_array",
+            OwnedTag::String(_) => "string",
+            OwnedTag::List(_) => "list",
+            OwnedTag::Compound(_) => "compound",
+            OwnedTag::IntArray(_) => "int_array",
+            OwnedTag::LongArray(_) => "long_array",
+        }
+    }
+
+    pub fn compound(&self) -> Result<&Compound> {
+        get_variant!(self, Compound)
+    }
+    pub fn compound_mut(&mut self) -> Result<&mut Compound> {
+        get_variant!(self, Compound)
+    }
+    pub fn list(&self) -> Result<&Vec<OwnedTag>> {
+        get_variant!(self, List)
+    }
+    pub fn list_mut(&mut self) -> Result<&mut Vec<OwnedTag>> {
+        get_variant!(self, List)
+    }
+    pub fn string(&self) -> Result<&str> {
+        get_variant!(self, String).map(|x| x.as_str())
+    }
+    pub fn byte(&self) -> Result<i8> {
+        get_variant!(self, Byte).copied()
+    }
+    pub fn short(&self) -> Result<i16> {
+        get_variant!(self, Short).copied()
+    }
+    pub fn int(&self) -> Result<i32> {
+        get_variant!(self, Int).copied()
+    }
+    pub fn long(&self) -> Result<i64> {
+        get_variant!(self, Long).copied()
+    }
+    pub fn float(&self) -> Result<f32> {
+        get_variant!(self, Float).copied()
+    }
+    pub fn double(&self) -> Result<f64> {
+        get_variant!(self, Double).copied()
+    }
+
+    /// Looks up `key` if this is a compound.
+    pub fn get(&self, key: &str) -> Option<&OwnedTag> {
+        match self {
+            OwnedTag::Compound(x) => x.get(key),
+            _ => None,
+        }
+    }
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut OwnedTag> {
+        match self {
+            OwnedTag::Compound(x) => x.get_mut(key),
+            _ => None,
+        }
+    }
+}
+
+impl<'n> From<&Tag<'n>> for OwnedTag {
+    fn from(tag: &Tag<'n>) -> Self {
+        match tag {
+            Tag::Byte(x) => OwnedTag::Byte(*x),
+            Tag::Short(x) => OwnedTag::Short(*x),
+            Tag::Int(x) => OwnedTag::Int(*x),
+            Tag::Long(x) => OwnedTag::Long(*x),
+            Tag::Float(x) => OwnedTag::Float(*x),
+            Tag::Double(x) => OwnedTag::Double(*x),
+            Tag::ByteArray(x) => OwnedTag::ByteArray(x.to_vec()),
+            Tag::String(x) => OwnedTag::String(x.to_string()),
+            Tag::List(x) => OwnedTag::List(x.iter().map(OwnedTag::from).collect()),
+            Tag::Compound(x) => OwnedTag::Compound(
+                x.iter()
+                    .map(|(k, v)| (k.to_string(), OwnedTag::from(v)))
+                    .collect(),
+            ),
+            Tag::IntArray(x) => OwnedTag::IntArray(x.to_vec()),
+            Tag::LongArray(x) => OwnedTag::LongArray(x.to_vec()),
+        }
+    }
+}
+impl<'n> From<&RootTag<'n>> for OwnedRootTag {
+    fn from(root: &RootTag<'n>) -> Self {
+        OwnedRootTag {
+            name: root.name.to_string(),
+            tag: OwnedTag::from(&root.tag),
+        }
+    }
+}
+
+// read
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let size = reader.read_u16::<BE>()?;
+    let mut result = vec![0; size as usize];
+    reader.read_exact(&mut result)?;
+    Ok(String::from_utf8(result)?)
+}
+
+fn read_impl<R: Read>(reader: &mut R, tag: u8) -> Result<OwnedTag> {
+    let result = match tag {
+        1 => OwnedTag::Byte(reader.read_i8()?),
+        2 => OwnedTag::Short(reader.read_i16::<BE>()?),
+        3 => OwnedTag::Int(reader.read_i32::<BE>()?),
+        4 => OwnedTag::Long(reader.read_i64::<BE>()?),
+        5 => OwnedTag::Float(reader.read_f32::<BE>()?),
+        6 => OwnedTag::Double(reader.read_f64::<BE>()?),
+        7 => {
+            let size = reader.read_i32::<BE>()?.max(0) as usize;
+            let mut bytes = vec![0; size];
+            reader.read_exact(&mut bytes)?;
+            OwnedTag::ByteArray(bytes)
+        }
+        8 => OwnedTag::String(read_string(reader)?),
+        9 => {
+            let kind = reader.read_u8()?;
+            let size = reader.read_i32::<BE>()?.max(0) as usize;
+            let mut values = Vec::with_capacity(size.min(4096));
+            for _ in 0..size {
+                values.push(read_impl(reader, kind)?);
+            }
+            OwnedTag::List(values)
+        }
+        10 => {
+            let mut map = Compound::new();
+            loop {
+                let kind = reader.read_u8()?;
+                if kind == 0 {
+                    // tag_end
+                    break;
+                }
+                let name = read_string(reader)?;
+                let tag = read_impl(reader, kind)?;
+                map.insert(name, tag);
+            }
+            OwnedTag::Compound(map)
+        }
+        11 => {
+            let size = reader.read_i32::<BE>()?.max(0) as usize;
+            let mut values = Vec::with_capacity(size.min(4096));
+            for _ in 0..size {
+                values.push(reader.read_i32::<BE>()?);
+            }
+            OwnedTag::IntArray(values)
+        }
+        12 => {
+            let size = reader.read_i32::<BE>()?.max(0) as usize;
+            let mut values = Vec::with_capacity(size.min(4096));
+            for _ in 0..size {
+                values.push(reader.read_i64::<BE>()?);
+            }
+            OwnedTag::LongArray(values)
+        }
+        _ => return Err(anyhow!("unknown tag {}", tag)),
+    };
+    Ok(result)
+}
+
+fn check_root(tag: u8) -> Result<()> {
+    if tag != 10 {
+        return Err(anyhow!(
+            "expected the stream to start with a compound tag, found {}",
+            tag
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a named root compound, like [`read`](crate::nbt::read) but into an owned tree.
+pub fn read_owned<R: Read>(mut reader: R) -> Result<OwnedRootTag> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    check_root(tag)?;
+    let name = read_string(reader)?;
+    let tag = read_impl(reader, tag)?;
+    Ok(OwnedRootTag { name, tag })
+}
+
+/// Reads a root compound without a name, the counterpart of
+/// [`write_network`](crate::nbt::write_network).
+pub fn read_owned_network<R: Read>(mut reader: R) -> Result<OwnedTag> {
+    let reader = &mut reader;
+    let tag = reader.read_u8()?;
+    check_root(tag)?;
+    read_impl(reader, tag)
+}
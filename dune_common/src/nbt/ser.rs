@@ -0,0 +1,578 @@
+use std::fmt;
+use std::io::Write;
+
+use serde::de::{SeqAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use serde::{Deserialize, Deserializer};
+
+use crate::nbt::de::{Error, INT_ARRAY, LONG_ARRAY};
+use crate::nbt::owned::{Compound, OwnedRootTag, OwnedTag};
+use crate::nbt::write::{write, write_network};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// An int array, like `[I; 1, 2]`. A plain `Vec<i32>` is written as a list of ints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntArray(pub Vec<i32>);
+/// A long array, like `[L; 1L, 2L]`. A plain `Vec<i64>` is written as a list of longs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LongArray(pub Vec<i64>);
+
+macro_rules! impl_array {
+    ($name:ident, $t:ty, $marker:ident) => {
+        impl Serialize for $name {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($marker, &self.0)
+            }
+        }
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct ArrayVisitor;
+                impl<'de> Visitor<'de> for ArrayVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str(stringify!($name))
+                    }
+                    fn visit_newtype_struct<D: Deserializer<'de>>(
+                        self,
+                        deserializer: D,
+                    ) -> std::result::Result<$name, D::Error> {
+                        Vec::deserialize(deserializer).map($name)
+                    }
+                    fn visit_seq<A: SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> std::result::Result<$name, A::Error> {
+                        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                        while let Some(x) = seq.next_element::<$t>()? {
+                            values.push(x);
+                        }
+                        Ok($name(values))
+                    }
+                }
+                deserializer.deserialize_newtype_struct($marker, ArrayVisitor)
+            }
+        }
+    };
+}
+impl_array!(IntArray, i32, INT_ARRAY);
+impl_array!(LongArray, i64, LONG_ARRAY);
+
+/// Builds an [`OwnedTag`] out of a value. `None`s and units give nothing, so
+/// optional fields are left out of their compound.
+struct Serializer;
+
+fn unsigned<T, U: TryFrom<T>>(value: T, f: impl FnOnce(U) -> OwnedTag) -> Result<Option<OwnedTag>>
+where
+    T: Copy + fmt::Display,
+{
+    match U::try_from(value) {
+        Ok(x) => Ok(Some(f(x))),
+        Err(_) => Err(ser::Error::custom(format!(
+            "{} is too big for a signed nbt number",
+            value
+        ))),
+    }
+}
+
+fn required(tag: Option<OwnedTag>) -> Result<OwnedTag> {
+    tag.ok_or_else(|| ser::Error::custom("a value with nothing to write can't go in a list"))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Byte(v as i8)))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Byte(v)))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Short(v)))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Int(v)))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Long(v)))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        unsigned(v, OwnedTag::Byte)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        unsigned(v, OwnedTag::Short)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        unsigned(v, OwnedTag::Int)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        unsigned(v, OwnedTag::Long)
+    }
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Float(v)))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::Double(v)))
+    }
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::String(v.to_string())))
+    }
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::String(v.to_string())))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::ByteArray(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Ok(None)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Ok(Some(OwnedTag::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let tag = value.serialize(self)?;
+        if name != INT_ARRAY && name != LONG_ARRAY {
+            return Ok(tag);
+        }
+        let Some(OwnedTag::List(list)) = tag else {
+            return Err(ser::Error::custom("expected a list for an array"));
+        };
+        let result = if name == INT_ARRAY {
+            list.iter()
+                .map(OwnedTag::int)
+                .collect::<anyhow::Result<_>>()
+                .map(OwnedTag::IntArray)
+        } else {
+            list.iter()
+                .map(OwnedTag::long)
+                .collect::<anyhow::Result<_>>()
+                .map(OwnedTag::LongArray)
+        };
+        result.map(Some).map_err(ser::Error::custom)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let mut compound = Compound::new();
+        if let Some(tag) = value.serialize(self)? {
+            compound.insert(variant.to_string(), tag);
+        }
+        Ok(Some(OwnedTag::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            key: None,
+            variant: None,
+        })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wraps `tag` in `{variant: tag}` for enum variants.
+fn in_variant(tag: OwnedTag, variant: Option<&'static str>) -> OwnedTag {
+    match variant {
+        Some(variant) => {
+            let mut compound = Compound::new();
+            compound.insert(variant.to_string(), tag);
+            OwnedTag::Compound(compound)
+        }
+        None => tag,
+    }
+}
+
+struct SerializeList {
+    values: Vec<OwnedTag>,
+    variant: Option<&'static str>,
+}
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.values.push(required(value.serialize(Serializer)?)?);
+        Ok(())
+    }
+    fn finish(self) -> Result<Option<OwnedTag>> {
+        Ok(Some(in_variant(OwnedTag::List(self.values), self.variant)))
+    }
+}
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+struct SerializeCompound {
+    compound: Compound,
+    /// The key given to `serialize_key`, waiting for its value.
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+impl SerializeCompound {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.compound.insert(key, tag);
+        }
+        Ok(())
+    }
+    fn finish(self) -> Result<Option<OwnedTag>> {
+        Ok(Some(in_variant(
+            OwnedTag::Compound(self.compound),
+            self.variant,
+        )))
+    }
+}
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("value serialized before its key"))?;
+        self.insert(key, value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+impl ser::SerializeStructVariant for SerializeCompound {
+    type Ok = Option<OwnedTag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+/// Compound keys can only be strings.
+struct KeySerializer;
+
+fn key_error() -> Error {
+    ser::Error::custom("nbt compound keys have to be strings")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<String> {
+        Ok(v.to_string())
+    }
+    fn serialize_char(self, v: char) -> Result<String> {
+        Ok(v.to_string())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.to_string())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_i8(self, _v: i8) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_i16(self, _v: i16) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_i32(self, _v: i32) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_i64(self, _v: i64) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_u8(self, _v: u8) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_u16(self, _v: u16) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_u32(self, _v: u32) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_u64(self, _v: u64) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_none(self) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_unit(self) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        Err(key_error())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_error())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_error())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_error())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_error())
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_error())
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_error())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_error())
+    }
+}
+
+/// Turns `value` into a tag, to edit it or write it with [`write`].
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<OwnedTag> {
+    value
+        .serialize(Serializer)?
+        .ok_or_else(|| ser::Error::custom("the value has nothing to write"))
+}
+
+/// Writes `value` as a named root compound, the way it's stored in files.
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    name: &str,
+    value: &T,
+) -> anyhow::Result<()> {
+    let root = OwnedRootTag {
+        name: name.to_string(),
+        tag: to_tag(value)?,
+    };
+    write(writer, &root)
+}
+
+/// Writes `value` as a root compound without a name, like it's sent over the network.
+pub fn to_writer_network<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+) -> anyhow::Result<()> {
+    write_network(writer, &to_tag(value)?)
+}
+
+impl Serialize for OwnedTag {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            OwnedTag::Byte(x) => serializer.serialize_i8(*x),
+            OwnedTag::Short(x) => serializer.serialize_i16(*x),
+            OwnedTag::Int(x) => serializer.serialize_i32(*x),
+            OwnedTag::Long(x) => serializer.serialize_i64(*x),
+            OwnedTag::Float(x) => serializer.serialize_f32(*x),
+            OwnedTag::Double(x) => serializer.serialize_f64(*x),
+            OwnedTag::ByteArray(x) => serializer.serialize_bytes(x),
+            OwnedTag::String(x) => serializer.serialize_str(x),
+            OwnedTag::List(x) => x.serialize(serializer),
+            OwnedTag::Compound(x) => x.serialize(serializer),
+            OwnedTag::IntArray(x) => serializer.serialize_newtype_struct(INT_ARRAY, x),
+            OwnedTag::LongArray(x) => serializer.serialize_newtype_struct(LONG_ARRAY, x),
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::{Result, anyhow};
+use bumpalo::Bump;
+use bumpalo::collections::{String as BString, Vec as BVec};
+
+use crate::nbt::{RootTag, Tag};
+
+// print
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+fn print_string(output: &mut String, s: &str) {
+    // like the game, prefer double quotes unless the string has some in it
+    let quote = if s.contains('"') && !s.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    output.push(quote);
+    for c in s.chars() {
+        if c == quote || c == '\\' {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output.push(quote);
+}
+
+fn print_key(output: &mut String, key: &str) {
+    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+        *output += key;
+    } else {
+        print_string(output, key);
+    }
+}
+
+fn print_array<T: Copy>(
+    output: &mut String,
+    prefix: &str,
+    values: &[T],
+    f: impl Fn(&mut String, T),
+) {
+    *output += prefix;
+    for (index, x) in values.iter().enumerate() {
+        if index != 0 {
+            output.push(',');
+        }
+        f(output, *x);
+    }
+    output.push(']');
+}
+
+/// Spelled like Java does, which is how the game writes them.
+fn non_finite(x: f64) -> Option<&'static str> {
+    if x.is_nan() {
+        Some("NaN")
+    } else if x == f64::INFINITY {
+        Some("Infinity")
+    } else if x == f64::NEG_INFINITY {
+        Some("-Infinity")
+    } else {
+        None
+    }
+}
+
+fn print_impl(output: &mut String, tag: &Tag) {
+    // writing to a `String` doesn't fail
+    match tag {
+        Tag::Byte(x) => write!(output, "{}b", x).unwrap(),
+        Tag::Short(x) => write!(output, "{}s", x).unwrap(),
+        Tag::Int(x) => write!(output, "{}", x).unwrap(),
+        Tag::Long(x) => write!(output, "{}L", x).unwrap(),
+        Tag::Float(x) => match non_finite(*x as f64) {
+            Some(name) => write!(output, "{}f", name).unwrap(),
+            None => write!(output, "{:?}f", x).unwrap(),
+        },
+        Tag::Double(x) => match non_finite(*x) {
+            Some(name) => write!(output, "{}d", name).unwrap(),
+            None => write!(output, "{:?}d", x).unwrap(),
+        },
+        Tag::ByteArray(x) => {
+            print_array(output, "[B;", x, |o, x| write!(o, "{}b", x as i8).unwrap())
+        }
+        Tag::String(x) => print_string(output, x),
+        Tag::List(x) => {
+            output.push('[');
+            for (index, i) in x.iter().enumerate() {
+                if index != 0 {
+                    output.push(',');
+                }
+                print_impl(output, i);
+            }
+            output.push(']');
+        }
+        Tag::Compound(x) => {
+            // sorted, so the same compound always prints the same
+            let mut entries: Vec<_> = x.iter().collect();
+            entries.sort_unstable_by_key(|x| *x.0);
+
+            output.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index != 0 {
+                    output.push(',');
+                }
+                print_key(output, key);
+                output.push(':');
+                print_impl(output, value);
+            }
+            output.push('}');
+        }
+        Tag::IntArray(x) => print_array(output, "[I;", x, |o, x| write!(o, "{}", x).unwrap()),
+        Tag::LongArray(x) => print_array(output, "[L;", x, |o, x| write!(o, "{}L", x).unwrap()),
+    }
+}
+
+impl<'n> Tag<'n> {
+    /// Prints the tag as SNBT, the text format used in commands.
+    pub fn to_snbt(&self) -> String {
+        let mut result = String::new();
+        print_impl(&mut result, self);
+        result
+    }
+}
+impl<'n> RootTag<'n> {
+    /// Prints the root compound as SNBT. SNBT has no place for the root name, so
+    /// it's left out.
+    pub fn to_snbt(&self) -> String {
+        self.tag.to_snbt()
+    }
+}
+
+// parse
+
+struct Parser<'s, 'n> {
+    input: &'s str,
+    pos: usize,
+    bump: &'n Bump,
+}
+
+impl<'s, 'n> Parser<'s, 'n> {
+    fn error<T>(&self, message: &str) -> Result<T> {
+        Err(anyhow!("{} at position {} in snbt", message, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input[self.pos..].chars().next()
+    }
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.peek() != Some(c) {
+            return self.error(&format!("expected '{}'", c));
+        }
+        self.pos += c.len_utf8();
+        Ok(())
+    }
+    /// Eats a `,` and tells if there's another element before `end`.
+    fn next_element(&mut self, end: char) -> Result<bool> {
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(c) if c == end => Ok(false),
+            _ => self.error(&format!("expected ',' or '{}'", end)),
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<&'n str> {
+        // the opening quote
+        self.pos += 1;
+        let mut result = BString::new_in(self.bump);
+        let mut chars = self.input[self.pos..].char_indices();
+        loop {
+            let Some((index, c)) = chars.next() else {
+                return self.error("unterminated string");
+            };
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c @ ('\\' | '"' | '\''))) => result.push(c),
+                    Some((_, 'n')) => result.push('\n'),
+                    Some((_, 't')) => result.push('\t'),
+                    _ => {
+                        self.pos += index;
+                        return self.error("invalid escape");
+                    }
+                },
+                c if c == quote => {
+                    self.pos += index + 1;
+                    return Ok(result.into_bump_str());
+                }
+                c => result.push(c),
+            }
+        }
+    }
+    fn unquoted(&mut self) -> Result<&'s str> {
+        let rest = &self.input[self.pos..];
+        let size = rest.find(|c| !is_unquoted_char(c)).unwrap_or(rest.len());
+        if size == 0 {
+            return self.error("expected a value");
+        }
+        self.pos += size;
+        Ok(&rest[..size])
+    }
+    fn key(&mut self) -> Result<&'n str> {
+        match self.peek() {
+            Some(c @ ('"' | '\'')) => self.quoted(c),
+            _ => Ok(self.bump.alloc_str(self.unquoted()?)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Tag<'n>> {
+        match self.peek() {
+            Some('{') => self.compound(),
+            Some('[') => self.list(),
+            Some(c @ ('"' | '\'')) => Ok(Tag::String(self.quoted(c)?)),
+            _ => {
+                let token = self.unquoted()?;
+                Ok(number(token).unwrap_or_else(|| Tag::String(self.bump.alloc_str(token))))
+            }
+        }
+    }
+
+    fn compound(&mut self) -> Result<Tag<'n>> {
+        self.expect('{')?;
+        let mut map = HashMap::new();
+        if self.peek() != Some('}') {
+            loop {
+                let key = self.key()?;
+                self.expect(':')?;
+                let value = self.value()?;
+                map.insert(key, value);
+                if !self.next_element('}')? {
+                    break;
+                }
+            }
+        }
+        self.expect('}')?;
+        Ok(Tag::Compound(map))
+    }
+
+    fn list(&mut self) -> Result<Tag<'n>> {
+        self.expect('[')?;
+        let rest = &self.input.as_bytes()[self.pos..];
+        if let [kind @ (b'B' | b'I' | b'L'), b';', ..] = rest {
+            self.pos += 2;
+            return self.array(*kind);
+        }
+
+        let mut values = BVec::new_in(self.bump);
+        if self.peek() != Some(']') {
+            loop {
+                let value = self.value()?;
+                if let Some(first) = values.first()
+                    && !same_type(first, &value)
+                {
+                    return self.error(&format!(
+                        "list of {} can't contain {}",
+                        first.tag_name(),
+                        value.tag_name()
+                    ));
+                }
+                values.push(value);
+                if !self.next_element(']')? {
+                    break;
+                }
+            }
+        }
+        self.expect(']')?;
+        Ok(Tag::List(values))
+    }
+
+    fn array(&mut self, kind: u8) -> Result<Tag<'n>> {
+        let mut values = Vec::new();
+        if self.peek() != Some(']') {
+            loop {
+                let value = match self.value()? {
+                    Tag::Byte(x) => x as i64,
+                    Tag::Short(x) => x as i64,
+                    Tag::Int(x) => x as i64,
+                    Tag::Long(x) => x,
+                    x => return self.error(&format!("arrays can't contain {}", x.tag_name())),
+                };
+                values.push(value);
+                if !self.next_element(']')? {
+                    break;
+                }
+            }
+        }
+        self.expect(']')?;
+
+        let out_of_range = |x: i64| anyhow!("{} doesn't fit in a [{};] array", x, kind as char);
+        let tag = match kind {
+            b'B' => {
+                let mut bytes = BVec::with_capacity_in(values.len(), self.bump);
+                for x in values {
+                    bytes.push(i8::try_from(x).map_err(|_| out_of_range(x))? as u8);
+                }
+                Tag::ByteArray(bytes.into_bump_slice())
+            }
+            b'I' => {
+                let mut ints = BVec::with_capacity_in(values.len(), self.bump);
+                for x in values {
+                    ints.push(i32::try_from(x).map_err(|_| out_of_range(x))?);
+                }
+                Tag::IntArray(ints)
+            }
+            _ => Tag::LongArray(BVec::from_iter_in(values, self.bump)),
+        };
+        Ok(tag)
+    }
+}
+
+fn same_type(a: &Tag, b: &Tag) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Reads an unquoted token as a number, `None` means it's a string.
+fn number<'n>(token: &str) -> Option<Tag<'n>> {
+    match token {
+        "true" => return Some(Tag::Byte(1)),
+        "false" => return Some(Tag::Byte(0)),
+        _ => {}
+    }
+    let (value, suffix) = match token.char_indices().last()? {
+        (index, c) if c.is_ascii_alphabetic() && !matches!(c, 'e' | 'E') => {
+            (&token[..index], Some(c.to_ascii_lowercase()))
+        }
+        _ => (token, None),
+    };
+    // only with a suffix, without one they're strings
+    let special = match value {
+        "NaN" => Some(f64::NAN),
+        "Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    };
+    if let Some(x) = special {
+        return match suffix {
+            Some('f') => Some(Tag::Float(x as f32)),
+            Some('d') => Some(Tag::Double(x)),
+            _ => None,
+        };
+    }
+    // `parse` also takes things like "inf" and "NaN", which are strings in snbt
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'))
+    {
+        return None;
+    }
+
+    let tag = match suffix {
+        Some('b') => Tag::Byte(value.parse().ok()?),
+        Some('s') => Tag::Short(value.parse().ok()?),
+        Some('l') => Tag::Long(value.parse().ok()?),
+        Some('f') => Tag::Float(value.parse().ok()?),
+        Some('d') => Tag::Double(value.parse().ok()?),
+        Some(_) => return None,
+        None => match value.parse() {
+            Ok(x) => Tag::Int(x),
+            // without a suffix, only numbers with a dot or an exponent are doubles
+            Err(_) if value.contains(['.', 'e', 'E']) => Tag::Double(value.parse().ok()?),
+            Err(_) => return None,
+        },
+    };
+    Some(tag)
+}
+
+/// Parses SNBT, the text format used in commands, like `{Count:1b,id:"minecraft:stone"}`.
+pub fn parse_snbt<'n>(input: &str, bump: &'n Bump) -> Result<Tag<'n>> {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        bump,
+    };
+    let tag = parser.value()?;
+    if parser.peek().is_some() {
+        return parser.error("unexpected trailing characters");
+    }
+    Ok(tag)
+}
@@ -0,0 +1,105 @@
+use std::io::Write;
+
+use anyhow::{Result, anyhow};
+use byteorder::{BE, WriteBytesExt};
+
+use crate::nbt::owned::{OwnedRootTag, OwnedTag};
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+    let size: u16 = s
+        .len()
+        .try_into()
+        .map_err(|_| anyhow!("string too long for nbt: {} bytes", s.len()))?;
+    writer.write_u16::<BE>(size)?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn write_size<W: Write>(writer: &mut W, size: usize) -> Result<()> {
+    let size: i32 = size
+        .try_into()
+        .map_err(|_| anyhow!("too many elements for nbt: {}", size))?;
+    writer.write_i32::<BE>(size)?;
+    Ok(())
+}
+
+fn write_impl<W: Write>(writer: &mut W, tag: &OwnedTag) -> Result<()> {
+    match tag {
+        OwnedTag::Byte(x) => writer.write_i8(*x)?,
+        OwnedTag::Short(x) => writer.write_i16::<BE>(*x)?,
+        OwnedTag::Int(x) => writer.write_i32::<BE>(*x)?,
+        OwnedTag::Long(x) => writer.write_i64::<BE>(*x)?,
+        OwnedTag::Float(x) => writer.write_f32::<BE>(*x)?,
+        OwnedTag::Double(x) => writer.write_f64::<BE>(*x)?,
+        OwnedTag::ByteArray(x) => {
+            write_size(writer, x.len())?;
+            writer.write_all(x)?;
+        }
+        OwnedTag::String(x) => write_string(writer, x)?,
+        OwnedTag::List(x) => {
+            // empty lists are written with the end tag as their type, like the game does
+            let kind = x.first().map(OwnedTag::id).unwrap_or(0);
+            if let Some(other) = x.iter().find(|x| x.id() != kind) {
+                return Err(anyhow!(
+                    "list of tag {} can't contain tag {}",
+                    kind,
+                    other.id()
+                ));
+            }
+            writer.write_u8(kind)?;
+            write_size(writer, x.len())?;
+            for i in x {
+                write_impl(writer, i)?;
+            }
+        }
+        OwnedTag::Compound(x) => {
+            for (name, value) in x {
+                writer.write_u8(value.id())?;
+                write_string(writer, name)?;
+                write_impl(writer, value)?;
+            }
+            // tag_end
+            writer.write_u8(0)?;
+        }
+        OwnedTag::IntArray(x) => {
+            write_size(writer, x.len())?;
+            for i in x {
+                writer.write_i32::<BE>(*i)?;
+            }
+        }
+        OwnedTag::LongArray(x) => {
+            write_size(writer, x.len())?;
+            for i in x {
+                writer.write_i64::<BE>(*i)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_root(tag: &OwnedTag) -> Result<()> {
+    match tag {
+        OwnedTag::Compound(_) => Ok(()),
+        _ => Err(anyhow!(
+            "the root tag should be a compound, found {}",
+            tag.id()
+        )),
+    }
+}
+
+/// Writes `root` the way it's stored in files, with the root compound's name.
+pub fn write<W: Write>(mut writer: W, root: &OwnedRootTag) -> Result<()> {
+    let writer = &mut writer;
+    check_root(&root.tag)?;
+    writer.write_u8(root.tag.id())?;
+    write_string(writer, &root.name)?;
+    write_impl(writer, &root.tag)
+}
+
+/// Writes `tag` the way it's sent over the network since 1.20.2, without a root name.
+pub fn write_network<W: Write>(mut writer: W, tag: &OwnedTag) -> Result<()> {
+    let writer = &mut writer;
+    check_root(tag)?;
+    writer.write_u8(tag.id())?;
+    write_impl(writer, tag)
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use Item::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Item {
     Air = 0,
     Stone = 1,
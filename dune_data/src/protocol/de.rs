@@ -25,9 +25,7 @@ impl<'x> MemoryExt<'x> for &'x [u8] {
 }
 
 pub trait MD<'x> {
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
-    }
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()>;
     fn deserialize(memory: &mut &'x [u8]) -> Result<Self>
     where
         Self: Sized;
@@ -131,27 +129,23 @@ impl<'x> MD<'x> for bool {
     }
 }
 
-impl<'x> MD<'x> for InventorySlot<'x> {
-    fn deserialize(mut memory: &mut &'x [u8]) -> Result<Self> {
+// The types holding nbt are read with the format of the protocol version they come
+// from, the generated code passes it in. Through `MD` they use the named format.
+
+impl<'x> InventorySlot<'x> {
+    pub fn deserialize_nbt(mut memory: &mut &'x [u8], format: nbt::Format) -> Result<Self> {
         let present: bool = MD::deserialize(memory)?;
 
         let data = if present {
             let item_id = read_varint(&mut memory)?;
             let count = MD::deserialize(memory)?;
-            let original = *memory;
-
-            let nbt = if nbt::skip_option(&mut memory)? {
-                let size = memory.as_ptr() as usize - original.as_ptr() as usize;
-                let buf = &original[..size];
-                Some(buf)
-            } else {
-                None
-            };
+            let nbt = IndexedOptionNbt::deserialize_nbt(memory, format)?.nbt;
 
             Some(InventorySlotData {
                 item_id,
                 count,
                 nbt,
+                nbt_format: format,
             })
         } else {
             None
@@ -159,38 +153,67 @@ impl<'x> MD<'x> for InventorySlot<'x> {
 
         Ok(InventorySlot { data })
     }
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+}
+impl<'x> MD<'x> for InventorySlot<'x> {
+    fn deserialize(memory: &mut &'x [u8]) -> Result<Self> {
+        Self::deserialize_nbt(memory, nbt::Format::Named)
+    }
+    fn serialize<W: Write>(&self, mut writer: &mut W) -> IoResult<()> {
+        let Some(data) = &self.data else {
+            return false.serialize(writer);
+        };
+        true.serialize(writer)?;
+        write_varint(&mut writer, data.item_id as u32)?;
+        data.count.serialize(writer)?;
+        IndexedOptionNbt {
+            nbt: data.nbt,
+            format: data.nbt_format,
+        }
+        .serialize(writer)
     }
 }
 
-impl<'x> MD<'x> for IndexedNbt<'x> {
-    fn deserialize(mut memory: &mut &'x [u8]) -> Result<Self> {
+impl<'x> IndexedNbt<'x> {
+    pub fn deserialize_nbt(mut memory: &mut &'x [u8], format: nbt::Format) -> Result<Self> {
         let tmp = *memory;
-        nbt::skip(&mut memory)?;
+        format.skip(&mut memory)?;
         let size = memory.as_ptr() as usize - tmp.as_ptr() as usize;
         let nbt = &tmp[..size];
-        Ok(IndexedNbt { nbt })
+        Ok(IndexedNbt { nbt, format })
     }
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+}
+impl<'x> MD<'x> for IndexedNbt<'x> {
+    fn deserialize(memory: &mut &'x [u8]) -> Result<Self> {
+        Self::deserialize_nbt(memory, nbt::Format::Named)
+    }
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        writer.write_all(self.nbt)
     }
 }
 
-impl<'x> MD<'x> for IndexedOptionNbt<'x> {
-    fn deserialize(mut memory: &mut &'x [u8]) -> Result<Self> {
+impl<'x> IndexedOptionNbt<'x> {
+    pub fn deserialize_nbt(mut memory: &mut &'x [u8], format: nbt::Format) -> Result<Self> {
         let tmp = *memory;
-        let nbt = if nbt::skip_option(&mut memory)? {
+        let nbt = if format.skip_option(&mut memory)? {
             let size = memory.as_ptr() as usize - tmp.as_ptr() as usize;
             let nbt = &tmp[..size];
             Some(nbt)
         } else {
             None
         };
-        Ok(IndexedOptionNbt { nbt })
+        Ok(IndexedOptionNbt { nbt, format })
     }
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+}
+impl<'x> MD<'x> for IndexedOptionNbt<'x> {
+    fn deserialize(memory: &mut &'x [u8]) -> Result<Self> {
+        Self::deserialize_nbt(memory, nbt::Format::Named)
+    }
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        match self.nbt {
+            Some(nbt) => writer.write_all(nbt),
+            // a lone end tag
+            None => writer.write_all(&[0]),
+        }
     }
 }
 
@@ -237,15 +260,6 @@ pub struct Position {
     pub y: i32,
     pub z: i32,
 }
-// impl Position {
-//     pub(crate) fn write<W: Write>(&self, mut writer: W) -> IoResult<()> {
-//         writer.write_u64::<BE>(
-//             ((self.x as u64 & 0x3FFFFFF) << 38)
-//                 | ((self.z as u64 & 0x3FFFFFF) << 12)
-//                 | (self.y as u64 & 0xFFF),
-//         )
-//     }
-// }
 
 impl<'x> MD<'x> for Position {
     fn deserialize(memory: &mut &'x [u8]) -> Result<Self> {
@@ -256,8 +270,11 @@ impl<'x> MD<'x> for Position {
 
         Ok(Position { x, y, z })
     }
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        let val = ((self.x as u64 & 0x3FFFFFF) << 38)
+            | ((self.z as u64 & 0x3FFFFFF) << 12)
+            | (self.y as u64 & 0xFFF);
+        val.serialize(writer)
     }
 }
 
@@ -266,14 +283,14 @@ pub(super) fn cautious_size(size: usize) -> usize {
     size.min(LIMIT)
 }
 
-impl<'x> MD<'x> for ChunkBlockEntity<'x> {
-    fn deserialize(mut memory: &mut &'x [u8]) -> Result<Self> {
+impl<'x> ChunkBlockEntity<'x> {
+    pub fn deserialize_nbt(mut memory: &mut &'x [u8], format: nbt::Format) -> Result<Self> {
         let xz = u8::deserialize(memory)?;
         let x = xz & 0b1111;
         let z = xz >> 4;
         let y = i16::deserialize(memory)?;
         let type_ = read_varint(&mut memory)?;
-        let nbt_data = IndexedOptionNbt::deserialize(memory)?;
+        let nbt_data = IndexedOptionNbt::deserialize_nbt(memory, format)?;
 
         Ok(ChunkBlockEntity {
             x,
@@ -283,8 +300,17 @@ impl<'x> MD<'x> for ChunkBlockEntity<'x> {
             nbt_data,
         })
     }
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+}
+impl<'x> MD<'x> for ChunkBlockEntity<'x> {
+    fn deserialize(memory: &mut &'x [u8]) -> Result<Self> {
+        Self::deserialize_nbt(memory, nbt::Format::Named)
+    }
+    fn serialize<W: Write>(&self, mut writer: &mut W) -> IoResult<()> {
+        let xz = (self.z << 4) | (self.x & 0b1111);
+        xz.serialize(writer)?;
+        self.y.serialize(writer)?;
+        write_varint(&mut writer, self.type_ as u32)?;
+        self.nbt_data.serialize(writer)
     }
 }
 
@@ -308,7 +334,9 @@ impl<'x> MD<'x> for Vec3f64 {
         Ok(Vec3f64 { x, y, z })
     }
 
-    fn serialize<W: Write>(&self, _writer: &mut W) -> IoResult<()> {
-        unimplemented!()
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        self.x.serialize(writer)?;
+        self.y.serialize(writer)?;
+        self.z.serialize(writer)
     }
 }
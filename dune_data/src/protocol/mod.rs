@@ -1,18 +1,18 @@
-pub mod common_states;
 pub mod de;
-pub mod v1_20_2;
 pub mod varint;
+mod versions;
 
 use std::fmt::Debug;
 use std::io::Read;
 use std::mem::size_of;
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
+use dune_common::nbt;
 use flate2::read::ZlibDecoder;
 use num_enum::TryFromPrimitive;
 
-use crate::protocol::de::MD;
 use crate::protocol::varint::{read_varint, read_varint_with_size};
+pub use crate::protocol::versions::*;
 
 #[repr(u8)]
 #[derive(Copy, Clone, Debug)]
@@ -21,6 +21,67 @@ pub enum ConnectionState {
     Status = 1,
     Login = 2,
     Play = 3,
+    Configuration = 4,
+}
+impl ConnectionState {
+    /// The state entered once the login is acknowledged. Since 1.20.2 (764) the
+    /// server goes through configuration before play.
+    pub fn after_login(protocol_version: i32) -> ConnectionState {
+        if protocol_version >= CONFIGURATION_PROTOCOL_VERSION {
+            ConnectionState::Configuration
+        } else {
+            ConnectionState::Play
+        }
+    }
+}
+
+pub const CONFIGURATION_PROTOCOL_VERSION: i32 = 764;
+/// Since 1.20.2 (764) nbt sent over the network has no root name.
+pub const NETWORK_NBT_PROTOCOL_VERSION: i32 = 764;
+
+pub const fn nbt_format(protocol_version: i32) -> nbt::Format {
+    if protocol_version >= NETWORK_NBT_PROTOCOL_VERSION {
+        nbt::Format::Network
+    } else {
+        nbt::Format::Named
+    }
+}
+
+pub type DeserializeFn = for<'r> fn(
+    ConnectionState,
+    PacketDirection,
+    PacketId,
+    &mut &'r [u8],
+) -> Result<VersionedPacket<'r>>;
+pub type SerializeFn = fn(&mut Vec<u8>, VersionedPacket) -> Result<()>;
+/// The bytes of a packet after its id, generated to cover every field.
+#[cfg(test)]
+type Sample = (ConnectionState, PacketDirection, u32, &'static [u8]);
+
+pub struct Version {
+    pub name: &'static str,
+    pub protocol: i32,
+    pub has_configuration: bool,
+    pub deserialize: DeserializeFn,
+    pub serialize: SerializeFn,
+}
+impl Version {
+    pub fn from_protocol(protocol: i32) -> Option<&'static Version> {
+        VERSIONS.iter().find(|x| x.protocol == protocol)
+    }
+    /// The handshake and the status packets have the same layout everywhere, so
+    /// any version can read them before the real one is known. The login packets
+    /// don't.
+    pub fn latest() -> &'static Version {
+        VERSIONS
+            .last()
+            .expect("at least one version should be generated")
+    }
+}
+impl std::fmt::Debug for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.protocol)
+    }
 }
 
 #[repr(u8)]
@@ -30,11 +91,25 @@ pub enum PacketDirection {
     S2C,
 }
 
+#[derive(Debug)]
+pub struct Coords {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+#[derive(Debug)]
+pub enum UseEntityKind {
+    Interact,
+    Attack,
+    InteractAt(Coords),
+}
+
 #[derive(Debug)]
 pub struct InventorySlotData<'x> {
     pub item_id: i32,
     pub count: u8,
     pub nbt: Option<&'x [u8]>,
+    pub nbt_format: nbt::Format,
 }
 
 #[derive(Debug)]
@@ -45,10 +120,12 @@ pub struct InventorySlot<'x> {
 #[derive(Debug)]
 pub struct IndexedOptionNbt<'x> {
     pub nbt: Option<&'x [u8]>,
+    pub format: nbt::Format,
 }
 #[derive(Debug)]
 pub struct IndexedNbt<'x> {
     pub nbt: &'x [u8],
+    pub format: nbt::Format,
 }
 
 pub struct PacketData<'x> {
@@ -82,6 +159,15 @@ macro_rules! impl_unaligned_slice_be {
                         .expect("unaligned slice should be passed a correct sized buffer"),
                 }
             }
+            pub fn len(&self) -> usize {
+                self.inner.len()
+            }
+            pub fn is_empty(&self) -> bool {
+                self.inner.is_empty()
+            }
+            pub fn as_bytes(&self) -> &'x [u8] {
+                bytemuck::cast_slice(self.inner)
+            }
         }
         impl<'x> IntoIterator for $name<'x> {
             type Item = $t;
@@ -172,129 +258,218 @@ impl std::fmt::Display for PacketId {
     }
 }
 
-#[derive(Debug)]
-pub enum Handshaking<'x> {
-    SetProtocolRequest(common_states::handshaking::SetProtocolRequest<'x>),
-}
-pub fn handshaking<'r>(
-    state: ConnectionState,
-    direction: PacketDirection,
-    id: PacketId,
-    reader: &mut &'r [u8],
-) -> Result<Handshaking<'r>> {
-    use {ConnectionState as S, PacketDirection as D};
-
-    let packet = match (state, direction, id) {
-        (S::Handshaking, D::C2S, PacketId(0x0)) => {
-            let p = MD::deserialize(reader)?;
-            Handshaking::SetProtocolRequest(p)
-        }
-        _ => {
-            return Err(anyhow!("unknown handshaking packet id={}", id));
-        }
+#[cfg(test)]
+mod tests {
+    use dune_common::nbt::{self, OwnedTag};
+
+    use crate::protocol::de::{MD, Position};
+    use crate::protocol::varint::write_varint;
+    use crate::protocol::{
+        ConnectionState, PacketDirection, PacketId, SAMPLES, VERSIONS, Version, VersionedPacket,
+        v1_18_2, v1_20_2,
     };
-    Ok(packet)
-}
 
-#[derive(Debug)]
-pub enum Status<'x> {
-    PingStartRequest(common_states::status::PingStartRequest),
-    PingRequest(common_states::status::PingRequest),
-    ServerInfoResponse(common_states::status::ServerInfoResponse<'x>),
-    PingResponse(common_states::status::PingResponse),
-}
+    #[test]
+    fn roundtrip_all_packets() {
+        for (version, samples) in VERSIONS.iter().zip(SAMPLES) {
+            for &(state, direction, id, data) in samples {
+                let info = format!("{:?} {:?} {:?} id={:#x}", version, state, direction, id);
 
-pub fn status<'r>(
-    state: ConnectionState,
-    direction: PacketDirection,
-    id: PacketId,
-    reader: &mut &'r [u8],
-) -> Result<Status<'r>> {
-    use {ConnectionState as S, PacketDirection as D};
-
-    let packet = match (state, direction, id) {
-        (S::Status, D::C2S, PacketId(0x0)) => {
-            let p = MD::deserialize(reader)?;
-            Status::PingStartRequest(p)
-        }
-        (S::Status, D::C2S, PacketId(0x1)) => {
-            let p = MD::deserialize(reader)?;
-            Status::PingRequest(p)
-        }
-        (S::Status, D::S2C, PacketId(0x0)) => {
-            let p = MD::deserialize(reader)?;
-            Status::ServerInfoResponse(p)
-        }
-        (S::Status, D::S2C, PacketId(0x1)) => {
-            let p = MD::deserialize(reader)?;
-            Status::PingResponse(p)
-        }
-        _ => {
-            return Err(anyhow!("unknown status packet id={}", id));
-        }
-    };
-    Ok(packet)
-}
+                let mut reader = data;
+                let packet = (version.deserialize)(state, direction, PacketId(id), &mut reader)
+                    .unwrap_or_else(|e| panic!("{}: {}", info, e));
+                assert!(reader.is_empty(), "{}: bytes left after reading", info);
 
-#[derive(Debug)]
-pub enum Login<'x> {
-    LoginStartRequest(common_states::login::LoginStartRequest<'x>),
-    EncryptionBeginRequest(common_states::login::EncryptionBeginRequest<'x>),
-    LoginPluginResponse(common_states::login::LoginPluginResponse<'x>),
-    DisconnectResponse(common_states::login::DisconnectResponse<'x>),
-    EncryptionBeginResponse(common_states::login::EncryptionBeginResponse<'x>),
-    SuccessResponse(common_states::login::SuccessResponse<'x>),
-    CompressResponse(common_states::login::CompressResponse),
-    LoginPluginRequest(common_states::login::LoginPluginRequest<'x>),
-}
-pub fn login<'r>(
-    state: ConnectionState,
-    direction: PacketDirection,
-    id: PacketId,
-    reader: &mut &'r [u8],
-) -> Result<Login<'r>> {
-    use {ConnectionState as S, PacketDirection as D};
-
-    let packet = match (state, direction, id) {
-        (S::Login, D::C2S, PacketId(0x0)) => {
-            let p = MD::deserialize(reader)?;
-            Login::LoginStartRequest(p)
-        }
-        (S::Login, D::C2S, PacketId(0x1)) => {
-            let p = MD::deserialize(reader)?;
-            Login::EncryptionBeginRequest(p)
-        }
-        (S::Login, D::C2S, PacketId(0x2)) => {
-            let p = MD::deserialize(reader)?;
-            Login::LoginPluginResponse(p)
-        }
-        (S::Login, D::S2C, PacketId(0x0)) => {
-            let p = MD::deserialize(reader)?;
-            Login::DisconnectResponse(p)
-        }
-        (S::Login, D::S2C, PacketId(0x1)) => {
-            let p = MD::deserialize(reader)?;
-            Login::EncryptionBeginResponse(p)
-        }
-        (S::Login, D::S2C, PacketId(0x2)) => {
-            let p = MD::deserialize(reader)?;
-            Login::SuccessResponse(p)
-        }
-        (S::Login, D::S2C, PacketId(0x3)) => {
-            let p = MD::deserialize(reader)?;
-            Login::CompressResponse(p)
-        }
-        (S::Login, D::S2C, PacketId(0x4)) => {
-            let p = MD::deserialize(reader)?;
-            Login::LoginPluginRequest(p)
+                let mut expected = Vec::new();
+                write_varint(&mut expected, id).unwrap();
+                expected.extend_from_slice(data);
+
+                let mut out = Vec::new();
+                (version.serialize)(&mut out, packet).unwrap();
+                assert_eq!(out, expected, "{}", info);
+            }
         }
-        _ => {
-            return Err(anyhow!(
-                "unknown login packet id={},direction={:?}",
-                id,
-                direction
-            ));
+    }
+
+    #[test]
+    fn position() {
+        let position = Position {
+            x: -33554432,
+            y: -2048,
+            z: 33554431,
+        };
+        let mut out = Vec::new();
+        position.serialize(&mut out).unwrap();
+
+        let read = Position::deserialize(&mut out.as_slice()).unwrap();
+        assert_eq!(
+            (read.x, read.y, read.z),
+            (position.x, position.y, position.z)
+        );
+    }
+
+    // The packets below are laid out by hand after the protocol docs, the way
+    // the server sends them, instead of being generated from the same types.
+
+    /// Reads a play packet sent by the server, checks it, and checks it writes
+    /// back to the same bytes.
+    fn fixture(protocol: i32, id: u32, data: &[u8], check: impl FnOnce(&VersionedPacket)) {
+        let version = Version::from_protocol(protocol).unwrap();
+        let mut reader = data;
+        let packet = (version.deserialize)(
+            ConnectionState::Play,
+            PacketDirection::S2C,
+            PacketId(id),
+            &mut reader,
+        )
+        .unwrap();
+        assert!(reader.is_empty(), "bytes left after reading");
+        check(&packet);
+
+        let mut expected = Vec::new();
+        write_varint(&mut expected, id).unwrap();
+        expected.extend_from_slice(data);
+        let mut out = Vec::new();
+        (version.serialize)(&mut out, packet).unwrap();
+        assert_eq!(out, expected);
+    }
+
+    fn display_name(buffer: &[u8], format: nbt::Format) -> OwnedTag {
+        let OwnedTag::Compound(mut root) = nbt::from_slice(buffer, format).unwrap() else {
+            panic!("expected a compound");
+        };
+        let Some(OwnedTag::Compound(mut display)) = root.shift_remove("display") else {
+            panic!("expected a display compound");
+        };
+        display.shift_remove("Name").unwrap()
+    }
+
+    #[test]
+    fn slots() {
+        let name = OwnedTag::String(r#"{"text":"Shiny"}"#.to_string());
+        // window 0, state 1, slot 36, 1 diamond named Shiny
+        let named = [
+            &[0x00, 0x01, 0x00, 0x24, 0x01, 0xAE, 0x05, 0x01][..],
+            &[0x0A, 0x00, 0x00],
+            &[0x0A, 0x00, 0x07],
+            b"display",
+            &[0x08, 0x00, 0x04],
+            b"Name",
+            &[0x00, 0x10],
+            br#"{"text":"Shiny"}"#,
+            &[0x00, 0x00],
+        ]
+        .concat();
+        fixture(758, 0x15, &named, |packet| {
+            let VersionedPacket::V1_18_2(v1_18_2::Packet::SetSlotResponse(p)) = packet else {
+                panic!("expected a set slot");
+            };
+            assert_eq!(p.slot, 36);
+            let data = p.item.data.as_ref().unwrap();
+            assert_eq!((data.item_id, data.count), (686, 1));
+            assert_eq!(display_name(data.nbt.unwrap(), data.nbt_format), name);
+        });
+
+        // since 1.20.2 the root compound has no name
+        let network = [
+            &[0x00, 0x01, 0x00, 0x24, 0x01, 0xFC, 0x05, 0x01][..],
+            &[0x0A],
+            &[0x0A, 0x00, 0x07],
+            b"display",
+            &[0x08, 0x00, 0x04],
+            b"Name",
+            &[0x00, 0x10],
+            br#"{"text":"Shiny"}"#,
+            &[0x00, 0x00],
+        ]
+        .concat();
+        fixture(764, 0x15, &network, |packet| {
+            let VersionedPacket::V1_20_2(v1_20_2::Packet::SetSlotResponse(p)) = packet else {
+                panic!("expected a set slot");
+            };
+            let data = p.item.data.as_ref().unwrap();
+            assert_eq!((data.item_id, data.count), (764, 1));
+            assert_eq!(display_name(data.nbt.unwrap(), data.nbt_format), name);
+        });
+
+        // 64 stone in slot 9 without nbt, then an empty slot
+        for (data, item) in [
+            (
+                &[0x00, 0x02, 0x00, 0x09, 0x01, 0x01, 0x40, 0x00][..],
+                Some((1, 64)),
+            ),
+            (&[0x00, 0x03, 0x00, 0x09, 0x00], None),
+        ] {
+            fixture(758, 0x15, data, |packet| {
+                let VersionedPacket::V1_18_2(v1_18_2::Packet::SetSlotResponse(p)) = packet else {
+                    panic!("expected a set slot");
+                };
+                let read = p.item.data.as_ref();
+                assert_eq!(read.map(|x| (x.item_id, x.count)), item);
+                assert!(read.is_none_or(|x| x.nbt.is_none()));
+            });
         }
-    };
-    Ok(packet)
+    }
+
+    #[test]
+    fn chunk_block_entities() {
+        let data = [
+            // chunk -3 5
+            &[0xFF, 0xFF, 0xFF, 0xFD, 0x00, 0x00, 0x00, 0x05][..],
+            // {MOTION_BLOCKING: [L; 1L, 2L]}
+            &[0x0A, 0x00, 0x00, 0x0C, 0x00, 0x0F],
+            b"MOTION_BLOCKING",
+            &[0x00, 0x00, 0x00, 0x02],
+            &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0x00],
+            // the sections, left as they are
+            &[0x03, 0xAA, 0xBB, 0xCC],
+            // 2 block entities, a chest at 3 -12 7 named Loot
+            &[0x02, 0x73, 0xFF, 0xF4, 0x01],
+            &[0x0A, 0x00, 0x00, 0x08, 0x00, 0x0A],
+            b"CustomName",
+            &[0x00, 0x0F],
+            br#"{"text":"Loot"}"#,
+            &[0x00],
+            // and a bed at 15 300 0, without nbt
+            &[0x0F, 0x01, 0x2C, 0x17, 0x00],
+            // the light data that follows isn't in the definitions
+        ]
+        .concat();
+        fixture(758, 0x25, &data, |packet| {
+            let VersionedPacket::V1_18_2(v1_18_2::Packet::MapChunkResponse(p)) = packet else {
+                panic!("expected a chunk");
+            };
+            assert_eq!((p.x, p.z), (-3, 5));
+            assert_eq!(p.chunk_data, [0xAA, 0xBB, 0xCC]);
+            let [chest, bed] = &p.block_entities[..] else {
+                panic!("expected 2 block entities");
+            };
+            assert_eq!((chest.x, chest.y, chest.z, chest.type_), (3, -12, 7, 1));
+            let OwnedTag::Compound(nbt) =
+                nbt::from_slice(chest.nbt_data.nbt.unwrap(), nbt::Format::Named).unwrap()
+            else {
+                panic!("expected a compound");
+            };
+            assert_eq!(
+                nbt["CustomName"],
+                OwnedTag::String(r#"{"text":"Loot"}"#.to_string())
+            );
+            assert_eq!((bed.x, bed.y, bed.z, bed.type_), (15, 300, 0, 23));
+            assert!(bed.nbt_data.nbt.is_none());
+        });
+    }
+
+    #[test]
+    fn negative_position() {
+        // -100 -60 200 set to state 1
+        let data = [0xFF, 0xFF, 0xE7, 0x00, 0x00, 0x0C, 0x8F, 0xC4, 0x01];
+        fixture(758, 0x9, &data, |packet| {
+            let VersionedPacket::V1_18_2(v1_18_2::Packet::BlockChangeResponse(p)) = packet else {
+                panic!("expected a block change");
+            };
+            let location = &p.location;
+            assert_eq!((location.x, location.y, location.z), (-100, -60, 200));
+            assert_eq!(p.type_, 1);
+        });
+    }
 }
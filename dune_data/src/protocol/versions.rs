@@ -12,4 +12,4 @@
 #![allow(unused_variables)]
 // fix
 
-include!(concat!(env!("OUT_DIR"), "/v1_20_2.rs"));
+include!(concat!(env!("OUT_DIR"), "/versions.rs"));
@@ -131,7 +131,7 @@ fn process_items(versions: &HashMap<&str, Version>, depends: &mut Vec<PathBuf>)
     *out += "use std::collections::HashMap;
     use Item::*;
     
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)] pub enum Item {";
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)] pub enum Item {";
     for (index, item) in items.iter().enumerate() {
         write!(out, "{} = {},", title_case(&item.name), index).unwrap();
     }
@@ -158,7 +158,7 @@ fn process_items(versions: &HashMap<&str, Version>, depends: &mut Vec<PathBuf>)
 
         let mut sorted: Vec<(&str, u16)> =
             map.iter().map(|(name, id)| (name.as_str(), *id)).collect();
-        sorted.sort_by(|(_, id1), (_, id2)| id1.cmp(id2));
+        sorted.sort_by_key(|(_, id)| *id);
 
         for (name, version_id) in sorted {
             let item = items.iter().find(|x| x.name == name).unwrap();
@@ -221,7 +221,7 @@ fn process_enchants(versions: &HashMap<&str, Version>, depends: &mut Vec<PathBuf
     fs::write(JSON_PATH, out).unwrap();
 
     let out = &mut String::with_capacity(4096);
-    *out += "#[derive(Debug)] pub enum Enchantment {";
+    *out += "#[derive(Debug, Clone, Copy, PartialEq, Eq)] pub enum Enchantment {";
 
     for i in enchants.iter() {
         write!(out, "{} = {},", title_case(&i.name), i.id).unwrap();
@@ -253,9 +253,12 @@ impl Enchantment {
 
 pub fn run(out_dir: &str, mc_data_path: &Path) -> Vec<PathBuf> {
     const VERSIONS: &[VersionInfo] = &[
-        // VersionInfo::new("1.18.2", false),
-        // VersionInfo::new("1.19.3", false),
-        VersionInfo::new("1.20.2", true),
+        VersionInfo::new("1.18.2", 758, false),
+        VersionInfo::new("1.19.4", 762, true),
+        VersionInfo::new("1.20.1", 763, true),
+        VersionInfo::new("1.20.2", 764, true),
+        VersionInfo::new("1.20.4", 765, true),
+        VersionInfo::new("1.21", 767, true),
     ];
 
     Command::new("git")
@@ -277,9 +280,11 @@ pub fn run(out_dir: &str, mc_data_path: &Path) -> Vec<PathBuf> {
         process_enchants(&versions, &mut depends);
     }
 
-    for v in VERSIONS {
-        protocol::run(*v, &versions[v.name].protocol_path, out_dir, &mut depends);
-    }
+    let generated: Vec<_> = VERSIONS
+        .iter()
+        .map(|v| protocol::run(*v, &versions[v.name].protocol_path, out_dir, &mut depends))
+        .collect();
+    protocol::run_versions(&generated, out_dir);
 
     depends
 }
@@ -126,6 +126,8 @@ impl<'x> Ty<'x> {
         use Ty::*;
         match self {
             String | Buffer(_) | RestBuffer | Slot | Nbt | OptionNbt | ChunkBlockEntity => true,
+            // failed structs keep the raw bytes around
+            Struct(_) if self.failed(types) => true,
             Struct(x) => x
                 .fields
                 .iter()
@@ -142,6 +144,21 @@ impl<'x> Ty<'x> {
             _ => false,
         }
     }
+    /// If this type, or anything inside it, couldn't be parsed.
+    fn failed(&self, types: &TypesMap) -> bool {
+        use Ty::*;
+        match self {
+            Struct(x) => x.failed || x.fields.iter().any(|x| types[x.ty].failed(types)),
+            Option(x) => types[x.subtype].failed(types),
+            Array(x) => types[x.subtype].failed(types),
+            Enum(x) => x
+                .variants
+                .values()
+                .flat_map(|x| x.fields.iter())
+                .any(|x| types[x.ty].failed(types)),
+            _ => false,
+        }
+    }
     fn get_simple_type(&self) -> &'static str {
         use Ty::*;
         match self {
@@ -218,12 +235,12 @@ struct Packet<'x> {
 struct Direction<'x> {
     pub packets: Vec<Packet<'x>>,
 }
-#[allow(dead_code)]
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum ConnectionState {
     Handshaking,
     Status,
     Login,
+    Configuration,
     Play,
 }
 impl ConnectionState {
@@ -239,6 +256,10 @@ impl ConnectionState {
 
             Login if title => "Login",
             Login => "login",
+
+            Configuration if title => "Configuration",
+            Configuration => "configuration",
+
             Play if title => "Play",
             Play => "play",
         }
@@ -253,24 +274,66 @@ struct State<'x> {
 #[derive(Copy, Clone)]
 pub struct VersionInfo {
     pub name: &'static str,
+    pub protocol: i32,
     pub has_bundle_delimiter: bool,
 }
 impl VersionInfo {
-    pub const fn new(name: &'static str, has_bundle_delimiter: bool) -> VersionInfo {
+    pub const fn new(name: &'static str, protocol: i32, has_bundle_delimiter: bool) -> VersionInfo {
         VersionInfo {
             name,
+            protocol,
             has_bundle_delimiter,
         }
     }
+
+    fn module_name(&self) -> String {
+        format!("v{}", self.name.replace('.', "_"))
+    }
+    fn variant_name(&self) -> String {
+        format!("V{}", self.name.replace('.', "_"))
+    }
 }
 
-pub(super) fn run(version: VersionInfo, path: &Path, out_dir: &str, depends: &mut Vec<PathBuf>) {
+pub(super) struct GeneratedVersion {
+    pub info: VersionInfo,
+    pub has_configuration: bool,
+}
+
+pub(super) fn run(
+    version: VersionInfo,
+    path: &Path,
+    out_dir: &str,
+    depends: &mut Vec<PathBuf>,
+) -> GeneratedVersion {
     let bump = Bump::new();
     let mut types = TypesMap::with_capacity_and_key(32);
 
     let states = parser::parse(&mut types, &bump, path, depends);
-    let out = writer::write(&types, states, version.has_bundle_delimiter);
+    let has_configuration = states
+        .iter()
+        .any(|x| x.kind == ConnectionState::Configuration);
+    let out = writer::write(&types, states, version);
+    write_formatted(out_dir, &version.module_name(), out);
+
+    let bytes = bump.allocated_bytes();
+    println!(
+        "bump size: {} ({} bytes)",
+        format_size(bytes, BINARY),
+        bytes
+    );
 
+    GeneratedVersion {
+        info: version,
+        has_configuration,
+    }
+}
+
+pub(super) fn run_versions(versions: &[GeneratedVersion], out_dir: &str) {
+    let out = writer::write_versions(versions);
+    write_formatted(out_dir, "versions", out);
+}
+
+fn write_formatted(out_dir: &str, name: &str, out: String) {
     let out = match syn::parse_file(&out) {
         Ok(syntax_tree) => prettyplease::unparse(&syntax_tree),
         Err(e) => {
@@ -279,13 +342,6 @@ pub(super) fn run(version: VersionInfo, path: &Path, out_dir: &str, depends: &mu
         }
     };
 
-    let path = format!("{}/v{}.rs", out_dir, version.name.replace('.', "_"));
+    let path = format!("{}/{}.rs", out_dir, name);
     fs::write(path, out).unwrap();
-
-    let bytes = bump.allocated_bytes();
-    println!(
-        "bump size: {} ({} bytes)",
-        format_size(bytes, BINARY),
-        bytes
-    );
 }
@@ -28,9 +28,10 @@ struct JsonState {
 
 #[derive(Debug, Deserialize)]
 struct Root {
-    // handshaking: JsonState,
-    // status: JsonState,
-    // login: JsonState,
+    handshaking: JsonState,
+    status: JsonState,
+    login: JsonState,
+    configuration: Option<JsonState>,
     play: JsonState,
 }
 
@@ -503,10 +504,11 @@ fn direction<'x>(
         let is_ignored = name == "packet_advancements";
         let name = name.strip_prefix("packet_").unwrap().to_string();
         let id = raw_mappings[&name];
-        let name = if state_kind == ConnectionState::Play && name == "ping" {
-            "play_".to_string() + &name
-        } else {
-            name
+        let name = match state_kind {
+            ConnectionState::Play if name == "ping" => "play_".to_string() + &name,
+            // configuration reuses most of the play and login packet names
+            ConnectionState::Configuration => "config_".to_string() + &name,
+            _ => name,
         };
         let name = if name.ends_with("_request") || name.ends_with("_response") {
             name
@@ -563,18 +565,43 @@ pub(super) fn parse<'x>(
     bump: &'x Bump,
     path: &Path,
     depends: &mut Vec<PathBuf>,
-) -> [State<'x>; 1] {
+) -> Vec<State<'x>> {
     let content = read_file(path, depends);
     let root: Root = serde_json::from_str(&content).unwrap();
 
     let mut parser = Parser::new(types);
 
-    let result = [
-        // state(&parser, root.handshaking, ConnectionState::Handshaking),
-        // state(&parser, root.status, ConnectionState::Status),
-        // state(&parser, root.login, ConnectionState::Login),
-        state(&mut parser, bump, root.play, ConnectionState::Play),
+    let mut result = vec![
+        state(
+            &mut parser,
+            bump,
+            root.handshaking,
+            ConnectionState::Handshaking,
+        ),
+        state(&mut parser, bump, root.status, ConnectionState::Status),
+        state(&mut parser, bump, root.login, ConnectionState::Login),
     ];
+    if let Some(configuration) = root.configuration {
+        result.push(state(
+            &mut parser,
+            bump,
+            configuration,
+            ConnectionState::Configuration,
+        ));
+    }
+    result.push(state(&mut parser, bump, root.play, ConnectionState::Play));
+
+    // all the states end up in the same `Packet` enum
+    let mut names = HashSet::new();
+    for state in &result {
+        for packet in state.c2s.packets.iter().chain(&state.s2c.packets) {
+            assert!(
+                names.insert(packet.name),
+                "duplicated packet name `{}`",
+                packet.name
+            );
+        }
+    }
 
     let mut unknown_types: Vec<_> = parser.unknown_types.into_iter().collect();
     unknown_types.sort_by_key(|x| x.0);
@@ -1,7 +1,10 @@
 use std::borrow::Cow;
 use std::fmt::Arguments;
 
-use super::{Direction, State, Ty, TyBufferCountKind, TyEnum, TyKey, TyStruct, TypesMap};
+use super::{
+    Direction, GeneratedVersion, State, Ty, TyBufferCountKind, TyEnum, TyKey, TyStruct, TypesMap,
+    VersionInfo,
+};
 use crate::protocol::Constant;
 
 trait FmtWriteNoFail {
@@ -104,6 +107,19 @@ fn deserialize_one(
         writeln!(out, "{}.push({}); }}", name, elem);
         return;
     }
+    if let Ty::Option(x) = ty {
+        // not through `MD`, the subtype might be a varint or the rest of the buffer
+        let elem = format!("x_{}", count);
+        write!(
+            out,
+            "let {}: {} = if <bool as MD>::deserialize(reader)? {{",
+            name,
+            get_type_name(ty_key, types)
+        );
+        deserialize_one(out, &elem, x.subtype, types, bitfield_base_width, count + 1);
+        write!(out, "Some({}) }} else {{ None }};", elem);
+        return;
+    }
     if let Ty::Enum(x) = ty {
         // TODO: get the correct type in parser so not do .into()
         write!(
@@ -130,16 +146,54 @@ fn deserialize_one(
         Ty::VarLong => *out += "read_varlong(&mut reader)?;",
         Ty::RestBuffer => *out += "&reader[..]; *reader = &[];",
         Ty::Struct(x) => write!(out, "{}::deserialize(reader)?;", x.name),
+        Ty::Slot => *out += "InventorySlot::deserialize_nbt(reader, NBT_FORMAT)?;",
+        Ty::Nbt => *out += "IndexedNbt::deserialize_nbt(reader, NBT_FORMAT)?;",
+        Ty::OptionNbt => *out += "IndexedOptionNbt::deserialize_nbt(reader, NBT_FORMAT)?;",
+        Ty::ChunkBlockEntity => *out += "ChunkBlockEntity::deserialize_nbt(reader, NBT_FORMAT)?;",
         _ => *out += "MD::deserialize(reader)?;",
     }
     writeln!(out);
 }
-fn serialize_one(out: &mut String, name: &str, ty_key: TyKey, types: &TypesMap) {
+fn serialize_one(out: &mut String, name: &str, ty_key: TyKey, types: &TypesMap, count: u32) {
     let ty = &types[ty_key];
     match ty {
         Ty::VarInt => write!(out, "write_varint(&mut writer, {} as u32)?;", name),
         Ty::VarLong => write!(out, "write_varlong(&mut writer, {} as u64)?;", name),
         Ty::RestBuffer => write!(out, "writer.write_all({})?;", name),
+        Ty::Option(x) => {
+            let elem = format!("x_{}", count);
+            write!(
+                out,
+                "match &{} {{ None => false.serialize(&mut writer)?, Some({}) => {{ true.serialize(&mut writer)?;",
+                name, elem
+            );
+            let elem = match types[x.subtype] {
+                // `write_all` derefs on its own
+                Ty::RestBuffer => elem,
+                _ => format!("(*{})", elem),
+            };
+            serialize_one(out, &elem, x.subtype, types, count + 1);
+            *out += "} }";
+        }
+        Ty::Array(x) => {
+            let array_count = format!("array_count_{}", count);
+            write!(
+                out,
+                "let {}: {} = {}.len() as _;",
+                array_count,
+                get_type_name(x.count_ty, types),
+                name
+            );
+            serialize_one(out, &array_count, x.count_ty, types, count + 1);
+            if types[x.subtype].is_rs_builtin() {
+                write!(out, "writer.write_all({}.as_bytes())?;", name);
+            } else {
+                let elem = format!("x_{}", count);
+                write!(out, "for {} in {}.iter() {{", elem, name);
+                serialize_one(out, &format!("(*{})", elem), x.subtype, types, count + 1);
+                *out += "}";
+            }
+        }
         _ => {
             write!(out, "{}.serialize(&mut writer)?;", name);
         }
@@ -167,23 +221,14 @@ fn serialize_struct(
     // TODO:
     if name == "UseEntityRequest" {
         *out += r#"
-        #[derive(Debug)]
-        pub struct Coords {
-            pub x: f32,
-            pub y: f32,
-            pub z: f32,
-        }
-        #[derive(Debug)]
-        pub enum UseEntityKind {
-            Interact,
-            Attack,
-            InteractAt(Coords),
-        }
-        
+        pub use crate::protocol::{Coords, UseEntityKind};
+
         #[derive(Debug)]
         pub struct UseEntityRequest {
             pub entity_id: i32,
             pub kind: UseEntityKind,
+            /// Not sent for attacks.
+            pub hand: i32,
             pub sneaking: bool,
         }
         
@@ -191,9 +236,10 @@ fn serialize_struct(
             fn deserialize(mut reader: &mut &[u8]) -> Result<UseEntityRequest> {
                 let entity_id = read_varint(&mut reader)?;
                 let kind = read_varint(&mut reader)?;
+                let mut hand = 0;
                 let kind = match kind {
                     0 => {
-                        let _ = read_varint(&mut reader)?;
+                        hand = read_varint(&mut reader)?;
                         UseEntityKind::Interact
                     }
                     1 => UseEntityKind::Attack,
@@ -201,7 +247,7 @@ fn serialize_struct(
                         let x = MD::deserialize(&mut reader)?;
                         let y = MD::deserialize(&mut reader)?;
                         let z = MD::deserialize(&mut reader)?;
-                        let _ = read_varint(&mut reader)?;
+                        hand = read_varint(&mut reader)?;
     
                         UseEntityKind::InteractAt(Coords { x, y, z })
                     }
@@ -212,13 +258,55 @@ fn serialize_struct(
                 Ok(UseEntityRequest {
                     entity_id,
                     kind,
+                    hand,
                     sneaking,
                 })
             }
+            fn serialize<W: Write>(&self, mut writer: &mut W) -> IoResult<()> {
+                write_varint(&mut writer, self.entity_id as u32)?;
+                match &self.kind {
+                    UseEntityKind::Interact => {
+                        write_varint(&mut writer, 0)?;
+                        write_varint(&mut writer, self.hand as u32)?;
+                    }
+                    UseEntityKind::Attack => {
+                        write_varint(&mut writer, 1)?;
+                    }
+                    UseEntityKind::InteractAt(coords) => {
+                        write_varint(&mut writer, 2)?;
+                        coords.x.serialize(&mut writer)?;
+                        coords.y.serialize(&mut writer)?;
+                        coords.z.serialize(&mut writer)?;
+                        write_varint(&mut writer, self.hand as u32)?;
+                    }
+                }
+                self.sneaking.serialize(&mut writer)
+            }
         }
         "#;
         return;
     }
+    if ty.failed(types) {
+        // keep the bytes around so the packet can still be forwarded
+        write!(
+            out,
+            "#[derive(Debug)] pub struct {name}<'p> {{
+                // failed
+                pub raw: &'p [u8],
+            }}
+            impl<'p> MD<'p> for {name}<'p> {{
+                fn deserialize(reader: &mut &'p [u8]) -> Result<{name}<'p>> {{
+                    let raw = &reader[..];
+                    *reader = &[];
+                    Ok({name} {{ raw }})
+                }}
+                fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {{
+                    writer.write_all(self.raw)
+                }}
+            }}"
+        );
+        return;
+    }
 
     let (lifetime, lifetime_simple) = life(ty.needs_lifetime(types));
     writeln!(out, "#[derive(Debug)] pub struct {}{} {{", name, lifetime);
@@ -243,10 +331,6 @@ fn serialize_struct(
         ty_struct.name
     );
 
-    if ty_struct.failed {
-        *out += "// failed\n";
-    }
-
     let bitfield_base_width = if let Some(base_type) = ty_struct.base_type {
         deserialize_one(out, "value", base_type, types, 0, 1);
         types[base_type].width()
@@ -265,23 +349,36 @@ fn serialize_struct(
 
     *out += "}; Ok(result) }";
 
-    let has_serialization = bitfield_base_width == 0
-        && !ty_struct.fields.iter().any(|x| {
-            let ty = &types[x.ty];
-            matches!(ty, Ty::Array(_) | Ty::Struct(_) | Ty::Enum(_))
-        });
-
-    if has_serialization && !ty_struct.failed {
+    *out += "fn serialize<W: Write>(&self, mut writer: &mut W) -> IoResult<()> {";
+    if let Some(base_type) = ty_struct.base_type {
+        *out += "let mut value = 0u64;";
+        for field in &ty_struct.fields {
+            let Ty::Bitfield(x) = &types[field.ty] else {
+                unreachable!("bitfield struct with a non bitfield field");
+            };
+            let size = x.range_end - x.range_begin;
+            let mask = if size == 64 {
+                u64::MAX
+            } else {
+                (1 << size) - 1
+            };
+            write!(
+                out,
+                "value |= (self.{} as u64 & {:#x}) << {};",
+                field.name, mask, x.range_begin
+            );
+        }
         write!(
             out,
-            "fn serialize<W: Write>(&self, mut {}writer: &mut W) -> IoResult<()> {{",
-            underscore(!has_serialization)
+            "(value as {}).serialize(&mut writer)?;",
+            types[base_type].get_simple_type()
         );
+    } else {
         for field in &ty_struct.fields {
-            serialize_one(out, &format!("self.{}", field.name), field.ty, types);
+            serialize_one(out, &format!("self.{}", field.name), field.ty, types, 1);
         }
-        *out += "Ok(()) }";
     }
+    *out += "Ok(()) }";
 
     *out += "}";
 }
@@ -329,14 +426,30 @@ fn serialize_enum(out: &mut String, ty_key: TyKey, types: &TypesMap, ty_enum: &T
     _ => todo!()
 ";
 
-    *out += "}; Ok(r) }}";
+    *out += "}; Ok(r) }";
+
+    *out += "fn serialize<W: Write>(&self, mut writer: &mut W) -> IoResult<()> { match self {";
+    for variants in ty_enum.variants.values() {
+        write!(out, "{}::{} {{", ty_enum.name, variants.name);
+        for field in variants.fields.iter() {
+            write!(out, "{},", field.name);
+        }
+        *out += "} => {";
+        for field in variants.fields.iter() {
+            serialize_one(out, &format!("(*{})", field.name), field.ty, types, 1);
+        }
+        *out += "}";
+    }
+    *out += "} Ok(()) } }";
 }
 fn write_all_structs(out: &mut String, ty_key: TyKey, types: &TypesMap) {
     let ty = &types[ty_key];
     match ty {
         Ty::Struct(x) => {
-            for field in x.fields.iter() {
-                write_all_structs(out, field.ty, types);
+            if !ty.failed(types) {
+                for field in x.fields.iter() {
+                    write_all_structs(out, field.ty, types);
+                }
             }
             serialize_struct(out, ty_key, types, x, x.name);
         }
@@ -417,7 +530,7 @@ pub fn deserialize<'r>(state: ConnectionState, direction: PacketDirection, id: P
         }
     }
 
-    *out += r#"_ => { return Err(anyhow!("unknown play packet id={},direction={:?}", id, direction)); } }; Ok(packet) }"#;
+    *out += r#"_ => { return Err(anyhow!("unknown packet id={},state={:?},direction={:?}", id, state, direction)); } }; Ok(packet) }"#;
 }
 
 fn serialize_fn(out: &mut String, states: &[State], has_bundle_delimiter: bool) {
@@ -428,7 +541,7 @@ pub fn serialize<'r, W: Write>(mut writer: &mut W, packet: Packet) -> IoResult<(
 ";
 
     if has_bundle_delimiter {
-        *out += "Packet::BundleDelimiter => Ok(()),";
+        *out += "Packet::BundleDelimiter => { write_varint(&mut writer, 0x0)?; Ok(()) }";
     }
 
     for state in states {
@@ -446,11 +559,225 @@ pub fn serialize<'r, W: Write>(mut writer: &mut W, packet: Packet) -> IoResult<(
     *out += r#"}}"#;
 }
 
-pub(super) fn write(
-    types: &TypesMap,
-    mut states: [State; 1],
-    has_bundle_delimiter: bool,
-) -> String {
+// samples, for testing that every packet serializes back to the same bytes
+
+const SAMPLE_NBT: &[u8] = &[10, 0, 0, 1, 0, 1, b'a', 7, 0];
+const SAMPLE_NBT_NETWORK: &[u8] = &[10, 1, 0, 1, b'a', 7, 0];
+/// Mirrors `dune_data::protocol::NETWORK_NBT_PROTOCOL_VERSION`.
+const NETWORK_NBT_PROTOCOL_VERSION: i32 = 764;
+
+struct SampleContext<'x, 't> {
+    types: &'x TypesMap<'t>,
+    nbt: &'static [u8],
+    /// Which variant every enum takes, modulo how many it has.
+    variant: usize,
+}
+
+/// How many samples it takes for every enum under `ty_key` to take all its variants.
+fn variant_count(ty_key: TyKey, types: &TypesMap) -> usize {
+    let ty = &types[ty_key];
+    let fields_count = |fields: &mut dyn Iterator<Item = TyKey>| {
+        fields.map(|x| variant_count(x, types)).max().unwrap_or(1)
+    };
+    match ty {
+        Ty::Struct(_) if ty.failed(types) => 1,
+        Ty::Struct(x) => match x.base_type {
+            Some(base_type) => variant_count(base_type, types),
+            None => fields_count(&mut x.fields.iter().map(|x| x.ty)),
+        },
+        Ty::Enum(x) => {
+            let mut fields = x
+                .variants
+                .values()
+                .flat_map(|x| x.fields.iter().map(|x| x.ty));
+            x.variants.len().max(fields_count(&mut fields))
+        }
+        Ty::Option(x) => variant_count(x.subtype, types),
+        Ty::Array(x) => variant_count(x.subtype, types),
+        _ => 1,
+    }
+}
+
+fn sample_varint(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+fn sample_constant(out: &mut Vec<u8>, ty: &Ty, constant: Constant) {
+    match constant {
+        Constant::Bool(x) => out.push(x as u8),
+        Constant::String(x) => {
+            sample_varint(out, x.len() as u32);
+            out.extend_from_slice(x.as_bytes());
+        }
+        Constant::Int(x) => match ty {
+            Ty::VarInt | Ty::VarLong => sample_varint(out, x),
+            Ty::Bool | Ty::U8 | Ty::I8 => out.push(x as u8),
+            Ty::U16 | Ty::I16 => out.extend_from_slice(&(x as u16).to_be_bytes()),
+            Ty::U32 | Ty::I32 => out.extend_from_slice(&x.to_be_bytes()),
+            Ty::U64 | Ty::I64 => out.extend_from_slice(&(x as u64).to_be_bytes()),
+            _ => unreachable!("unknown discriminator type {:?}", ty),
+        },
+    }
+}
+fn sample_fields<'x>(
+    out: &mut Vec<u8>,
+    fields: impl Iterator<Item = (&'x str, TyKey)> + Clone,
+    ctx: &SampleContext,
+) {
+    let types = ctx.types;
+    // the fields switched on have to hold a value the switch knows about
+    let discriminators: Vec<_> = fields
+        .clone()
+        .filter_map(|(_, ty)| match &types[ty] {
+            Ty::Enum(x) => {
+                let index = ctx.variant % x.variants.len();
+                Some((x.compare_to, *x.variants.keys().nth(index)?))
+            }
+            _ => None,
+        })
+        .collect();
+
+    for (name, ty) in fields {
+        match discriminators.iter().find(|x| x.0 == name) {
+            Some((_, constant)) => sample_constant(out, &types[ty], *constant),
+            None => sample(out, ty, ctx),
+        }
+    }
+}
+fn sample(out: &mut Vec<u8>, ty_key: TyKey, ctx: &SampleContext) {
+    let types = ctx.types;
+    let ty = &types[ty_key];
+    match ty {
+        Ty::U8 | Ty::I8 => out.push(0xF7),
+        Ty::U16 | Ty::I16 => out.extend_from_slice(&[0xF7, 0x01]),
+        Ty::U32 | Ty::I32 => out.extend_from_slice(&[0xF7, 0x01, 0x02, 0x03]),
+        Ty::U64 | Ty::I64 => out.extend_from_slice(&[0xF7, 0x01, 0x02, 0x03, 4, 5, 6, 7]),
+        Ty::U128 => out.extend_from_slice(&[0xF7; 16]),
+        Ty::F32 => out.extend_from_slice(&1.5f32.to_be_bytes()),
+        Ty::F64 => out.extend_from_slice(&1.5f64.to_be_bytes()),
+        Ty::Bool => out.push(1),
+        Ty::VarInt | Ty::VarLong => sample_varint(out, 150),
+        Ty::String => out.extend_from_slice(&[3, b'a', b'b', b'c']),
+        Ty::Buffer(x) => match x.kind {
+            TyBufferCountKind::Fixed(count) => out.extend(std::iter::repeat_n(0xAB, count.into())),
+            TyBufferCountKind::Varint => out.extend_from_slice(&[2, 0xAB, 0xCD]),
+        },
+        Ty::RestBuffer => out.extend_from_slice(&[1, 2, 3]),
+        Ty::Position => out.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xC0, 0x00, 0x12, 0x3F, 0xFE]),
+        Ty::Slot => {
+            out.push(1);
+            sample_varint(out, 150);
+            out.push(5);
+            out.extend_from_slice(ctx.nbt);
+        }
+        Ty::Nbt | Ty::OptionNbt => out.extend_from_slice(ctx.nbt),
+        Ty::ChunkBlockEntity => {
+            out.extend_from_slice(&[0x5A, 0, 64]);
+            sample_varint(out, 3);
+            out.extend_from_slice(ctx.nbt);
+        }
+        Ty::Vec3f64 => {
+            for _ in 0..3 {
+                out.extend_from_slice(&1.5f64.to_be_bytes());
+            }
+        }
+        Ty::Struct(_) if ty.failed(types) => out.extend_from_slice(&[1, 2, 3]),
+        Ty::Struct(x) => match x.base_type {
+            Some(base_type) => sample(out, base_type, ctx),
+            None => sample_fields(out, x.fields.iter().map(|x| (x.name, x.ty)), ctx),
+        },
+        Ty::Enum(x) => {
+            let index = ctx.variant % x.variants.len();
+            let variant = x
+                .variants
+                .values()
+                .nth(index)
+                .expect("enum without variants");
+            sample_fields(out, variant.fields.iter().map(|x| (x.name, x.ty)), ctx);
+        }
+        Ty::Option(x) => {
+            out.push(1);
+            sample(out, x.subtype, ctx);
+        }
+        Ty::Array(x) => {
+            sample_constant(out, &types[x.count_ty], Constant::Int(2));
+            for _ in 0..2 {
+                sample(out, x.subtype, ctx);
+            }
+        }
+        Ty::Bitfield(_) => unreachable!("bitfields are sampled through their base type"),
+    }
+}
+fn samples(out: &mut String, types: &TypesMap, states: &[State], version: VersionInfo) {
+    let mut ctx = SampleContext {
+        types,
+        nbt: if version.protocol >= NETWORK_NBT_PROTOCOL_VERSION {
+            SAMPLE_NBT_NETWORK
+        } else {
+            SAMPLE_NBT
+        },
+        variant: 0,
+    };
+    *out += "
+#[cfg(test)]
+pub(crate) const SAMPLES: &[crate::protocol::Sample] = &[";
+
+    if version.has_bundle_delimiter {
+        *out += "(ConnectionState::Play, PacketDirection::S2C, 0x0, &[]),";
+    }
+
+    let mut buffer = Vec::new();
+    for state in states {
+        for (direction, direction_string) in [(&state.c2s, "C2S"), (&state.s2c, "S2C")] {
+            for packet in &direction.packets {
+                let count = match packet.name {
+                    // interact, attack and interact at
+                    "UseEntityRequest" => 3,
+                    _ => variant_count(packet.ty, types),
+                };
+                for variant in 0..count {
+                    buffer.clear();
+                    ctx.variant = variant;
+                    if packet.name == "UseEntityRequest" {
+                        // written by hand, see `serialize_struct`
+                        buffer.extend_from_slice(&[1, variant as u8]);
+                        if variant == 2 {
+                            for _ in 0..3 {
+                                buffer.extend_from_slice(&1.5f32.to_be_bytes());
+                            }
+                        }
+                        if variant != 1 {
+                            buffer.push(1);
+                        }
+                        buffer.push(1);
+                    } else {
+                        sample(&mut buffer, packet.ty, &ctx);
+                    }
+                    write!(
+                        out,
+                        "(ConnectionState::{}, PacketDirection::{}, {:#x}, &{:?}),",
+                        state.kind.name(true),
+                        direction_string,
+                        packet.id,
+                        buffer
+                    );
+                }
+            }
+        }
+    }
+
+    *out += "];";
+}
+
+pub(super) fn write(types: &TypesMap, mut states: Vec<State>, version: VersionInfo) -> String {
+    let has_bundle_delimiter = version.has_bundle_delimiter;
     let mut out = String::with_capacity(4096);
 
     out += "
@@ -483,11 +810,17 @@ use crate::protocol::PacketDirection;
 use crate::protocol::ChunkBlockEntity;
 use crate::protocol::UnalignedSliceI64;
 use crate::protocol::UnalignedSliceU128;
-use super::de::MemoryExt;
+use crate::protocol::de::MemoryExt;
 use anyhow::{anyhow, Result};
 use std::io::{Result as IoResult, Write};
 use std::mem::size_of;
+use dune_common::nbt;
     ";
+    write!(
+        out,
+        "const NBT_FORMAT: nbt::Format = crate::protocol::nbt_format({});",
+        version.protocol
+    );
     for i in states.iter() {
         state(&mut out, types, i);
     }
@@ -517,6 +850,148 @@ use std::mem::size_of;
     }
     deserialize_fn(&mut out, &states, has_bundle_delimiter);
     serialize_fn(&mut out, &states, has_bundle_delimiter);
+    samples(&mut out, types, &states, version);
+
+    out
+}
+
+fn for_each_version_arms(out: &mut String, versions: &[&GeneratedVersion]) {
+    for version in versions {
+        write!(
+            out,
+            "$crate::protocol::VersionedPacket::{}($p) => {{
+                #[allow(unused_imports)]
+                use $crate::protocol::{}::{{self as version, Packet}};
+                $body
+            }}",
+            version.info.variant_name(),
+            version.info.module_name(),
+        );
+    }
+}
+
+pub(super) fn write_versions(versions: &[GeneratedVersion]) -> String {
+    let mut out = String::with_capacity(4096);
+
+    out += "
+use anyhow::{bail, Result};
+
+use crate::protocol::{ConnectionState, PacketDirection, PacketId, Version};
+    ";
+
+    for version in versions {
+        write!(
+            &mut out,
+            r#"pub mod {0} {{ include!(concat!(env!("OUT_DIR"), "/{0}.rs")); }}"#,
+            version.info.module_name()
+        );
+    }
+
+    out += "#[derive(Debug)] pub enum VersionedPacket<'p> {";
+    for version in versions {
+        write!(
+            &mut out,
+            "{}({}::Packet<'p>),",
+            version.info.variant_name(),
+            version.info.module_name()
+        );
+    }
+    out += "}";
+
+    for version in versions {
+        let module = version.info.module_name();
+        let variant = version.info.variant_name();
+        write!(
+            &mut out,
+            r#"
+impl<'p> From<{module}::Packet<'p>> for VersionedPacket<'p> {{
+    fn from(packet: {module}::Packet<'p>) -> Self {{
+        VersionedPacket::{variant}(packet)
+    }}
+}}
+fn deserialize_{module}<'r>(state: ConnectionState, direction: PacketDirection, id: PacketId, reader: &mut &'r [u8]) -> Result<VersionedPacket<'r>> {{
+    {module}::deserialize(state, direction, id, reader).map(VersionedPacket::{variant})
+}}
+fn serialize_{module}(writer: &mut Vec<u8>, packet: VersionedPacket) -> Result<()> {{
+    #[allow(unreachable_patterns)]
+    match packet {{
+        VersionedPacket::{variant}(p) => Ok({module}::serialize(writer, p)?),
+        _ => bail!("packet doesn't belong to version {name}"),
+    }}
+}}"#,
+            name = version.info.name,
+        );
+    }
+
+    write!(
+        &mut out,
+        "pub static VERSIONS: [Version; {}] = [",
+        versions.len()
+    );
+    for version in versions {
+        let module = version.info.module_name();
+        write!(
+            &mut out,
+            r#"Version {{
+                name: "{}",
+                protocol: {},
+                has_configuration: {},
+                deserialize: deserialize_{module},
+                serialize: serialize_{module},
+            }},"#,
+            version.info.name, version.info.protocol, version.has_configuration,
+        );
+    }
+    out += "];";
+
+    out += "impl VersionedPacket<'_> {
+    /// The version the packet was read with.
+    pub fn version(&self) -> &'static Version {
+        match self {";
+    for (index, version) in versions.iter().enumerate() {
+        write!(
+            &mut out,
+            "VersionedPacket::{}(_) => &VERSIONS[{}],",
+            version.info.variant_name(),
+            index
+        );
+    }
+    out += "}
+    }
+}";
+
+    write!(
+        &mut out,
+        "#[cfg(test)] pub(crate) static SAMPLES: [&[crate::protocol::Sample]; {}] = [",
+        versions.len()
+    );
+    for version in versions {
+        write!(&mut out, "{}::SAMPLES,", version.info.module_name());
+    }
+    out += "];";
+
+    let all: Vec<_> = versions.iter().collect();
+    let configuration: Vec<_> = versions.iter().filter(|x| x.has_configuration).collect();
+
+    out += "
+/// Matches a `VersionedPacket`, expanding the body once per version with `Packet` and
+/// `version` naming that version's packet enum and module. The `configuration` form
+/// only expands for versions with a configuration state.
+#[macro_export]
+macro_rules! for_each_version {
+    ($packet:expr, |$p:ident| $body:expr) => {
+        match $packet {";
+    for_each_version_arms(&mut out, &all);
+    out += "}
+    };
+    (configuration $packet:expr, |$p:ident| $body:expr, _ => $default:expr) => {
+        match $packet {";
+    for_each_version_arms(&mut out, &configuration);
+    out += "#[allow(unreachable_patterns)]
+            _ => $default,
+        }
+    };
+}";
 
     out
 }
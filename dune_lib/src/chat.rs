@@ -1,6 +1,7 @@
 use std::fmt::{Display, Write};
 
 use anyhow::{Result, anyhow, bail};
+use dune_common::nbt::OwnedTag;
 use serde_json::Value;
 
 struct LangFormat {
@@ -41,10 +42,67 @@ const LANG_ENG: &[LangFormat] = &[
     },
 ];
 
-#[derive(Debug)]
+/// The 16 named colors, with their legacy `§` codes and how they look.
+const COLORS: [(char, &str, &str); 16] = [
+    ('0', "black", "#000000"),
+    ('1', "dark_blue", "#0000aa"),
+    ('2', "dark_green", "#00aa00"),
+    ('3', "dark_aqua", "#00aaaa"),
+    ('4', "dark_red", "#aa0000"),
+    ('5', "dark_purple", "#aa00aa"),
+    ('6', "gold", "#ffaa00"),
+    ('7', "gray", "#aaaaaa"),
+    ('8', "dark_gray", "#555555"),
+    ('9', "blue", "#5555ff"),
+    ('a', "green", "#55ff55"),
+    ('b', "aqua", "#55ffff"),
+    ('c', "red", "#ff5555"),
+    ('d', "light_purple", "#ff55ff"),
+    ('e', "yellow", "#ffff55"),
+    ('f', "white", "#ffffff"),
+];
+
+/// How a text looks. What isn't set is taken from the parent component.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    /// A name like `dark_red`, or `#rrggbb`.
+    pub color: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+}
+
+impl Style {
+    /// This style, with what isn't set taken from `parent`.
+    pub fn inherit(&self, parent: &Style) -> Style {
+        Style {
+            color: self.color.clone().or_else(|| parent.color.clone()),
+            bold: self.bold.or(parent.bold),
+            italic: self.italic.or(parent.italic),
+            underlined: self.underlined.or(parent.underlined),
+            strikethrough: self.strikethrough.or(parent.strikethrough),
+            obfuscated: self.obfuscated.or(parent.obfuscated),
+        }
+    }
+
+    fn flags(&self) -> [(&'static str, Option<bool>); 5] {
+        [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough),
+            ("obfuscated", self.obfuscated),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChatComponent {
     Text {
         text: String,
+        style: Style,
         extra: Vec<ChatComponent>,
     },
     Translate {
@@ -56,7 +114,7 @@ pub enum ChatComponent {
 impl Display for ChatComponent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ChatComponent::Text { text, extra } => {
+            ChatComponent::Text { text, extra, .. } => {
                 f.write_str(text)?;
                 for i in extra {
                     f.write_fmt(format_args!("{}", i))?;
@@ -90,6 +148,108 @@ impl Display for ChatComponent {
     }
 }
 
+impl ChatComponent {
+    /// Text as it is, without looking for `§` codes.
+    pub fn text(text: impl Into<String>) -> ChatComponent {
+        ChatComponent::Text {
+            text: text.into(),
+            style: Style::default(),
+            extra: vec![],
+        }
+    }
+
+    /// The component as the game writes it. Translations are written as the
+    /// text they make.
+    pub fn to_json(&self) -> Value {
+        let ChatComponent::Text { text, style, extra } = self else {
+            return serde_json::json!({ "text": self.to_string() });
+        };
+        let mut result = serde_json::Map::new();
+        result.insert("text".to_string(), Value::from(text.as_str()));
+        if let Some(color) = &style.color {
+            result.insert("color".to_string(), Value::from(color.as_str()));
+        }
+        for (key, value) in style.flags() {
+            if let Some(value) = value {
+                result.insert(key.to_string(), Value::from(value));
+            }
+        }
+        if !extra.is_empty() {
+            let extra = extra.iter().map(ChatComponent::to_json).collect();
+            result.insert("extra".to_string(), Value::Array(extra));
+        }
+        Value::Object(result)
+    }
+
+    /// Markdown, with html for the colors and the underlines which it doesn't
+    /// have. New lines are kept as line breaks.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        self.write_markdown(&mut out, &Style::default());
+        out
+    }
+
+    fn write_markdown(&self, out: &mut String, parent: &Style) {
+        match self {
+            ChatComponent::Text { text, style, extra } => {
+                let style = style.inherit(parent);
+                write_markdown_text(out, text, &style);
+                for i in extra {
+                    i.write_markdown(out, &style);
+                }
+            }
+            ChatComponent::Translate { .. } => write_markdown_text(out, &self.to_string(), parent),
+        }
+    }
+}
+
+fn write_markdown_text(out: &mut String, text: &str, style: &Style) {
+    let mut open = String::new();
+    let mut close = Vec::new();
+    if let Some(color) = &style.color {
+        let color = match COLORS.iter().find(|x| x.1 == color) {
+            Some((_, _, hex)) => hex,
+            None => color.as_str(),
+        };
+        write!(open, "<span style=\"color: {}\">", color).unwrap();
+        close.push("</span>");
+    }
+    for (enabled, start, end) in [
+        (style.underlined, "<u>", "</u>"),
+        (style.bold, "**", "**"),
+        (style.italic, "*", "*"),
+        (style.strikethrough, "~~", "~~"),
+    ] {
+        if enabled == Some(true) {
+            open.push_str(start);
+            close.push(end);
+        }
+    }
+
+    for (index, line) in text.split('\n').enumerate() {
+        if index != 0 {
+            out.push_str("  \n");
+        }
+        // markdown doesn't allow spaces right inside the markers
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            out.push_str(line);
+            continue;
+        }
+        let start = line.len() - line.trim_start().len();
+        out.push_str(&line[..start]);
+        out.push_str(&open);
+        for c in trimmed.chars() {
+            if "\\`*_~#<>[]|".contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        close.iter().rev().for_each(|x| out.push_str(x));
+        out.push_str(&line[start + trimmed.len()..]);
+    }
+}
+
 macro_rules! some_or_err {
     ($opt:expr, $err:expr) => {
         match $opt {
@@ -147,27 +307,89 @@ fn parse_translate(input: &Value) -> Result<ChatComponent> {
     })
 }
 
-fn strip_old_control(x: &str) -> String {
-    let mut res = String::with_capacity(x.len());
+/// Splits text with `§` codes into components with their style. A color resets
+/// the formatting before it, like in the game.
+fn parse_legacy(text: &str, style: Style, mut extra: Vec<ChatComponent>) -> ChatComponent {
+    if !text.contains('§') {
+        return ChatComponent::Text {
+            text: text.to_string(),
+            style,
+            extra,
+        };
+    }
 
-    let mut last_is_control = false;
-    for c in x.chars() {
-        if last_is_control {
-            last_is_control = false;
+    let mut parts = Vec::new();
+    let mut current = Style::default();
+    let mut chars = text.chars();
+    let mut part = String::new();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            part.push(c);
             continue;
         }
-        if c == '§' {
-            last_is_control = true;
-        } else {
-            res.push(c);
+        let Some(code) = chars.next() else {
+            break;
+        };
+        if !part.is_empty() {
+            parts.push(ChatComponent::Text {
+                text: std::mem::take(&mut part),
+                style: current.clone(),
+                extra: vec![],
+            });
         }
+        let code = code.to_ascii_lowercase();
+        match code {
+            'k' => current.obfuscated = Some(true),
+            'l' => current.bold = Some(true),
+            'm' => current.strikethrough = Some(true),
+            'n' => current.underlined = Some(true),
+            'o' => current.italic = Some(true),
+            'r' => current = Style::default(),
+            _ => {
+                if let Some((_, name, _)) = COLORS.iter().find(|x| x.0 == code) {
+                    current = Style {
+                        color: Some(name.to_string()),
+                        ..Style::default()
+                    };
+                }
+            }
+        }
+    }
+    if !part.is_empty() {
+        parts.push(ChatComponent::Text {
+            text: part,
+            style: current,
+            extra: vec![],
+        });
+    }
+    parts.append(&mut extra);
+    ChatComponent::Text {
+        text: String::new(),
+        style,
+        extra: parts,
     }
+}
 
-    res
+fn parse_style(input: &Value) -> Style {
+    // nbt has no booleans, they're bytes
+    let flag = |key: &str| {
+        let value = input.get(key)?;
+        value.as_bool().or_else(|| value.as_i64().map(|x| x != 0))
+    };
+    Style {
+        color: input
+            .get("color")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        bold: flag("bold"),
+        italic: flag("italic"),
+        underlined: flag("underlined"),
+        strikethrough: flag("strikethrough"),
+        obfuscated: flag("obfuscated"),
+    }
 }
 
-fn parse_text(input: &Value) -> Result<ChatComponent> {
-    let text = get_str!(input, "text");
+fn parse_text(input: &Value, text: &str) -> Result<ChatComponent> {
     let arr = match input.get("extra") {
         Some(x) => {
             let arr = some_or_err!(x.as_array(), "expected array for extra");
@@ -181,10 +403,7 @@ fn parse_text(input: &Value) -> Result<ChatComponent> {
         extra.push(parse_component(i)?);
     }
 
-    Ok(ChatComponent::Text {
-        text: strip_old_control(text),
-        extra,
-    })
+    Ok(parse_legacy(text, parse_style(input), extra))
 }
 
 fn parse_component(input: &Value) -> Result<ChatComponent> {
@@ -192,19 +411,127 @@ fn parse_component(input: &Value) -> Result<ChatComponent> {
         return parse_translate(input);
     }
     if input.get("text").is_some() {
-        return parse_text(input);
+        return parse_text(input, get_str!(input, "text"));
     }
-    if let Some(text) = input.as_str() {
-        return Ok(ChatComponent::Text {
-            text: strip_old_control(text),
-            extra: vec![],
-        });
+    // what the game resolves when it shows the text is kept as it's written
+    for key in ["keybind", "selector", "nbt"] {
+        if input.get(key).is_some() {
+            return parse_text(input, get_str!(input, key));
+        }
+    }
+    if let Some(score) = input.get("score") {
+        let name = score.get("value").or_else(|| score.get("name"));
+        let name = some_or_err!(name.and_then(Value::as_str), "expected a score name");
+        return parse_text(input, name);
+    }
+    match input {
+        Value::String(x) => Ok(parse_legacy(x, Style::default(), vec![])),
+        Value::Number(_) | Value::Bool(_) => {
+            Ok(parse_legacy(&input.to_string(), Style::default(), vec![]))
+        }
+        Value::Array(x) => {
+            let extra = x.iter().map(parse_component).collect::<Result<_>>()?;
+            Ok(ChatComponent::Text {
+                text: String::new(),
+                style: Style::default(),
+                extra,
+            })
+        }
+        _ => bail!("expected a component, found {}", input),
     }
-
-    unimplemented!()
 }
 
 pub fn parse_chat(input: &str) -> Result<ChatComponent> {
     let j: Value = serde_json::from_str(input)?;
     parse_component(&j)
 }
+
+fn nbt_to_json(input: &OwnedTag) -> Value {
+    match input {
+        OwnedTag::Byte(x) => Value::from(*x),
+        OwnedTag::Short(x) => Value::from(*x),
+        OwnedTag::Int(x) => Value::from(*x),
+        OwnedTag::Long(x) => Value::from(*x),
+        OwnedTag::Float(x) => Value::from(*x),
+        OwnedTag::Double(x) => Value::from(*x),
+        OwnedTag::ByteArray(x) => Value::from(x.clone()),
+        OwnedTag::String(x) => Value::from(x.as_str()),
+        OwnedTag::List(x) => Value::Array(x.iter().map(nbt_to_json).collect()),
+        OwnedTag::Compound(x) => Value::Object(
+            x.iter()
+                .map(|(key, value)| (key.clone(), nbt_to_json(value)))
+                .collect(),
+        ),
+        OwnedTag::IntArray(x) => Value::from(x.clone()),
+        OwnedTag::LongArray(x) => Value::from(x.clone()),
+    }
+}
+
+/// Text saved as nbt, since 1.21.5. Before that it was a json string, so strings
+/// that look like json are parsed as json. What can't be parsed is kept as
+/// literal text.
+pub fn parse_chat_nbt(input: &OwnedTag) -> ChatComponent {
+    let literal = |text: &str| parse_legacy(text, Style::default(), vec![]);
+    match input {
+        OwnedTag::String(x) if x.starts_with(['{', '"', '[']) => {
+            parse_chat(x).unwrap_or_else(|_| literal(x))
+        }
+        OwnedTag::String(x) => literal(x),
+        x => {
+            let json = nbt_to_json(x);
+            parse_component(&json).unwrap_or_else(|_| literal(&json.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dune_common::nbt::OwnedTag;
+    use serde_json::json;
+
+    use crate::chat::{parse_chat, parse_chat_nbt};
+    use crate::tests::snbt_to_compound;
+
+    #[test]
+    fn formatting() {
+        let page = parse_chat(
+            r#"{"text":"","extra":[{"text":"Chapter 1\n","bold":true,"color":"dark_red"},"a §oquiet§r *day*"]}"#,
+        )
+        .unwrap();
+        assert_eq!(page.to_string(), "Chapter 1\na quiet *day*");
+        assert_eq!(
+            page.to_markdown(),
+            "<span style=\"color: #aa0000\">**Chapter 1**</span>  \na *quiet* \\*day\\*"
+        );
+        assert_eq!(
+            page.to_json(),
+            json!({"text": "", "extra": [
+                {"text": "Chapter 1\n", "color": "dark_red", "bold": true},
+                {"text": "", "extra": [
+                    {"text": "a "},
+                    {"text": "quiet", "italic": true},
+                    {"text": " *day*"},
+                ]},
+            ]})
+        );
+    }
+
+    #[test]
+    fn other_components() {
+        let text = parse_chat(
+            r#"["Press ",{"keybind":"key.jump"},{"score":{"name":"@p","objective":"kills"}},1,[" ",true]]"#,
+        )
+        .unwrap();
+        assert_eq!(text.to_string(), "Press key.jump@p1 true");
+
+        let sign = snbt_to_compound(
+            r#"{messages: [{selector: "@a", extra: [" won"]}, {nbt: "Owner", entity: "@s"}, {color: "red"}]}"#,
+        );
+        let texts: Vec<_> = match &sign["messages"] {
+            OwnedTag::List(x) => x.iter().map(|x| parse_chat_nbt(x).to_string()).collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(texts, ["@a won", "Owner", r#"{"color":"red"}"#]);
+        assert_eq!(parse_chat_nbt(&OwnedTag::Int(5)).to_string(), "5");
+    }
+}
@@ -1,4 +1,3 @@
-use std::borrow::Borrow;
 use std::io::{BufRead, Read, Write, stdin};
 use std::net::TcpStream;
 use std::sync::Arc;
@@ -7,23 +6,24 @@ use std::thread;
 
 use aes::cipher::AsyncStreamCipher;
 use anyhow::Result;
-use dune_data::protocol::common_states::handshaking::SetProtocolRequest;
-use dune_data::protocol::common_states::login::LoginStartRequest;
-use dune_data::protocol::de::MD;
+use dune_data::for_each_version;
 use dune_data::protocol::v1_20_2::Packet;
-use dune_data::protocol::v1_20_2::play::KeepAliveRequest;
+use dune_data::protocol::v1_20_2::handshaking::SetProtocolRequest;
+use dune_data::protocol::v1_20_2::login::LoginStartRequest;
 use dune_data::protocol::varint::{VarintSerialized, write_varint, write_varint_serialize};
-use dune_data::protocol::{self, ConnectionState, PacketDirection};
+use dune_data::protocol::{self, ConnectionState, PacketDirection, Version, VersionedPacket};
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
 use log::warn;
 use polling::{Event, Poller};
 
 use crate::Buffer;
-use crate::record::AuthData;
+use crate::record::{AuthData, crypt_reply};
 
 pub(crate) type Aes128Cfb8 = cfb8::Cfb8<aes::Aes128>;
 
+const PROTOCOL_VERSION: i32 = 764;
+
 #[derive(Default)]
 pub(crate) struct ClientReader {
     pub(crate) buffer: Buffer,
@@ -59,19 +59,15 @@ impl ClientWriter {
         }
     }
 
-    pub(crate) fn send_packet<'x, P, Q>(&mut self, packet: P) -> Result<()>
-    where
-        Q: MD<'x>,
-        P: Borrow<Q>,
-    {
+    pub(crate) fn send_packet<'p>(&mut self, packet: impl Into<VersionedPacket<'p>>) -> Result<()> {
         self.tmp.clear();
         self.tmp2.clear();
         // can we do this with only one tmp? mojang :squint:
 
         let start_offset = self.buffer.len();
 
-        // TODO: use the serialize fn
-        packet.borrow().serialize(&mut self.tmp)?;
+        let tmp = &mut self.tmp;
+        for_each_version!(packet.into(), |p| version::serialize(tmp, p))?;
 
         let (packet_buffer, data_length) = match self.compression_threshold {
             Some(threshold) if self.tmp.len() >= threshold => {
@@ -116,33 +112,47 @@ struct Session {
 struct Client {
     compression: bool,
     state: ConnectionState,
+    version: &'static Version,
 }
 impl Client {
     fn new() -> Client {
         Client {
             compression: false,
             state: ConnectionState::Login,
+            version: Version::from_protocol(PROTOCOL_VERSION).unwrap(),
         }
     }
 }
 
-fn send_start(client: &mut ClientWriter, username: &str, server_host: (&str, u16)) -> Result<()> {
+fn send_start(
+    client: &mut ClientWriter,
+    auth_data: &AuthData,
+    server_host: (&str, u16),
+) -> Result<()> {
     let (addr, port) = server_host;
     let p = SetProtocolRequest {
-        protocol_version: 758,
+        protocol_version: PROTOCOL_VERSION,
         server_host: addr,
         server_port: port,
         next_state: ConnectionState::Login as i32,
     };
-    client.send_packet(p)?;
+    client.send_packet(Packet::SetProtocolRequest(p))?;
 
-    let p = LoginStartRequest { username };
-    client.send_packet(p)?;
+    let player_uuid = u128::from_str_radix(&auth_data.selected_profile.replace('-', ""), 16)?;
+    let p = LoginStartRequest {
+        username: &auth_data.name,
+        player_uuid,
+    };
+    client.send_packet(Packet::LoginStartRequest(p))?;
 
     Ok(())
 }
 
-fn handle_packet(_client: &mut Client, _writer: &mut ClientWriter, _packet: Packet) -> Result<()> {
+fn handle_packet(
+    _client: &mut Client,
+    _writer: &mut ClientWriter,
+    _packet: VersionedPacket,
+) -> Result<()> {
     // if let Packet::PlayerChatResponse(x) = packet {
     // println!("{}", chat::parse_chat(x.message)?);
     // }
@@ -153,7 +163,7 @@ fn handle_packet(_client: &mut Client, _writer: &mut ClientWriter, _packet: Pack
 fn read_packet(
     client: &mut Client,
     session: &mut Session,
-    _auth_data: &mut AuthData,
+    auth_data: &mut AuthData,
 ) -> Result<bool> {
     let Some(packet_data) = protocol::read_packet_info(
         &session.reader.buffer,
@@ -164,7 +174,7 @@ fn read_packet(
         return Ok(false);
     };
     let mut data = packet_data.data;
-    let packet = protocol::v1_20_2::deserialize(
+    let packet = (client.version.deserialize)(
         client.state,
         PacketDirection::S2C,
         packet_data.id,
@@ -173,32 +183,83 @@ fn read_packet(
 
     // println!("{:?}", packet);
     // system packets
-    match packet {
-        // Packet::SuccessResponse(_) => {
-        //     client.state = ConnectionState::Play;
-        // }
-        // Packet::CompressResponse(x) => {
-        //     client.compression = x.threshold >= 0;
-        //     session.writer.compression_threshold = Some(x.threshold.try_into()?);
-        // }
-        // Packet::EncryptionBeginResponse(packet) => {
-        //     let (c1, c2) = crypt_reply(packet, auth_data, &mut session.writer)?;
-        //     session.reader.crypt = Some(c1);
-        //     session.writer.crypt = Some(c2);
-        // }
+    let handled = for_each_version!(&packet, |p| match p {
+        Packet::SuccessResponse(_) => {
+            client.state = ConnectionState::after_login(client.version.protocol);
+            send_login_acknowledged(&mut session.writer, &packet)?;
+            true
+        }
+        Packet::CompressResponse(x) => {
+            client.compression = x.threshold >= 0;
+            session.writer.compression_threshold = x.threshold.try_into().ok();
+            true
+        }
+        Packet::EncryptionBeginResponse(x) => {
+            let reply = crypt_reply(x.server_id, x.public_key, x.verify_token, auth_data)?;
+            let p = version::login::EncryptionBeginRequest {
+                shared_secret: &reply.shared_secret,
+                verify_token: &reply.verify_token,
+            };
+            session
+                .writer
+                .send_packet(Packet::EncryptionBeginRequest(p))?;
+            session.reader.crypt = Some(reply.decrypt);
+            session.writer.crypt = Some(reply.encrypt);
+            true
+        }
         Packet::KeepAliveResponse(x) => {
-            let p = KeepAliveRequest {
+            let p = version::play::KeepAliveRequest {
                 keep_alive_id: x.keep_alive_id,
             };
-            session.writer.send_packet(p)?;
+            session.writer.send_packet(Packet::KeepAliveRequest(p))?;
+            true
         }
-        _ => handle_packet(client, &mut session.writer, packet)?,
+        _ => false,
+    });
+    let handled = handled
+        || for_each_version!(configuration &packet, |p| match p {
+            Packet::ConfigKeepAliveResponse(x) => {
+                let p = version::configuration::ConfigKeepAliveRequest {
+                    keep_alive_id: x.keep_alive_id,
+                };
+                session
+                    .writer
+                    .send_packet(Packet::ConfigKeepAliveRequest(p))?;
+                true
+            }
+            Packet::ConfigFinishConfigurationResponse(_) => {
+                client.state = ConnectionState::Play;
+                let p = version::configuration::ConfigFinishConfigurationRequest {};
+                session
+                    .writer
+                    .send_packet(Packet::ConfigFinishConfigurationRequest(p))?;
+                true
+            }
+            Packet::StartConfigurationResponse(_) => {
+                client.state = ConnectionState::Configuration;
+                let p = version::play::ConfigurationAcknowledgedRequest {};
+                session
+                    .writer
+                    .send_packet(Packet::ConfigurationAcknowledgedRequest(p))?;
+                true
+            }
+            _ => false,
+        }, _ => false);
+    if !handled {
+        handle_packet(client, &mut session.writer, packet)?;
     }
     session.reader.buffer.advance(packet_data.total_size);
 
     Ok(true)
 }
 
+fn send_login_acknowledged(writer: &mut ClientWriter, packet: &VersionedPacket) -> Result<()> {
+    for_each_version!(configuration packet, |_p| {
+        let p = version::login::LoginAcknowledgedRequest {};
+        writer.send_packet(Packet::LoginAcknowledgedRequest(p))
+    }, _ => Ok(()))
+}
+
 fn on_stdin_line(_writer: &mut ClientWriter, _line: String) -> Result<()> {
     // let line = line.trim();
     // if !line.is_empty() {
@@ -247,7 +308,7 @@ pub fn run(mut auth_data: AuthData, server_host: (&str, u16)) -> Result<()> {
     let mut buffer = [0; 4096];
 
     let receiver = spawn_stdin_thread(poller.clone())?;
-    send_start(&mut session.writer, &auth_data.name, server_host)?;
+    send_start(&mut session.writer, &auth_data, server_host)?;
 
     loop {
         events.clear();
@@ -1,5 +1,7 @@
 use anyhow::Result;
-pub use dune_data::protocol::v1_20_2::play::{TradeListResponse, UseEntityKind};
+use dune_data::for_each_version;
+pub use dune_data::protocol::{InventorySlot, UseEntityKind};
+use dune_data::protocol::{Version, VersionedPacket, de};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct PositionInt {
@@ -7,31 +9,395 @@ pub struct PositionInt {
     pub y: i32,
     pub z: i32,
 }
+impl From<de::Position> for PositionInt {
+    fn from(p: de::Position) -> Self {
+        PositionInt {
+            x: p.x,
+            y: p.y,
+            z: p.z,
+        }
+    }
+}
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
     pub z: f64,
 }
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlayerPosition {
+    pub position: Position,
+    /// Which of x, y and z are deltas from the last known position. Only the
+    /// server sends them, to teleport the player.
+    pub relative: [bool; 3],
+}
+impl PlayerPosition {
+    /// Where the player ends up, coming from `last`.
+    pub fn apply(&self, last: Position) -> Position {
+        let pick = |relative: bool, value: f64, last: f64| match relative {
+            true => last + value,
+            false => value,
+        };
+        Position {
+            x: pick(self.relative[0], self.position.x, last.x),
+            y: pick(self.relative[1], self.position.y, last.y),
+            z: pick(self.relative[2], self.position.z, last.z),
+        }
+    }
+}
 pub struct UseEntity {
     pub entity_id: i32,
     pub kind: UseEntityKind,
 }
 
+#[derive(Debug, Copy, Clone)]
+pub enum ChatMessage<'x> {
+    /// A json text component, as sent by the server for system messages.
+    Json(&'x str),
+    /// A message typed by a player.
+    Player { sender: u128, message: &'x str },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct EntitySpawn {
+    pub entity_id: i32,
+    pub uuid: u128,
+    /// `None` for players spawned with the old named entity packet.
+    pub entity_type: Option<i32>,
+    pub position: Position,
+}
+#[derive(Debug, Copy, Clone)]
+pub struct EntityMove {
+    pub entity_id: i32,
+    pub position: Position,
+    /// If set, `position` is a delta from the last known position.
+    pub relative: bool,
+}
+
+#[derive(Debug)]
+pub enum InventoryChange<'x> {
+    Slot {
+        window_id: i32,
+        slot: i16,
+        item: InventorySlot<'x>,
+    },
+    Window {
+        window_id: i32,
+        items: Vec<InventorySlot<'x>>,
+        carried_item: InventorySlot<'x>,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BlockChange {
+    pub position: PositionInt,
+    pub state: i32,
+}
+
+#[derive(Debug)]
+pub struct Trade<'x> {
+    pub input_item_1: InventorySlot<'x>,
+    pub input_item_2: InventorySlot<'x>,
+    pub output_item: InventorySlot<'x>,
+    pub trade_disabled: bool,
+    pub uses: i32,
+    pub max_uses: i32,
+    pub xp: i32,
+    pub special_price: i32,
+    pub price_multiplier: f32,
+    pub demand: i32,
+}
+#[derive(Debug)]
+pub struct Trades<'x> {
+    /// What the slots are read with.
+    pub version: &'static Version,
+    pub window_id: i32,
+    pub trades: Vec<Trade<'x>>,
+    pub villager_level: i32,
+    pub experience: i32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Time {
+    pub world_age: i64,
+    pub time_of_day: i64,
+}
+#[derive(Debug, Copy, Clone)]
+pub struct Health {
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+}
+
 pub trait EventSubscriber: Sync {
-    fn on_chat(&mut self, _message: &str) -> Result<()> {
+    fn on_chat(&mut self, _message: ChatMessage) -> Result<()> {
         Ok(())
     }
     fn player_info(&mut self, _name: &str, _uuid: u128) -> Result<()> {
         Ok(())
     }
-    fn position(&mut self, _pos: Position) -> Result<()> {
+    fn position(&mut self, _pos: PlayerPosition) -> Result<()> {
+        Ok(())
+    }
+    fn entity_spawn(&mut self, _spawn: EntitySpawn) -> Result<()> {
+        Ok(())
+    }
+    fn entity_move(&mut self, _entity_move: EntityMove) -> Result<()> {
         Ok(())
     }
-    fn trades(&mut self, _trades: TradeListResponse) -> Result<()> {
+    fn entity_remove(&mut self, _entity_ids: &[i32]) -> Result<()> {
+        Ok(())
+    }
+    fn inventory(&mut self, _change: InventoryChange) -> Result<()> {
+        Ok(())
+    }
+    fn block_change(&mut self, _change: BlockChange) -> Result<()> {
+        Ok(())
+    }
+    fn trades(&mut self, _trades: Trades) -> Result<()> {
         Ok(())
     }
     fn interact(&mut self, _use_entity: UseEntity) -> Result<()> {
         Ok(())
     }
+    fn time(&mut self, _time: Time) -> Result<()> {
+        Ok(())
+    }
+    fn health(&mut self, _health: Health) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Like `for_each_version!`, but only for the listed versions, for packets that
+/// don't exist everywhere. Everything else is bound to `$rest`.
+macro_rules! for_versions {
+    (
+        $packet:expr,
+        [$($variant:ident => $module:ident),*],
+        |$p:ident| $body:expr,
+        $rest:ident => $default:expr
+    ) => {
+        match $packet {
+            $(VersionedPacket::$variant($p) => {
+                #[allow(unused_imports)]
+                use dune_data::protocol::$module::{self as version, Packet};
+                $body
+            })*
+            #[allow(unreachable_patterns)]
+            $rest => $default,
+        }
+    };
+}
+
+fn relative(d_x: i16, d_y: i16, d_z: i16) -> Position {
+    Position {
+        x: d_x as f64 / 4096.0,
+        y: d_y as f64 / 4096.0,
+        z: d_z as f64 / 4096.0,
+    }
+}
+
+/// Turns a packet of any supported version into the events it stands for.
+pub fn dispatch(handler: &mut dyn EventSubscriber, packet: VersionedPacket) -> Result<()> {
+    let packet = for_versions!(packet, [V1_18_2 => v1_18_2], |p| match p {
+        Packet::ChatResponse(p) => return handler.on_chat(ChatMessage::Json(p.message)),
+        Packet::SpawnEntityLivingResponse(p) => {
+            return handler.entity_spawn(EntitySpawn {
+                entity_id: p.entity_id,
+                uuid: p.entity_uuid,
+                entity_type: Some(p.type_),
+                position: Position {
+                    x: p.x,
+                    y: p.y,
+                    z: p.z,
+                },
+            });
+        }
+        p => VersionedPacket::from(p),
+    }, x => x);
+
+    let packet = for_versions!(packet, [
+        V1_18_2 => v1_18_2,
+        V1_19_4 => v1_19_4,
+        V1_20_1 => v1_20_1
+    ], |p| match p {
+        Packet::NamedEntitySpawnResponse(p) => {
+            return handler.entity_spawn(EntitySpawn {
+                entity_id: p.entity_id,
+                uuid: p.player_uuid,
+                entity_type: None,
+                position: Position {
+                    x: p.x,
+                    y: p.y,
+                    z: p.z,
+                },
+            });
+        }
+        p => VersionedPacket::from(p),
+    }, x => x);
+
+    let packet = for_versions!(packet, [
+        V1_19_4 => v1_19_4,
+        V1_20_1 => v1_20_1,
+        V1_20_2 => v1_20_2,
+        V1_20_4 => v1_20_4,
+        V1_21 => v1_21
+    ], |p| match p {
+        Packet::PlayerChatResponse(p) => {
+            return handler.on_chat(ChatMessage::Player {
+                sender: p.sender_uuid,
+                message: p.plain_message,
+            });
+        }
+        Packet::SystemChatResponse(p) => return handler.on_chat(ChatMessage::Json(p.content)),
+        p => VersionedPacket::from(p),
+    }, x => x);
+
+    let packet_version = packet.version();
+    for_each_version!(packet, |p| match p {
+        Packet::SuccessResponse(p) => handler.player_info(p.username, p.uuid),
+        Packet::PositionRequest(p) => handler.position(PlayerPosition {
+            position: Position {
+                x: p.x,
+                y: p.y,
+                z: p.z,
+            },
+            relative: [false; 3],
+        }),
+        Packet::PositionResponse(p) => handler.position(PlayerPosition {
+            position: Position {
+                x: p.x,
+                y: p.y,
+                z: p.z,
+            },
+            relative: [0, 1, 2].map(|bit| p.flags & (1 << bit) != 0),
+        }),
+        Packet::UseEntityRequest(p) => handler.interact(UseEntity {
+            entity_id: p.entity_id,
+            kind: p.kind,
+        }),
+        Packet::SpawnEntityResponse(p) => handler.entity_spawn(EntitySpawn {
+            entity_id: p.entity_id,
+            uuid: p.object_uuid,
+            entity_type: Some(p.type_),
+            position: Position {
+                x: p.x,
+                y: p.y,
+                z: p.z,
+            },
+        }),
+        Packet::RelEntityMoveResponse(p) => handler.entity_move(EntityMove {
+            entity_id: p.entity_id,
+            position: relative(p.d_x, p.d_y, p.d_z),
+            relative: true,
+        }),
+        Packet::EntityMoveLookResponse(p) => handler.entity_move(EntityMove {
+            entity_id: p.entity_id,
+            position: relative(p.d_x, p.d_y, p.d_z),
+            relative: true,
+        }),
+        Packet::EntityTeleportResponse(p) => handler.entity_move(EntityMove {
+            entity_id: p.entity_id,
+            position: Position {
+                x: p.x,
+                y: p.y,
+                z: p.z,
+            },
+            relative: false,
+        }),
+        Packet::EntityDestroyResponse(p) => handler.entity_remove(&p.entity_ids),
+        Packet::SetSlotResponse(p) => handler.inventory(InventoryChange::Slot {
+            window_id: p.window_id as i32,
+            slot: p.slot,
+            item: p.item,
+        }),
+        Packet::WindowItemsResponse(p) => handler.inventory(InventoryChange::Window {
+            window_id: p.window_id as i32,
+            items: p.items,
+            carried_item: p.carried_item,
+        }),
+        Packet::BlockChangeResponse(p) => handler.block_change(BlockChange {
+            position: p.location.into(),
+            state: p.type_,
+        }),
+        Packet::TradeListResponse(p) => handler.trades(Trades {
+            version: packet_version,
+            window_id: p.window_id,
+            trades: p
+                .trades
+                .into_iter()
+                .map(|x| Trade {
+                    input_item_1: x.input_item_1,
+                    input_item_2: x.input_item_2,
+                    output_item: x.output_item,
+                    trade_disabled: x.trade_disabled,
+                    uses: x.nb_trade_uses,
+                    max_uses: x.maximum_nb_trade_uses,
+                    xp: x.xp,
+                    special_price: x.special_price,
+                    price_multiplier: x.price_multiplier,
+                    demand: x.demand,
+                })
+                .collect(),
+            villager_level: p.villager_level,
+            experience: p.experience,
+        }),
+        Packet::UpdateTimeResponse(p) => handler.time(Time {
+            world_age: p.age,
+            time_of_day: p.time,
+        }),
+        Packet::UpdateHealthResponse(p) => handler.health(Health {
+            health: p.health,
+            food: p.food,
+            saturation: p.food_saturation,
+        }),
+        _ => Ok(()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use dune_data::protocol::v1_20_2::{Packet, play::PositionResponse};
+
+    use crate::events::{EventSubscriber, PlayerPosition, Position, dispatch};
+
+    #[test]
+    fn relative_teleport() {
+        struct Last(Option<PlayerPosition>);
+        impl EventSubscriber for Last {
+            fn position(&mut self, pos: PlayerPosition) -> Result<()> {
+                self.0 = Some(pos);
+                Ok(())
+            }
+        }
+
+        let packet = PositionResponse {
+            x: 10.0,
+            y: 64.0,
+            z: -2.5,
+            yaw: 0.0,
+            pitch: 0.0,
+            // x and z, and the rotation
+            flags: 0x01 | 0x04 | 0x08 | 0x10,
+            teleport_id: 1,
+        };
+        let mut last = Last(None);
+        dispatch(&mut last, Packet::PositionResponse(packet).into()).unwrap();
+        let pos = last.0.unwrap();
+        assert_eq!(pos.relative, [true, false, true]);
+        let from = Position {
+            x: 100.0,
+            y: 70.0,
+            z: 100.0,
+        };
+        assert_eq!(
+            pos.apply(from),
+            Position {
+                x: 110.0,
+                y: 64.0,
+                z: 97.5
+            }
+        );
+    }
 }
@@ -0,0 +1,600 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+use dune_common::nbt::{self, Compound, OwnedTag};
+use dune_data::protocol::{InventorySlot, Version};
+
+use crate::chat::{ChatComponent, parse_chat_nbt};
+use crate::{Enchantment, Item};
+
+/// An id from one of the game's registries. The tables end at some version, so
+/// newer ids and the ones of datapacks and mods are kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Id<T> {
+    Known(T),
+    /// Like `minecraft:mace`.
+    Unknown(String),
+}
+impl<T: PartialEq> PartialEq<T> for Id<T> {
+    fn eq(&self, other: &T) -> bool {
+        matches!(self, Id::Known(x) if x == other)
+    }
+}
+/// The name of the variant, or the id if it's unknown.
+impl<T: fmt::Debug> fmt::Display for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Known(x) => write!(f, "{:?}", x),
+            Id::Unknown(x) => f.write_str(x),
+        }
+    }
+}
+
+/// Like `minecraft:diamond`.
+pub fn item_id(id: &str) -> Id<Item> {
+    Item::from_str_id(id).map_or_else(|_| Id::Unknown(id.to_string()), Id::Known)
+}
+/// Like `minecraft:sharpness`.
+pub fn enchantment_id(id: &str) -> Id<Enchantment> {
+    Enchantment::from(id).map_or_else(|_| Id::Unknown(id.to_string()), Id::Known)
+}
+
+/// How many of the item fit in a slot. The item tables don't have it, so it's
+/// worked out from the name, unknown items stack to 64.
+pub fn max_stack_size(item: &Id<Item>) -> i32 {
+    let name = match item {
+        // `CarrotOnAStick` to `carrot_on_a_stick`
+        Id::Known(x) => {
+            let mut name = String::new();
+            for c in format!("{:?}", x).chars() {
+                if c.is_ascii_uppercase() && !name.is_empty() {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_lowercase());
+            }
+            name
+        }
+        Id::Unknown(x) => x.strip_prefix("minecraft:").unwrap_or(x).to_string(),
+    };
+    const SINGLE: [&str; 22] = [
+        "_sword",
+        "_shovel",
+        "_pickaxe",
+        "_axe",
+        "_hoe",
+        "_helmet",
+        "_chestplate",
+        "_leggings",
+        "_boots",
+        "_horse_armor",
+        "_bed",
+        "shulker_box",
+        "_boat",
+        "_raft",
+        "minecart",
+        "potion",
+        "_stew",
+        "_soup",
+        "_on_a_stick",
+        "_bucket",
+        "_banner_pattern",
+        "_book",
+    ];
+    match name.as_str() {
+        "bucket" | "snowball" | "egg" | "ender_pearl" | "armor_stand" | "honey_bottle"
+        | "written_book" => 16,
+        "bow" | "crossbow" | "trident" | "shield" | "elytra" | "totem_of_undying"
+        | "fishing_rod" | "flint_and_steel" | "shears" | "saddle" | "cake" | "bundle"
+        | "spyglass" | "goat_horn" | "brush" | "mace" | "debug_stick" | "milk_bucket" => 1,
+        x if x.ends_with("_sign") || x.ends_with("_banner") => 16,
+        x if x.starts_with("music_disc") || x.ends_with("_bundle") => 1,
+        x if SINGLE.iter().any(|y| x.ends_with(y)) => 1,
+        _ => 64,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnchantmentLevel {
+    pub enchantment: Id<Enchantment>,
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PotionEffect {
+    /// Like `minecraft:speed`, or the numeric id in older versions.
+    pub id: String,
+    pub amplifier: u8,
+    /// In ticks.
+    pub duration: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Book {
+    /// Only written books have a title and an author.
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Only written books have formatting.
+    pub pages: Vec<ChatComponent>,
+}
+
+/// What an item has on top of its id and count, from its nbt `tag` before 1.20.5
+/// and from its components since.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ItemData {
+    pub name: Option<String>,
+    pub lore: Vec<String>,
+    pub enchantments: Vec<EnchantmentLevel>,
+    /// The ones in enchanted books.
+    pub stored_enchantments: Vec<EnchantmentLevel>,
+    pub damage: i32,
+    pub repair_cost: i32,
+    /// The items of bundles, and of shulker boxes and other blocks that keep them
+    /// when broken.
+    pub contents: Vec<ItemStack>,
+    /// Like `minecraft:strong_healing`.
+    pub potion: Option<String>,
+    pub custom_effects: Vec<PotionEffect>,
+    pub custom_model_data: Option<i32>,
+    pub book: Option<Book>,
+    /// Everything that isn't decoded above.
+    pub rest: Compound,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub item: Id<Item>,
+    pub count: u8,
+    pub data: ItemData,
+}
+
+pub(crate) fn get_int(tag: &OwnedTag) -> Option<i32> {
+    match *tag {
+        OwnedTag::Byte(x) => Some(x as i32),
+        OwnedTag::Short(x) => Some(x as i32),
+        OwnedTag::Int(x) => Some(x),
+        OwnedTag::Long(x) => Some(x as i32),
+        _ => None,
+    }
+}
+pub(crate) fn take_int(tag: &mut Compound, key: &str) -> Option<i32> {
+    tag.shift_remove(key).as_ref().and_then(get_int)
+}
+pub(crate) fn take_string(tag: &mut Compound, key: &str) -> Option<String> {
+    match tag.shift_remove(key)? {
+        OwnedTag::String(x) => Some(x),
+        _ => None,
+    }
+}
+pub(crate) fn take_list(tag: &mut Compound, key: &str) -> Vec<OwnedTag> {
+    match tag.shift_remove(key) {
+        Some(OwnedTag::List(x)) => x,
+        _ => Vec::new(),
+    }
+}
+pub(crate) fn take_compound(tag: &mut Compound, key: &str) -> Option<Compound> {
+    match tag.shift_remove(key)? {
+        OwnedTag::Compound(x) => Some(x),
+        _ => None,
+    }
+}
+
+fn text(tag: &OwnedTag) -> String {
+    parse_chat_nbt(tag).to_string()
+}
+fn level(level: i32) -> u8 {
+    level.clamp(0, u8::MAX as i32) as u8
+}
+
+/// `[{id: "minecraft:sharpness", lvl: 5s}]`, before 1.20.5.
+fn enchantments_from_list(list: Vec<OwnedTag>) -> Vec<EnchantmentLevel> {
+    let mut result = Vec::with_capacity(list.len());
+    for x in list {
+        let OwnedTag::Compound(mut x) = x else {
+            continue;
+        };
+        // numeric ids are from before 1.13
+        let Some(id) = take_string(&mut x, "id") else {
+            continue;
+        };
+        result.push(EnchantmentLevel {
+            enchantment: enchantment_id(&id),
+            level: level(take_int(&mut x, "lvl").unwrap_or(0)),
+        });
+    }
+    result
+}
+/// `{levels: {"minecraft:sharpness": 5}}`, or without `levels` since 1.21.5.
+fn enchantments_from_component(mut tag: Compound) -> Vec<EnchantmentLevel> {
+    let levels = take_compound(&mut tag, "levels").unwrap_or(tag);
+    let mut result = Vec::with_capacity(levels.len());
+    for (id, value) in levels {
+        result.push(EnchantmentLevel {
+            enchantment: enchantment_id(&id),
+            level: level(get_int(&value).unwrap_or(0)),
+        });
+    }
+    result
+}
+
+fn effects(list: Vec<OwnedTag>) -> Vec<PotionEffect> {
+    let mut result = Vec::with_capacity(list.len());
+    for x in list {
+        let OwnedTag::Compound(mut x) = x else {
+            continue;
+        };
+        let id = match x.shift_remove("id").or_else(|| x.shift_remove("Id")) {
+            Some(OwnedTag::String(x)) => x,
+            Some(x) => get_int(&x).unwrap_or(0).to_string(),
+            None => continue,
+        };
+        let amplifier = take_int(&mut x, "amplifier").or_else(|| take_int(&mut x, "Amplifier"));
+        let duration = take_int(&mut x, "duration").or_else(|| take_int(&mut x, "Duration"));
+        result.push(PotionEffect {
+            id,
+            amplifier: level(amplifier.unwrap_or(0)),
+            duration: duration.unwrap_or(0),
+        });
+    }
+    result
+}
+
+/// Since 1.20.5 a book's text is `{raw: ...}`, with a filtered version next to it.
+fn book_raw(tag: OwnedTag) -> OwnedTag {
+    match tag {
+        OwnedTag::Compound(mut x) if x.contains_key("raw") => x.shift_remove("raw").unwrap(),
+        x => x,
+    }
+}
+fn book_page(tag: OwnedTag, is_written: bool) -> ChatComponent {
+    match tag {
+        // only written books have json pages
+        OwnedTag::String(x) if !is_written => ChatComponent::text(x),
+        x => parse_chat_nbt(&x),
+    }
+}
+
+impl ItemData {
+    /// From the `tag` of an item, before 1.20.5.
+    pub fn from_tag(mut tag: Compound, item: &Id<Item>) -> Result<ItemData> {
+        let mut data = ItemData::default();
+
+        if let Some(mut display) = take_compound(&mut tag, "display") {
+            if let Some(name) = display.shift_remove("Name") {
+                data.name = Some(text(&name));
+            }
+            for x in take_list(&mut display, "Lore") {
+                data.lore.push(text(&x));
+            }
+            if !display.is_empty() {
+                data.rest
+                    .insert("display".to_string(), OwnedTag::Compound(display));
+            }
+        }
+        data.enchantments = enchantments_from_list(take_list(&mut tag, "Enchantments"));
+        data.stored_enchantments =
+            enchantments_from_list(take_list(&mut tag, "StoredEnchantments"));
+        data.damage = take_int(&mut tag, "Damage").unwrap_or(0);
+        data.repair_cost = take_int(&mut tag, "RepairCost").unwrap_or(0);
+
+        if let Some(mut block_entity) = take_compound(&mut tag, "BlockEntityTag") {
+            data.contents = read_items(take_list(&mut block_entity, "Items"))?;
+            if !block_entity.is_empty() {
+                let value = OwnedTag::Compound(block_entity);
+                data.rest.insert("BlockEntityTag".to_string(), value);
+            }
+        }
+        if *item == Item::Bundle {
+            data.contents = read_items(take_list(&mut tag, "Items"))?;
+        }
+        data.potion = take_string(&mut tag, "Potion");
+        data.custom_effects = effects(take_list(&mut tag, "CustomPotionEffects"));
+        data.custom_model_data = take_int(&mut tag, "CustomModelData");
+
+        if *item == Item::WritableBook || *item == Item::WrittenBook {
+            let is_written = *item == Item::WrittenBook;
+            let pages = take_list(&mut tag, "pages")
+                .into_iter()
+                .map(|x| book_page(x, is_written))
+                .collect();
+            data.book = Some(Book {
+                title: take_string(&mut tag, "title"),
+                author: take_string(&mut tag, "author"),
+                pages,
+            });
+        }
+
+        data.rest.extend(tag);
+        Ok(data)
+    }
+
+    /// From the components of an item, since 1.20.5.
+    pub fn from_components(components: Compound) -> Result<ItemData> {
+        let mut data = ItemData::default();
+        for (key, value) in components {
+            let name = key.strip_prefix("minecraft:").unwrap_or(&key);
+            match (name, value) {
+                ("custom_name", x) => data.name = Some(text(&x)),
+                ("lore", OwnedTag::List(x)) => {
+                    data.lore = x.iter().map(text).collect();
+                }
+                ("enchantments", OwnedTag::Compound(x)) => {
+                    data.enchantments = enchantments_from_component(x);
+                }
+                ("stored_enchantments", OwnedTag::Compound(x)) => {
+                    data.stored_enchantments = enchantments_from_component(x);
+                }
+                ("damage", x) if get_int(&x).is_some() => data.damage = get_int(&x).unwrap(),
+                ("repair_cost", x) if get_int(&x).is_some() => {
+                    data.repair_cost = get_int(&x).unwrap();
+                }
+                ("container", OwnedTag::List(x)) => {
+                    // [{slot: 0, item: {...}}]
+                    let items = x
+                        .into_iter()
+                        .filter_map(|x| match x {
+                            OwnedTag::Compound(mut x) => x.shift_remove("item"),
+                            _ => None,
+                        })
+                        .collect();
+                    data.contents = read_items(items)?;
+                }
+                ("bundle_contents", OwnedTag::List(x)) => data.contents = read_items(x)?,
+                ("potion_contents", OwnedTag::String(x)) => data.potion = Some(x),
+                ("potion_contents", OwnedTag::Compound(mut x)) => {
+                    data.potion = take_string(&mut x, "potion");
+                    data.custom_effects = effects(take_list(&mut x, "custom_effects"));
+                }
+                ("custom_model_data", OwnedTag::Compound(mut x)) => {
+                    // since 1.21.4 it's a list of values for the model to pick from
+                    let first = take_list(&mut x, "floats").into_iter().next();
+                    data.custom_model_data = match first {
+                        Some(OwnedTag::Float(x)) => Some(x as i32),
+                        _ => None,
+                    };
+                }
+                ("custom_model_data", x) if get_int(&x).is_some() => {
+                    data.custom_model_data = get_int(&x);
+                }
+                ("written_book_content" | "writable_book_content", OwnedTag::Compound(mut x)) => {
+                    let is_written = name == "written_book_content";
+                    let title = match x.shift_remove("title").map(book_raw) {
+                        Some(OwnedTag::String(x)) => Some(x),
+                        _ => None,
+                    };
+                    let pages = take_list(&mut x, "pages")
+                        .into_iter()
+                        .map(|x| book_page(book_raw(x), is_written))
+                        .collect();
+                    data.book = Some(Book {
+                        title,
+                        author: take_string(&mut x, "author"),
+                        pages,
+                    });
+                }
+                (_, value) => {
+                    data.rest.insert(key, value);
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl ItemStack {
+    /// An item as it's saved, `{id, Count, tag}` or `{id, count, components}` since
+    /// 1.20.5. `None` for empty slots, which are `{}` or air.
+    pub fn from_nbt(mut tag: Compound) -> Result<Option<ItemStack>> {
+        let Some(id) = take_string(&mut tag, "id") else {
+            return Ok(None);
+        };
+        let item = item_id(&id);
+        // the count is left out when it's 1 since 1.20.5
+        let count = take_int(&mut tag, "Count")
+            .or_else(|| take_int(&mut tag, "count"))
+            .unwrap_or(1);
+        if item == Item::Air || count <= 0 {
+            return Ok(None);
+        }
+
+        let data = if let Some(components) = take_compound(&mut tag, "components") {
+            ItemData::from_components(components)?
+        } else if let Some(x) = take_compound(&mut tag, "tag") {
+            ItemData::from_tag(x, &item)?
+        } else {
+            ItemData::default()
+        };
+        Ok(Some(ItemStack {
+            item,
+            count: level(count),
+            data,
+        }))
+    }
+
+    /// `None` for empty slots. Fails for the versions without an item table, the
+    /// ids shift whenever items are added.
+    pub fn from_protocol(slot: &InventorySlot, version: &Version) -> Result<Option<ItemStack>> {
+        let Some(slot) = &slot.data else {
+            return Ok(None);
+        };
+        let id = slot.item_id.try_into()?;
+        let item = match version.protocol {
+            758 => Item::from_1_18_2(id)?,
+            // 1.20.2 added no items
+            763 | 764 => Item::from_1_20_2(id)?,
+            // 1.19.4 and 1.20.3 have the next version's items behind a flag, and
+            // slots are made of components since 1.20.5
+            _ => bail!("can't read the items of version {}", version.name),
+        };
+        let item = Id::Known(item);
+        let data = match slot.nbt {
+            Some(buffer) => match nbt::from_slice(buffer, slot.nbt_format)? {
+                OwnedTag::Compound(x) => ItemData::from_tag(x, &item)?,
+                _ => ItemData::default(),
+            },
+            None => ItemData::default(),
+        };
+        Ok(Some(ItemStack {
+            item,
+            count: slot.count,
+            data,
+        }))
+    }
+}
+
+/// Reads a list of saved items, skipping the empty ones.
+pub fn read_items(list: Vec<OwnedTag>) -> Result<Vec<ItemStack>> {
+    let mut result = Vec::with_capacity(list.len());
+    for x in list {
+        if let OwnedTag::Compound(x) = x
+            && let Some(x) = ItemStack::from_nbt(x)?
+        {
+            result.push(x);
+        }
+    }
+    Ok(result)
+}
+
+fn to_roman(number: u8) -> Option<&'static str> {
+    let r = match number {
+        1 => "I",
+        2 => "II",
+        3 => "III",
+        4 => "IV",
+        5 => "V",
+        _ => return None,
+    };
+    Some(r)
+}
+
+impl fmt::Display for EnchantmentLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.enchantment)?;
+        match to_roman(self.level) {
+            Some(x) => write!(f, " {}", x),
+            None if self.level == 0 => Ok(()),
+            None => write!(f, " {}", self.level),
+        }
+    }
+}
+
+impl fmt::Display for ItemStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>2}x {}", self.count, self.item)?;
+        if let Some(name) = &self.data.name {
+            write!(f, " \"{}\"", name)?;
+        }
+        let enchantments = self
+            .data
+            .enchantments
+            .iter()
+            .chain(&self.data.stored_enchantments);
+        for (index, x) in enchantments.enumerate() {
+            let separator = if index == 0 { " (" } else { ", " };
+            write!(f, "{}{}", separator, x)?;
+        }
+        if !self.data.enchantments.is_empty() || !self.data.stored_enchantments.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dune_common::nbt;
+    use dune_data::protocol::{InventorySlot, InventorySlotData, Version};
+
+    use crate::item::ItemStack;
+    use crate::tests::snbt_to_compound;
+    use crate::{Enchantment, Item};
+
+    fn item(snbt: &str) -> ItemStack {
+        ItemStack::from_nbt(snbt_to_compound(snbt))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn tag() {
+        let shulker = item(
+            r#"{id: "minecraft:shulker_box", Count: 1b, tag: {
+                display: {Name: '{"text":"Tools"}'},
+                BlockEntityTag: {Items: [
+                    {Slot: 0b, id: "minecraft:diamond_pickaxe", Count: 1b, tag: {
+                        Damage: 12, RepairCost: 3,
+                        Enchantments: [{id: "minecraft:efficiency", lvl: 5s}, {id: "minecraft:mending", lvl: 1s}]
+                    }},
+                    {Slot: 1b, id: "minecraft:air", Count: 0b}
+                ]}
+            }}"#,
+        );
+        assert_eq!(shulker.item, Item::ShulkerBox);
+        assert_eq!(shulker.data.name.as_deref(), Some("Tools"));
+        assert!(shulker.data.rest.is_empty());
+        let bundle = item(
+            r#"{id: "minecraft:bundle", Count: 1b, tag: {Items: [{id: "minecraft:diamond", Count: 5b}]}}"#,
+        );
+        assert_eq!(bundle.data.contents[0].to_string(), " 5x Diamond");
+        assert!(bundle.data.rest.is_empty());
+        let [pickaxe] = &shulker.data.contents[..] else {
+            panic!("expected 1 item");
+        };
+        assert_eq!((pickaxe.data.damage, pickaxe.data.repair_cost), (12, 3));
+        assert_eq!(
+            pickaxe.to_string(),
+            " 1x DiamondPickaxe (Efficiency V, Mending I)"
+        );
+    }
+
+    #[test]
+    fn components() {
+        let book = item(
+            r#"{id: "minecraft:enchanted_book", count: 2, components: {
+                "minecraft:stored_enchantments": {levels: {"minecraft:sharpness": 4}},
+                "minecraft:lore": ['{"text":"from the library"}'],
+                "minecraft:rarity": "rare"
+            }}"#,
+        );
+        assert_eq!(book.count, 2);
+        assert_eq!(book.data.lore, ["from the library"]);
+        assert_eq!(
+            book.data.stored_enchantments[0].enchantment,
+            Enchantment::Sharpness
+        );
+        assert!(book.data.rest.contains_key("minecraft:rarity"));
+        assert_eq!(book.to_string(), " 2x EnchantedBook (Sharpness IV)");
+
+        let container = item(
+            r#"{id: "minecraft:shulker_box", components: {
+                "minecraft:container": [{slot: 0, item: {id: "minecraft:torch", count: 64}}]
+            }}"#,
+        );
+        assert_eq!(container.count, 1);
+        assert_eq!(container.data.contents[0].to_string(), "64x Torch");
+
+        let bundle = item(
+            r#"{id: "minecraft:red_bundle", components: {
+                "minecraft:bundle_contents": [{id: "minecraft:torch", count: 3}]
+            }}"#,
+        );
+        assert_eq!(bundle.data.contents[0].to_string(), " 3x Torch");
+    }
+
+    #[test]
+    fn protocol() {
+        let slot = |item_id| InventorySlot {
+            data: Some(InventorySlotData {
+                item_id,
+                count: 3,
+                nbt: None,
+                nbt_format: nbt::Format::Named,
+            }),
+        };
+        let read = |item_id, protocol| {
+            ItemStack::from_protocol(&slot(item_id), Version::from_protocol(protocol).unwrap())
+        };
+        assert_eq!(read(686, 758).unwrap().unwrap().item, Item::Diamond);
+        assert_eq!(read(764, 764).unwrap().unwrap().item, Item::Diamond);
+        assert!(read(764, 767).is_err());
+    }
+}
@@ -1,10 +1,15 @@
 pub mod chat;
 pub mod client;
 pub mod events;
+pub mod item;
 pub mod record;
 pub mod replay;
+pub mod trade;
 pub mod world;
 
+#[cfg(test)]
+pub(crate) mod tests;
+
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -19,6 +24,12 @@ use dune_data::protocol::de::{MD, MemoryExt};
 use dune_data::protocol::{PacketDirection, PacketId};
 use slice_ring_buffer::SliceRingBuffer;
 
+/// Recordings start with this and a format number since they have the handshake
+/// in them. The ones before start at the login, and are from 1.20.2.
+const RECORDING_MAGIC: &[u8; 4] = b"DUNE";
+const RECORDING_FORMAT: u32 = 1;
+const OLD_RECORDING_PROTOCOL: i32 = 764;
+
 struct DiskPacket<'p> {
     pub id: PacketId,
     pub direction: PacketDirection,
@@ -4,12 +4,10 @@ use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
 
 use aes::cipher::NewCipher;
-use anyhow::{Result, anyhow};
-use dune_data::protocol::common_states::handshaking::SetProtocolRequest;
-use dune_data::protocol::common_states::login::{EncryptionBeginRequest, EncryptionBeginResponse};
+use anyhow::{Result, anyhow, bail};
+use dune_data::for_each_version;
 use dune_data::protocol::{
-    self, ConnectionState, Handshaking, Login, PacketData, PacketDirection, PacketId, Status,
-    handshaking, login, status,
+    self, ConnectionState, PacketData, PacketDirection, PacketId, Version, VersionedPacket,
 };
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
@@ -20,8 +18,8 @@ use rsa::{PaddingScheme, PublicKey, RsaPublicKey};
 use serde_derive::Serialize;
 use sha1::{Digest, Sha1};
 
-use crate::DiskPacket;
 use crate::client::{Aes128Cfb8, ClientReader, ClientWriter};
+use crate::{DiskPacket, RECORDING_FORMAT, RECORDING_MAGIC};
 
 #[derive(Clone)]
 pub struct AuthData {
@@ -32,25 +30,19 @@ pub struct AuthData {
 
 #[allow(dead_code)]
 #[derive(Debug)]
-enum Packet<'x> {
-    Handshaking(Handshaking<'x>),
-    Status(Status<'x>),
-    Login(Login<'x>),
-    V1_20_2(protocol::v1_20_2::Packet<'x>),
+enum ProxyPacket<'x> {
+    Versioned(VersionedPacket<'x>),
     PlayUnknown(&'x [u8]),
 }
 
-type DeserializeFn =
-    for<'r> fn(ConnectionState, PacketDirection, PacketId, &mut &'r [u8]) -> Result<Packet<'r>>;
-
 struct Proxy<'x> {
     state: ConnectionState,
     protocol_version: i32,
+    version: Option<&'static Version>,
     compression: bool,
     start_done: bool,
     auth_data: AuthData,
     server_host: (&'x str, u16),
-    deserialize: DeserializeFn,
     out_file: ZlibEncoder<File>,
     tmp_string: String,
     print_packets: bool,
@@ -68,18 +60,30 @@ fn rsa_crypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     let res = public_key.encrypt(&mut rand::thread_rng(), padding, data)?;
     Ok(res)
 }
+pub(crate) struct CryptReply {
+    pub(crate) shared_secret: Vec<u8>,
+    pub(crate) verify_token: Vec<u8>,
+    pub(crate) decrypt: Aes128Cfb8,
+    pub(crate) encrypt: Aes128Cfb8,
+}
+
+/// Authenticates with mojang for the server in an encryption request.
+///
+/// The returned secret and token still have to be sent back in the
+/// encryption response of the version in use.
 pub(crate) fn crypt_reply(
-    packet: EncryptionBeginResponse,
+    server_id: &str,
+    public_key: &[u8],
+    verify_token: &[u8],
     auth_data: &mut AuthData,
-    writer: &mut ClientWriter,
-) -> Result<(Aes128Cfb8, Aes128Cfb8)> {
+) -> Result<CryptReply> {
     let aes_key: [u8; 16] = rand::random();
 
     let hash = {
         let mut sha1 = Sha1::new();
-        sha1.update(packet.server_id);
+        sha1.update(server_id);
         sha1.update(aes_key);
-        sha1.update(packet.public_key);
+        sha1.update(public_key);
         let hash = sha1.finalize();
 
         num_bigint::BigInt::from_signed_bytes_be(&hash).to_str_radix(16)
@@ -112,93 +116,15 @@ pub(crate) fn crypt_reply(
         return Err(anyhow::Error::msg("bad mojang auth"));
     }
 
-    let p = EncryptionBeginRequest {
-        shared_secret: &rsa_crypt(packet.public_key, &aes_key)?,
-        verify_token: &rsa_crypt(packet.public_key, packet.verify_token)?,
+    let result = CryptReply {
+        shared_secret: rsa_crypt(public_key, &aes_key)?,
+        verify_token: rsa_crypt(public_key, verify_token)?,
+        decrypt: Aes128Cfb8::new_from_slices(&aes_key, &aes_key).unwrap(),
+        encrypt: Aes128Cfb8::new_from_slices(&aes_key, &aes_key).unwrap(),
     };
-    writer.send_packet(p)?;
-
-    let result = (
-        Aes128Cfb8::new_from_slices(&aes_key, &aes_key).unwrap(),
-        Aes128Cfb8::new_from_slices(&aes_key, &aes_key).unwrap(),
-    );
     Ok(result)
 }
 
-fn get_deserializer(state: ConnectionState, version: i32, ignore_play: bool) -> DeserializeFn {
-    fn handshaking_wrapper<'r>(
-        state: ConnectionState,
-        direction: PacketDirection,
-        id: PacketId,
-        reader: &mut &'r [u8],
-    ) -> Result<Packet<'r>> {
-        Ok(Packet::Handshaking(handshaking(
-            state, direction, id, reader,
-        )?))
-    }
-    fn login_wrapper<'r>(
-        state: ConnectionState,
-        direction: PacketDirection,
-        id: PacketId,
-        reader: &mut &'r [u8],
-    ) -> Result<Packet<'r>> {
-        Ok(Packet::Login(login(state, direction, id, reader)?))
-    }
-    fn status_wrapper<'r>(
-        state: ConnectionState,
-        direction: PacketDirection,
-        id: PacketId,
-        reader: &mut &'r [u8],
-    ) -> Result<Packet<'r>> {
-        Ok(Packet::Status(status(state, direction, id, reader)?))
-    }
-    fn ignore<'r>(
-        _state: ConnectionState,
-        _direction: PacketDirection,
-        _id: PacketId,
-        reader: &mut &'r [u8],
-    ) -> Result<Packet<'r>> {
-        let b = *reader;
-        *reader = &[];
-        Ok(Packet::PlayUnknown(b))
-    }
-
-    match state {
-        ConnectionState::Handshaking => return handshaking_wrapper,
-        ConnectionState::Login => return login_wrapper,
-        ConnectionState::Status => return status_wrapper,
-        ConnectionState::Play => {}
-    }
-    macro_rules! d {
-        ($module:ident, $variant:ident) => {{
-            fn $module<'r>(
-                state: ConnectionState,
-                direction: PacketDirection,
-                id: PacketId,
-                reader: &mut &'r [u8],
-            ) -> Result<Packet<'r>> {
-                let ret = protocol::$module::deserialize(state, direction, id, reader)?;
-                Ok(Packet::$variant(ret))
-            }
-            $module
-        }};
-    }
-
-    if ignore_play {
-        return ignore;
-    }
-
-    match version {
-        // 758 => d!(v1_18_2, V1_18_2),
-        // 761 => d!(v1_19_3, V1_19_3),
-        764 => d!(v1_20_2, V1_20_2),
-        _ => {
-            warn!("unknown protocol version: {}", version);
-            ignore
-        }
-    }
-}
-
 impl<'x> Proxy<'x> {
     fn new(
         auth_data: AuthData,
@@ -206,22 +132,24 @@ impl<'x> Proxy<'x> {
         out_path: &str,
         print_packets: bool,
     ) -> Result<Proxy<'x>> {
-        let file = File::create(out_path)?;
+        let mut out_file = ZlibEncoder::new(File::create(out_path)?, Compression::best());
+        out_file.write_all(RECORDING_MAGIC)?;
+        out_file.write_all(&RECORDING_FORMAT.to_be_bytes())?;
         Ok(Proxy {
             state: ConnectionState::Handshaking,
             protocol_version: i32::MAX,
+            version: None,
             compression: false,
             start_done: false,
             auth_data,
             server_host,
-            deserialize: get_deserializer(ConnectionState::Handshaking, 0, false),
-            out_file: ZlibEncoder::new(file, Compression::best()),
+            out_file,
             tmp_string: String::new(),
             print_packets,
         })
     }
 
-    fn println_packet(&mut self, p: &Packet) {
+    fn println_packet(&mut self, p: &ProxyPacket) {
         if !self.print_packets {
             return;
         }
@@ -239,6 +167,31 @@ impl<'x> Proxy<'x> {
         println!("{}", out);
     }
 
+    fn deserialize<'r>(
+        &self,
+        direction: PacketDirection,
+        id: PacketId,
+        reader: &mut &'r [u8],
+    ) -> Result<ProxyPacket<'r>> {
+        let version = match self.version {
+            Some(x) => x,
+            None if matches!(
+                self.state,
+                ConnectionState::Configuration | ConnectionState::Play
+            ) =>
+            {
+                let b = *reader;
+                *reader = &[];
+                return Ok(ProxyPacket::PlayUnknown(b));
+            }
+            // only the handshake and status are read without a version, a login
+            // with an unknown one is refused
+            None => Version::latest(),
+        };
+        let packet = (version.deserialize)(self.state, direction, id, reader)?;
+        Ok(ProxyPacket::Versioned(packet))
+    }
+
     fn on_start<'p>(
         &mut self,
         src_reader: &'p mut ClientReader,
@@ -256,7 +209,7 @@ impl<'x> Proxy<'x> {
         };
         let mut data = packet_data.data;
         let mut skip = false;
-        let packet = match (self.deserialize)(self.state, direction, packet_data.id, &mut data) {
+        let packet = match self.deserialize(direction, packet_data.id, &mut data) {
             Ok(x) => x,
             Err(e) => {
                 warn!("{}", e);
@@ -265,9 +218,11 @@ impl<'x> Proxy<'x> {
         };
 
         self.println_packet(&packet);
-        match packet {
-            Packet::Handshaking(p) => {
-                let Handshaking::SetProtocolRequest(x) = p;
+        let ProxyPacket::Versioned(packet) = packet else {
+            return Ok(Some(OnStartResult { skip, packet_data }));
+        };
+        for_each_version!(&packet, |p| match p {
+            Packet::SetProtocolRequest(x) => {
                 match x.next_state {
                     1 => {
                         self.start_done = true;
@@ -280,40 +235,71 @@ impl<'x> Proxy<'x> {
                         return Err(anyhow!("unknown next state: {}", x.next_state));
                     }
                 }
-                self.deserialize = get_deserializer(self.state, x.protocol_version, false);
 
                 skip = true;
                 self.protocol_version = x.protocol_version;
+                self.version = Version::from_protocol(x.protocol_version);
+                if self.version.is_none() {
+                    // the login packets change between versions, guessing would
+                    // break the encryption
+                    if matches!(self.state, ConnectionState::Login) {
+                        bail!("unsupported protocol {}", x.protocol_version);
+                    }
+                    warn!("unknown protocol version: {}", x.protocol_version);
+                }
                 let (addr, port) = self.server_host;
-                let p = SetProtocolRequest {
+                let p = version::handshaking::SetProtocolRequest {
                     protocol_version: x.protocol_version,
                     server_host: addr,
                     server_port: port,
                     next_state: x.next_state,
                 };
-                dest_writer.send_packet(p)?;
+                dest_writer.send_packet(Packet::SetProtocolRequest(p))?;
             }
 
             // ---------------------------------------------------
-            Packet::Login(x) => match x {
-                Login::SuccessResponse(_) => {
-                    self.start_done = true;
-                    self.state = ConnectionState::Play;
-                    self.deserialize = get_deserializer(self.state, self.protocol_version, false);
-                }
-                Login::CompressResponse(x) => {
-                    self.compression = x.threshold >= 0;
-                }
-                Login::EncryptionBeginResponse(packet) => {
-                    skip = true;
-                    let (c1, c2) = crypt_reply(packet, &mut self.auth_data, src_writer)?;
-                    src_reader.crypt = Some(c1);
-                    src_writer.crypt = Some(c2);
-                }
-                _ => {}
-            },
+            Packet::SuccessResponse(_)
+                if self.protocol_version < protocol::CONFIGURATION_PROTOCOL_VERSION =>
+            {
+                self.start_done = true;
+                self.state = ConnectionState::Play;
+            }
+            Packet::CompressResponse(x) => {
+                self.compression = x.threshold >= 0;
+            }
+            Packet::EncryptionBeginResponse(x) => {
+                skip = true;
+                let reply = crypt_reply(
+                    x.server_id,
+                    x.public_key,
+                    x.verify_token,
+                    &mut self.auth_data,
+                )?;
+                let p = version::login::EncryptionBeginRequest {
+                    shared_secret: &reply.shared_secret,
+                    verify_token: &reply.verify_token,
+                };
+                src_writer.send_packet(Packet::EncryptionBeginRequest(p))?;
+                src_reader.crypt = Some(reply.decrypt);
+                src_writer.crypt = Some(reply.encrypt);
+            }
             _ => {}
-        }
+        });
+
+        // ---------------------------------------------------
+        for_each_version!(configuration &packet, |p| match p {
+            Packet::LoginAcknowledgedRequest(_) => {
+                self.start_done = true;
+                self.state = ConnectionState::Configuration;
+            }
+            Packet::ConfigFinishConfigurationRequest(_) => {
+                self.state = ConnectionState::Play;
+            }
+            Packet::ConfigurationAcknowledgedRequest(_) => {
+                self.state = ConnectionState::Configuration;
+            }
+            _ => {}
+        }, _ => {});
 
         Ok(Some(OnStartResult { skip, packet_data }))
     }
@@ -333,14 +319,16 @@ impl<'x> Proxy<'x> {
             let data = packet_data.data;
             let total_size_original = packet_data.total_size;
 
-            if !result.skip {
-                let disk_packet = DiskPacket {
-                    id: packet_data.id,
-                    direction,
-                    data,
-                };
-                disk_packet.write(&mut self.out_file)?;
+            // skipped packets are still recorded, so the replay can see the
+            // handshake and follow the state transitions
+            let disk_packet = DiskPacket {
+                id: packet_data.id,
+                direction,
+                data,
+            };
+            disk_packet.write(&mut self.out_file)?;
 
+            if !result.skip {
                 let bytes = &src_reader.buffer[..total_size_original];
                 dest_writer.add(bytes);
             }
@@ -1,20 +1,22 @@
 use std::fs::File;
 use std::io::Read;
 
-use anyhow::Result;
-use dune_data::protocol;
-use dune_data::protocol::ConnectionState;
-use dune_data::protocol::v1_20_2::Packet;
+use anyhow::{Result, bail};
+use dune_data::for_each_version;
+use dune_data::protocol::de::MD;
+use dune_data::protocol::{self, ConnectionState, Version};
 use flate2::read::ZlibDecoder;
 use log::warn;
 
-use crate::events::{EventSubscriber, Position, UseEntity};
-use crate::{Buffer, DiskPacket};
+use crate::events::{self, EventSubscriber};
+use crate::{Buffer, DiskPacket, OLD_RECORDING_PROTOCOL, RECORDING_FORMAT, RECORDING_MAGIC};
 
 struct TrafficPlayer {
     reader: ZlibDecoder<File>,
     handler: Box<dyn EventSubscriber>,
     state: ConnectionState,
+    protocol_version: i32,
+    version: Option<&'static Version>,
 }
 
 impl TrafficPlayer {
@@ -26,49 +28,80 @@ impl TrafficPlayer {
             reader,
             handler,
             state: ConnectionState::Handshaking,
+            protocol_version: 0,
+            version: None,
         })
     }
 
+    fn read_header(&mut self, data: &mut &[u8]) -> Result<()> {
+        let Some(rest) = data.strip_prefix(RECORDING_MAGIC) else {
+            // an old recording, from the login on
+            self.state = ConnectionState::Login;
+            self.protocol_version = OLD_RECORDING_PROTOCOL;
+            self.version = Version::from_protocol(OLD_RECORDING_PROTOCOL);
+            return Ok(());
+        };
+        *data = rest;
+        let format: u32 = MD::deserialize(data)?;
+        if format != RECORDING_FORMAT {
+            bail!("unknown recording format: {}", format);
+        }
+        Ok(())
+    }
+
     fn do_packet(&mut self, disk_packet: DiskPacket) -> Result<()> {
         let mut data = disk_packet.data;
-        let packet = protocol::v1_20_2::deserialize(
-            self.state,
-            disk_packet.direction,
-            disk_packet.id,
-            &mut data,
-        )?;
+        let version = match self.version {
+            Some(x) => x,
+            None if matches!(
+                self.state,
+                ConnectionState::Configuration | ConnectionState::Play
+            ) =>
+            {
+                return Ok(());
+            }
+            // only the handshake and status, a login with an unknown version is
+            // refused
+            None => Version::latest(),
+        };
+        let packet =
+            (version.deserialize)(self.state, disk_packet.direction, disk_packet.id, &mut data)?;
 
         // println!("{:?}", packet);
-        match packet {
-            // Packet::SetProtocolRequest(p) => {
-            //     self.state = match p.next_state {
-            //         1 => ConnectionState::Status,
-            //         2 => ConnectionState::Login,
-            //         _ => unimplemented!(),
-            //     };
-            // }
-            // Packet::SuccessResponse(p) => {
-            //     self.state = ConnectionState::Play;
-            //     self.handler.player_info(p.username, p.uuid)?;
-            // }
-            Packet::PlayerChatResponse(_) => self.handler.on_chat("who knows")?, // ??
-            Packet::PositionRequest(p) => self.handler.position(Position {
-                x: p.x,
-                y: p.y,
-                z: p.z,
-            })?,
-            Packet::PositionResponse(p) => self.handler.position(Position {
-                x: p.x,
-                y: p.y,
-                z: p.z,
-            })?,
-            Packet::TradeListResponse(p) => self.handler.trades(p)?,
-            Packet::UseEntityRequest(p) => self.handler.interact(UseEntity {
-                entity_id: p.entity_id,
-                kind: p.kind,
-            })?,
+        for_each_version!(configuration &packet, |p| match p {
+            Packet::LoginAcknowledgedRequest(_) => self.state = ConnectionState::Configuration,
+            Packet::ConfigFinishConfigurationRequest(_) => self.state = ConnectionState::Play,
+            Packet::ConfigurationAcknowledgedRequest(_) => {
+                self.state = ConnectionState::Configuration
+            }
             _ => {}
-        }
+        }, _ => {});
+
+        for_each_version!(&packet, |p| match p {
+            Packet::SetProtocolRequest(p) => {
+                self.protocol_version = p.protocol_version;
+                self.version = Version::from_protocol(p.protocol_version);
+                self.state = match p.next_state {
+                    1 => ConnectionState::Status,
+                    2 => ConnectionState::Login,
+                    _ => bail!("unknown next state: {}", p.next_state),
+                };
+                if self.version.is_none() {
+                    if matches!(self.state, ConnectionState::Login) {
+                        bail!("unsupported protocol {}", p.protocol_version);
+                    }
+                    warn!("unknown protocol version: {}", p.protocol_version);
+                }
+            }
+            Packet::SuccessResponse(_)
+                if self.protocol_version < protocol::CONFIGURATION_PROTOCOL_VERSION =>
+            {
+                self.state = ConnectionState::Play;
+            }
+            _ => {}
+        });
+
+        events::dispatch(self.handler.as_mut(), packet)?;
         Ok(())
     }
 
@@ -76,6 +109,7 @@ impl TrafficPlayer {
         let mut buffer = Buffer::default();
         let mut tmp = [0; 4096];
         let mut packet_count = 0u32;
+        let mut header_done = false;
         loop {
             let read = self.reader.read(&mut tmp)?;
             if read == 0 {
@@ -88,6 +122,13 @@ impl TrafficPlayer {
             buffer.extend_from_slice(&tmp[..read]);
 
             let mut data = buffer.as_slice();
+            if !header_done {
+                if data.len() < RECORDING_MAGIC.len() + 4 {
+                    continue;
+                }
+                self.read_header(&mut data)?;
+                header_done = true;
+            }
             while DiskPacket::has_enough_bytes(data) {
                 let disk_packet = DiskPacket::read(&mut data)?;
                 if let Err(err) = self.do_packet(disk_packet) {
@@ -109,3 +150,55 @@ pub fn play(in_path: &str, handler: Box<dyn EventSubscriber>) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use dune_data::protocol::{ConnectionState, PacketDirection, PacketId};
+
+    use crate::DiskPacket;
+    use crate::events::EventSubscriber;
+    use crate::replay::TrafficPlayer;
+    use crate::tests::temp_dir;
+
+    struct Nothing;
+    impl EventSubscriber for Nothing {}
+
+    #[test]
+    fn header() {
+        let path = temp_dir("replay").join("empty.dune");
+        std::fs::write(&path, []).unwrap();
+        let player = || TrafficPlayer::new(path.to_str().unwrap(), Box::new(Nothing)).unwrap();
+
+        let mut new = player();
+        let mut data = &b"DUNE\0\0\0\x01\0\0\0\x05"[..];
+        new.read_header(&mut data).unwrap();
+        assert_eq!(data, b"\0\0\0\x05");
+        assert!(matches!(new.state, ConnectionState::Handshaking));
+        assert!(new.version.is_none());
+
+        let mut old = player();
+        let mut data = &b"\0\0\0\x05\0\0\0\0\x00"[..];
+        old.read_header(&mut data).unwrap();
+        assert_eq!(data.len(), 9);
+        assert!(matches!(old.state, ConnectionState::Login));
+        assert_eq!(old.version.unwrap().name, "1.20.2");
+
+        let mut data = &b"DUNE\0\0\0\x09"[..];
+        assert!(player().read_header(&mut data).is_err());
+
+        // a handshake for protocol 5, to the status or to the login
+        let handshake = |next_state| DiskPacket {
+            id: PacketId(0),
+            direction: PacketDirection::C2S,
+            data: match next_state {
+                1 => b"\x05\x01a\x63\xdd\x01",
+                _ => b"\x05\x01a\x63\xdd\x02",
+            },
+        };
+        let mut status = player();
+        status.do_packet(handshake(1)).unwrap();
+        assert!(matches!(status.state, ConnectionState::Status));
+        let error = player().do_packet(handshake(2)).unwrap_err();
+        assert_eq!(error.to_string(), "unsupported protocol 5");
+    }
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use bumpalo::Bump;
+use dune_common::nbt::{self, Compound, OwnedTag, parse_snbt};
+
+/// `snbt` as binary nbt, like in region and `.dat` files.
+pub(crate) fn snbt_to_nbt(snbt: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    nbt::to_writer(&mut data, "", &snbt_to_compound(snbt)).unwrap();
+    data
+}
+
+pub(crate) fn snbt_to_compound(snbt: &str) -> Compound {
+    let bump = Bump::new();
+    match OwnedTag::from(&parse_snbt(snbt, &bump).unwrap()) {
+        OwnedTag::Compound(x) => x,
+        _ => panic!("expected a compound"),
+    }
+}
+
+/// An empty folder in the temp dir for `name`, apart for every test run.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dune-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
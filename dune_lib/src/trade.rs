@@ -0,0 +1,167 @@
+use std::fmt;
+
+use anyhow::Result;
+use dune_data::protocol::Version;
+
+use crate::events::{Trade, Trades};
+use crate::item::{ItemStack, max_stack_size};
+use crate::world::entity::{Offer, Villager};
+
+/// A trade, the same whether it was seen in a `TradeListResponse` or read from a
+/// villager in a save.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeOffer {
+    pub input_1: ItemStack,
+    pub input_2: Option<ItemStack>,
+    pub output: ItemStack,
+    pub disabled: bool,
+    pub uses: i32,
+    pub max_uses: i32,
+    pub xp: i32,
+    pub price_multiplier: f32,
+    pub special_price: i32,
+    pub demand: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VillagerTrades {
+    pub level: i32,
+    pub offers: Vec<TradeOffer>,
+}
+
+impl TradeOffer {
+    pub fn from_protocol(trade: &Trade, version: &Version) -> Result<TradeOffer> {
+        let empty = || anyhow::anyhow!("trade without an item to buy or sell");
+        let item = |x| ItemStack::from_protocol(x, version);
+        Ok(TradeOffer {
+            input_1: item(&trade.input_item_1)?.ok_or_else(empty)?,
+            input_2: item(&trade.input_item_2)?,
+            output: item(&trade.output_item)?.ok_or_else(empty)?,
+            disabled: trade.trade_disabled,
+            uses: trade.uses,
+            max_uses: trade.max_uses,
+            xp: trade.xp,
+            price_multiplier: trade.price_multiplier,
+            special_price: trade.special_price,
+            demand: trade.demand,
+        })
+    }
+
+    pub fn from_world(offer: &Offer) -> TradeOffer {
+        TradeOffer {
+            input_1: offer.buy.clone(),
+            input_2: offer.buy_b.clone(),
+            output: offer.sell.clone(),
+            // the game disables trades that are used up
+            disabled: offer.uses >= offer.max_uses,
+            uses: offer.uses,
+            max_uses: offer.max_uses,
+            xp: offer.xp,
+            price_multiplier: offer.price_multiplier,
+            special_price: offer.special_price,
+            demand: offer.demand,
+        }
+    }
+
+    /// How many of the first input the trade costs right now, after demand and
+    /// discounts, the way the game computes it.
+    pub fn price(&self) -> i32 {
+        let base = self.input_1.count as i32;
+        let demand = (base as f32 * self.demand as f32 * self.price_multiplier).floor() as i32;
+        let max = max_stack_size(&self.input_1.item);
+        (base + demand.max(0) + self.special_price).clamp(1, max)
+    }
+}
+
+impl VillagerTrades {
+    pub fn from_protocol(trades: &Trades) -> Result<VillagerTrades> {
+        Ok(VillagerTrades {
+            level: trades.villager_level,
+            offers: trades
+                .trades
+                .iter()
+                .map(|x| TradeOffer::from_protocol(x, trades.version))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn from_world(villager: &Villager) -> VillagerTrades {
+        VillagerTrades {
+            level: villager.data.level,
+            offers: villager.offers.iter().map(TradeOffer::from_world).collect(),
+        }
+    }
+}
+
+impl fmt::Display for TradeOffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input_1)?;
+        if self.price() != self.input_1.count as i32 {
+            write!(f, " (now {})", self.price())?;
+        }
+        if let Some(x) = &self.input_2 {
+            write!(f, " + {}", x)?;
+        }
+        write!(
+            f,
+            " -> {} [{}/{} uses]",
+            self.output, self.uses, self.max_uses
+        )?;
+        if self.disabled {
+            write!(f, " (disabled)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::{EnchantmentLevel, Id, ItemData, ItemStack};
+    use crate::trade::TradeOffer;
+    use crate::{Enchantment, Item};
+
+    #[test]
+    fn price() {
+        let item = |item, count| ItemStack {
+            item: Id::Known(item),
+            count,
+            data: ItemData::default(),
+        };
+        let mut book = item(Item::EnchantedBook, 1);
+        book.data.stored_enchantments.push(EnchantmentLevel {
+            enchantment: Id::Known(Enchantment::Mending),
+            level: 1,
+        });
+        let mut offer = TradeOffer {
+            input_1: item(Item::Emerald, 20),
+            input_2: Some(item(Item::Book, 1)),
+            output: book,
+            disabled: false,
+            uses: 2,
+            max_uses: 12,
+            xp: 1,
+            price_multiplier: 0.2,
+            special_price: -5,
+            demand: 3,
+        };
+        // 20 + floor(20 * 3 * 0.2) - 5
+        assert_eq!(offer.price(), 27);
+        assert_eq!(
+            offer.to_string(),
+            "20x Emerald (now 27) +  1x Book ->  1x EnchantedBook (Mending I) [2/12 uses]"
+        );
+
+        offer.demand = -10;
+        offer.special_price = -30;
+        assert_eq!(offer.price(), 1);
+
+        // can't cost more than fits in the slot
+        offer.demand = 100;
+        offer.special_price = 0;
+        assert_eq!(offer.price(), 64);
+        offer.input_1 = item(Item::LavaBucket, 1);
+        assert_eq!(offer.price(), 1);
+        offer.input_1 = item(Item::EnderPearl, 4);
+        assert_eq!(offer.price(), 16);
+    }
+}
@@ -1,22 +1,77 @@
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use bitvec::vec::BitVec;
-use flate2::write::ZlibDecoder;
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
 
 const SECTOR_SIZE: usize = 4096;
+/// The locations and the timestamps.
+const HEADER_SECTORS: usize = 2;
+const CHUNKS_HEADER_SIZE: usize = 5;
+/// The size of a chunk is stored in a byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+/// Set on the compression byte when the chunk is in its own `.mcc` file.
+const EXTERNAL_FLAG: u8 = 128;
 pub const CHUNKS_PER_REGION: usize = 1024;
 
+/// How a chunk is compressed, the byte after its size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkCompression {
+    Gzip,
+    Zlib,
+    None,
+    /// Since 1.20.5, in the format of lz4-java's `LZ4BlockOutputStream`.
+    Lz4,
+    /// Added by mods, the name is written before the data.
+    Custom(String),
+}
+
 pub struct Region {
+    path: PathBuf,
     file: BufReader<File>,
     header: [u8; SECTOR_SIZE],
+    timestamps: [u8; SECTOR_SIZE],
+    /// Which sectors are in use, only filled in if asked for when loading.
     sectors: BitVec,
+    /// The chunks whose sectors are past the end of the file or taken by another
+    /// chunk, only when writable. They don't own any sectors.
+    corrupt: BitVec,
+    compressed: Vec<u8>,
 }
 
 impl Region {
+    /// With `sectors_map`, fails if a chunk is past the end of the file or
+    /// shares its sectors with another.
     pub fn load(path: &Path, sectors_map: bool) -> Result<Region> {
+        let file = File::open(path)?;
+        Self::load_file(path, file, sectors_map, false)
+    }
+    /// Opens the region for writing too. The sectors map is always built, it's
+    /// needed to find free space. Chunks that would fail [`Region::load`] are
+    /// marked as corrupt instead, so they can be written over or deleted.
+    pub fn load_writable(path: &Path) -> Result<Region> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Self::load_file(path, file, true, true)
+    }
+    /// Creates an empty region, overwriting `path` if it exists.
+    pub fn create(path: &Path) -> Result<Region> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.write_all(&[0; SECTOR_SIZE * HEADER_SECTORS])?;
+        file.rewind()?;
+        Self::load_file(path, file, true, true)
+    }
+
+    fn load_file(path: &Path, file: File, sectors_map: bool, writable: bool) -> Result<Region> {
         let extension = path.extension().unwrap_or_default();
         if extension != "mca" {
             bail!(
@@ -25,7 +80,7 @@ impl Region {
                 path.display()
             );
         }
-        let mut file = BufReader::with_capacity(128 * 1024, File::open(path)?);
+        let mut file = BufReader::with_capacity(128 * 1024, file);
 
         let file_size: usize = file.get_ref().metadata()?.len().try_into()?;
         if file_size < SECTOR_SIZE * 2 || !file_size.is_multiple_of(SECTOR_SIZE) {
@@ -39,11 +94,17 @@ impl Region {
 
         let mut header = [0; SECTOR_SIZE];
         file.read_exact(&mut header)?;
+        let mut timestamps = [0; SECTOR_SIZE];
+        file.read_exact(&mut timestamps)?;
 
         let mut region = Region {
+            path: path.to_path_buf(),
             file,
             header,
+            timestamps,
             sectors: BitVec::new(),
+            corrupt: BitVec::new(),
+            compressed: Vec::new(),
         };
         if !sectors_map {
             return Ok(region);
@@ -51,24 +112,26 @@ impl Region {
         region.sectors.resize(number_of_sectors, false);
         region.sectors.set(0, true); // locations
         region.sectors.set(1, true); // timestamps
+        region.corrupt.resize(CHUNKS_PER_REGION, false);
 
         for chunk_index in 0..CHUNKS_PER_REGION {
             let (loc, size) = region.sector_loc_and_size(chunk_index);
-            if loc + size > number_of_sectors {
-                bail!(
+            let error = if loc + size > number_of_sectors {
+                anyhow!(
                     "expected sector in the range 0..{}, found {}",
                     number_of_sectors,
                     loc + size
-                );
-            }
-
-            for i in 0..size {
-                let off = loc + i;
-                if region.sectors[off] {
-                    bail!("sector used by 2 different chunks: {}", off);
-                }
-                region.sectors.set(off, true);
+                )
+            } else if let Some(off) = (loc..loc + size).find(|x| region.sectors[*x]) {
+                anyhow!("sector used by 2 different chunks: {}", off)
+            } else {
+                region.sectors[loc..loc + size].fill(true);
+                continue;
+            };
+            if !writable {
+                return Err(error);
             }
+            region.corrupt.set(chunk_index, true);
         }
 
         Ok(region)
@@ -92,42 +155,475 @@ impl Region {
         (loc * SECTOR_SIZE, size * SECTOR_SIZE)
     }
 
-    pub fn get_chunk<'x>(&mut self, vec: &'x mut Vec<u8>, chunk_index: usize) -> Result<&'x [u8]> {
-        const CHUNKS_HEADER_SIZE: usize = 5;
+    /// `c.X.Z.mcc`, next to the region, holds a chunk too big for it.
+    fn external_path(&self, chunk_index: usize) -> Result<PathBuf> {
+        let Some((x, z)) = region_coords(&self.path) else {
+            bail!(
+                "can't find external chunks for {}, it isn't named r.X.Z.mca",
+                self.path.display()
+            );
+        };
+        let x = x * 32 + (chunk_index % 32) as i32;
+        let z = z * 32 + (chunk_index / 32) as i32;
+        Ok(self.path.with_file_name(format!("c.{}.{}.mcc", x, z)))
+    }
+    fn remove_external(&self, chunk_index: usize) -> Result<()> {
+        // regions with other names can't have external chunks
+        let Ok(path) = self.external_path(chunk_index) else {
+            return Ok(());
+        };
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
 
+    /// The chunk as it's stored, still compressed. `None` if the region doesn't have it.
+    pub fn get_chunk_raw<'x>(
+        &mut self,
+        vec: &'x mut Vec<u8>,
+        chunk_index: usize,
+    ) -> Result<Option<(ChunkCompression, &'x [u8])>> {
         assert!(chunk_index < CHUNKS_PER_REGION);
         let (loc, size) = self.sector_loc_and_size_real(chunk_index);
         if size == 0 {
-            return Ok(&[]);
+            return Ok(None);
         }
-        vec.clear();
-        vec.reserve(size);
 
-        let mut buffer = [0; SECTOR_SIZE];
+        let mut header = [0; CHUNKS_HEADER_SIZE];
         self.file.seek(SeekFrom::Start(loc as u64))?;
-        self.file.read_exact(&mut buffer)?;
-
-        let size = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize - 1;
+        self.file.read_exact(&mut header)?;
         // -1 because the compression byte is included here
-        let compression_type = buffer[4];
-        if compression_type != 2 {
+        let length = (u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize)
+            .saturating_sub(1);
+        let compression_type = header[4];
+
+        vec.clear();
+        if compression_type & EXTERNAL_FLAG != 0 {
+            let path = self.external_path(chunk_index)?;
+            File::open(&path)
+                .and_then(|mut x| x.read_to_end(vec))
+                .with_context(|| format!("can't read external chunk {}", path.display()))?;
+        } else {
+            if length > size - CHUNKS_HEADER_SIZE {
+                bail!(
+                    "chunk {} has {} bytes, more than its {} sectors",
+                    chunk_index,
+                    length,
+                    size / SECTOR_SIZE
+                );
+            }
+            vec.resize(length, 0);
+            self.file.read_exact(vec)?;
+        }
+
+        let mut data = &vec[..];
+        let compression = match compression_type & !EXTERNAL_FLAG {
+            1 => ChunkCompression::Gzip,
+            2 => ChunkCompression::Zlib,
+            3 => ChunkCompression::None,
+            4 => ChunkCompression::Lz4,
+            127 => {
+                // a java `writeUTF` string
+                let name_size = match data {
+                    [a, b, ..] => u16::from_be_bytes([*a, *b]) as usize,
+                    _ => bail!("missing the custom compression name"),
+                };
+                let name = data
+                    .get(2..2 + name_size)
+                    .ok_or_else(|| anyhow!("missing the custom compression name"))?;
+                let name = String::from_utf8_lossy(name).into_owned();
+                data = &data[2 + name_size..];
+                ChunkCompression::Custom(name)
+            }
+            x => bail!("unknown compression type: {}", x),
+        };
+        Ok(Some((compression, data)))
+    }
+
+    /// The uncompressed nbt of the chunk, empty if the region doesn't have it.
+    pub fn get_chunk<'x>(&mut self, vec: &'x mut Vec<u8>, chunk_index: usize) -> Result<&'x [u8]> {
+        let mut compressed = std::mem::take(&mut self.compressed);
+        vec.clear();
+        let result = match self.get_chunk_raw(&mut compressed, chunk_index) {
+            Ok(Some((compression, data))) => decompress(&compression, data, vec),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        self.compressed = compressed;
+        result?;
+        Ok(vec)
+    }
+
+    pub fn has_chunk(&self, chunk_index: usize) -> bool {
+        self.sector_loc_and_size(chunk_index).1 != 0
+    }
+
+    /// The last time the chunk was saved, in seconds since the unix epoch.
+    pub fn chunk_timestamp(&self, chunk_index: usize) -> u32 {
+        let off = chunk_index * 4;
+        u32::from_be_bytes(self.timestamps[off..off + 4].try_into().unwrap())
+    }
+
+    fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        // `BufReader` drops its buffer on the next seek, so reads don't see stale data
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.write_all(data)?;
+        Ok(())
+    }
+    fn set_location(&mut self, chunk_index: usize, loc: usize, size: usize) -> Result<()> {
+        let off = chunk_index * 4;
+        let loc = (loc as u32).to_be_bytes();
+        let entry = [loc[1], loc[2], loc[3], size as u8];
+        self.header[off..off + 4].copy_from_slice(&entry);
+        self.write_at(off, &entry)
+    }
+    fn set_timestamp(&mut self, chunk_index: usize, timestamp: u32) -> Result<()> {
+        let off = chunk_index * 4;
+        let entry = timestamp.to_be_bytes();
+        self.timestamps[off..off + 4].copy_from_slice(&entry);
+        self.write_at(SECTOR_SIZE + off, &entry)
+    }
+
+    /// The chunks marked as corrupt when the region was loaded, and not written
+    /// over or deleted since.
+    pub fn corrupt_chunks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.corrupt.iter_ones()
+    }
+
+    fn free(&mut self, chunk_index: usize) {
+        if self.corrupt[chunk_index] {
+            // its sectors belong to another chunk, or aren't there
+            self.corrupt.set(chunk_index, false);
+            return;
+        }
+        let (loc, size) = self.sector_loc_and_size(chunk_index);
+        for i in loc..(loc + size).min(self.sectors.len()) {
+            self.sectors.set(i, false);
+        }
+    }
+    /// Finds the first `count` free sectors in a row, growing the file if there
+    /// are none.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+        for i in HEADER_SECTORS..self.sectors.len() {
+            if self.sectors[i] {
+                run = 0;
+                continue;
+            }
+            run += 1;
+            if run == count {
+                let start = i + 1 - count;
+                self.sectors[start..=i].fill(true);
+                return start;
+            }
+        }
+        // the free sectors at the end of the file are reused
+        let start = self.sectors.len() - run;
+        self.sectors.resize(start + count, true);
+        self.sectors[start..].fill(true);
+        start
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.sectors.is_empty() {
+            bail!("the region wasn't loaded with a sectors map, it can't be written to");
+        }
+        Ok(())
+    }
+
+    /// Replaces the chunk with `data`, uncompressed nbt. It's stored zlib compressed,
+    /// like the game does.
+    pub fn write_chunk(&mut self, chunk_index: usize, data: &[u8]) -> Result<()> {
+        assert!(chunk_index < CHUNKS_PER_REGION);
+        self.check_writable()?;
+
+        let mut encoder = ZlibEncoder::new(vec![0; CHUNKS_HEADER_SIZE], Compression::default());
+        encoder.write_all(data)?;
+        let mut buffer = encoder.finish()?;
+        // +1 because the compression byte is included here
+        let size = (buffer.len() - CHUNKS_HEADER_SIZE + 1) as u32;
+        buffer[..4].copy_from_slice(&size.to_be_bytes());
+        buffer[4] = 2; // zlib
+
+        if buffer.len().div_ceil(SECTOR_SIZE) > MAX_CHUNK_SECTORS {
+            // like the game, only the header stays in the region
+            fs::write(
+                self.external_path(chunk_index)?,
+                &buffer[CHUNKS_HEADER_SIZE..],
+            )?;
+            buffer.truncate(CHUNKS_HEADER_SIZE);
+            buffer[..4].copy_from_slice(&1u32.to_be_bytes());
+            buffer[4] |= EXTERNAL_FLAG;
+        } else {
+            self.remove_external(chunk_index)?;
+        }
+        let sectors = buffer.len().div_ceil(SECTOR_SIZE);
+        buffer.resize(sectors * SECTOR_SIZE, 0);
+
+        self.free(chunk_index);
+        let loc = self.allocate(sectors);
+        self.write_at(loc * SECTOR_SIZE, &buffer)?;
+        self.set_location(chunk_index, loc, sectors)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.set_timestamp(chunk_index, now as u32)
+    }
+
+    pub fn delete_chunk(&mut self, chunk_index: usize) -> Result<()> {
+        assert!(chunk_index < CHUNKS_PER_REGION);
+        self.check_writable()?;
+
+        self.remove_external(chunk_index)?;
+        self.free(chunk_index);
+        self.set_location(chunk_index, 0, 0)?;
+        self.set_timestamp(chunk_index, 0)
+    }
+
+    /// Moves the chunks next to each other and truncates the file, so the space
+    /// left by deleted or moved chunks is given back. Corrupt chunks are deleted,
+    /// their sectors would be written over.
+    pub fn compact(&mut self) -> Result<()> {
+        self.check_writable()?;
+        let corrupt: Vec<_> = self.corrupt_chunks().collect();
+        for chunk_index in corrupt {
+            self.delete_chunk(chunk_index)?;
+        }
+
+        let mut chunks: Vec<_> = (0..CHUNKS_PER_REGION)
+            .map(|x| (x, self.sector_loc_and_size(x)))
+            .filter(|(_, (_, size))| *size != 0)
+            .collect();
+        chunks.sort_by_key(|(_, (loc, _))| *loc);
+
+        // every chunk moves to a lower or the same location, so nothing that's
+        // still needed gets overwritten
+        let mut next = HEADER_SECTORS;
+        let mut buffer = Vec::new();
+        for (chunk_index, (loc, size)) in chunks {
+            if loc != next {
+                buffer.resize(size * SECTOR_SIZE, 0);
+                self.file
+                    .seek(SeekFrom::Start((loc * SECTOR_SIZE) as u64))?;
+                self.file.read_exact(&mut buffer)?;
+                self.write_at(next * SECTOR_SIZE, &buffer)?;
+                self.set_location(chunk_index, next, size)?;
+            }
+            next += size;
+        }
+
+        let file = self.file.get_mut();
+        file.set_len((next * SECTOR_SIZE) as u64)?;
+        file.flush()?;
+        self.sectors.clear();
+        self.sectors.resize(next, true);
+        Ok(())
+    }
+}
+
+/// The coordinates in the name of `r.X.Z.mca`.
+pub fn region_coords(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_stem()?.to_str()?;
+    let (x, z) = name.strip_prefix("r.")?.split_once('.')?;
+    Some((x.parse().ok()?, z.parse().ok()?))
+}
+
+fn decompress(compression: &ChunkCompression, data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    match compression {
+        ChunkCompression::Gzip => {
+            GzDecoder::new(data).read_to_end(out)?;
+        }
+        ChunkCompression::Zlib => {
+            ZlibDecoder::new(data).read_to_end(out)?;
+        }
+        ChunkCompression::None => out.extend_from_slice(data),
+        ChunkCompression::Lz4 => decompress_lz4(data, out)?,
+        ChunkCompression::Custom(name) => bail!("unsupported custom compression: {}", name),
+    }
+    Ok(())
+}
+
+/// A series of blocks, each with a header: the magic, a token with the method,
+/// the compressed and original sizes and a checksum, which isn't verified.
+fn decompress_lz4(mut data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    const MAGIC: &[u8] = b"LZ4Block";
+    const HEADER_SIZE: usize = MAGIC.len() + 13;
+    const METHOD_RAW: u8 = 0x10;
+    const METHOD_LZ4: u8 = 0x20;
+    // the biggest block lz4-java writes
+    const MAX_BLOCK_SIZE: usize = 32 * 1024 * 1024;
+    // lz4 can't compress better than this
+    const MAX_RATIO: usize = 255;
+
+    let read_u32 = |x: &[u8]| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize;
+    while !data.is_empty() {
+        if data.len() < HEADER_SIZE || !data.starts_with(MAGIC) {
+            bail!("invalid lz4 block header");
+        }
+        let method = data[8] & 0xf0;
+        let compressed_size = read_u32(&data[9..]);
+        let original_size = read_u32(&data[13..]);
+        let block = data[HEADER_SIZE..]
+            .get(..compressed_size)
+            .ok_or_else(|| anyhow!("lz4 block cut off"))?;
+        data = &data[HEADER_SIZE + compressed_size..];
+        if original_size == 0 {
+            // the end of the stream
+            break;
+        }
+        if original_size > MAX_BLOCK_SIZE || original_size > compressed_size * MAX_RATIO {
             bail!(
-                "unknown compression type: {}. only zlib (2) is supported",
-                compression_type
-            )
-        }
-        let mut decoder = ZlibDecoder::new(vec);
-        let first_batch = size.min(SECTOR_SIZE - CHUNKS_HEADER_SIZE);
-        decoder.write_all(&buffer[CHUNKS_HEADER_SIZE..CHUNKS_HEADER_SIZE + first_batch])?;
-
-        let mut remaining_size = size - first_batch;
-        while remaining_size > 0 {
-            let to_read = remaining_size.min(buffer.len());
-            let read = self.file.read(&mut buffer[..to_read])?;
-            decoder.write_all(&buffer[..read])?;
-            remaining_size -= read;
-        }
-        Ok(decoder.finish()?)
+                "lz4 block of {} bytes can't hold {} bytes",
+                compressed_size,
+                original_size
+            );
+        }
+
+        match method {
+            METHOD_RAW if compressed_size != original_size => bail!(
+                "raw lz4 block has {} bytes, expected {}",
+                compressed_size,
+                original_size
+            ),
+            METHOD_RAW => out.extend_from_slice(block),
+            METHOD_LZ4 => {
+                let start = out.len();
+                out.resize(start + original_size, 0);
+                let size = lz4_flex::block::decompress_into(block, &mut out[start..])?;
+                if size != original_size {
+                    bail!("lz4 block has {} bytes, expected {}", size, original_size);
+                }
+            }
+            x => bail!("unknown lz4 block method: {:#x}", x),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::temp_dir;
+    use crate::world::anvil::{Region, SECTOR_SIZE, decompress_lz4};
+
+    #[test]
+    fn write_and_compact() {
+        let dir = temp_dir("compact");
+        let path = dir.join("r.0.0.mca");
+        let big: Vec<u8> = (0..20_000u32)
+            .flat_map(|x| x.wrapping_mul(2654435761).to_le_bytes())
+            .collect();
+
+        let mut region = Region::create(&path).unwrap();
+        region.write_chunk(0, b"first").unwrap();
+        region.write_chunk(1, &big).unwrap();
+        region.write_chunk(2, b"third").unwrap();
+        // the freed sectors are too small, it goes to the end
+        region.write_chunk(0, &big).unwrap();
+        region.delete_chunk(1).unwrap();
+        assert_eq!(region.chunk_timestamp(1), 0);
+        assert_ne!(region.chunk_timestamp(2), 0);
+        region.compact().unwrap();
+        drop(region);
+
+        let size = std::fs::metadata(&path).unwrap().len() as usize;
+        let mut region = Region::load(&path, true).unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(region.get_chunk(&mut buffer, 0).unwrap(), &big[..]);
+        assert!(region.get_chunk(&mut buffer, 1).unwrap().is_empty());
+        assert_eq!(region.get_chunk(&mut buffer, 2).unwrap(), b"third");
+        let used = 2 + 1 + region.sector_loc_and_size(0).1;
+        assert_eq!(size, used * SECTOR_SIZE);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repair() {
+        let dir = temp_dir("repair");
+        let path = dir.join("r.0.0.mca");
+        let mut region = Region::create(&path).unwrap();
+        region.write_chunk(0, b"first").unwrap();
+        region.write_chunk(1, b"second").unwrap();
+        // 1 points at the sectors of 0, 2 past the end of the file
+        let entry = region.header[..4].to_vec();
+        region.write_at(4, &entry).unwrap();
+        region.write_at(8, &[0, 3, 232, 1]).unwrap();
+        drop(region);
+
+        assert!(Region::load(&path, true).is_err());
+        let mut region = Region::load_writable(&path).unwrap();
+        assert_eq!(region.corrupt_chunks().collect::<Vec<_>>(), [1, 2]);
+        region.write_chunk(1, b"fixed").unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(region.get_chunk(&mut buffer, 0).unwrap(), b"first");
+        assert_eq!(region.get_chunk(&mut buffer, 1).unwrap(), b"fixed");
+        region.compact().unwrap();
+        drop(region);
+
+        let mut region = Region::load(&path, true).unwrap();
+        assert_eq!(region.get_chunk(&mut buffer, 0).unwrap(), b"first");
+        assert_eq!(region.get_chunk(&mut buffer, 1).unwrap(), b"fixed");
+        assert!(!region.has_chunk(2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lz4_blocks() {
+        let block = |method: u8, data: &[u8], original: usize| {
+            let mut result = b"LZ4Block".to_vec();
+            result.push(method);
+            result.extend((data.len() as u32).to_le_bytes());
+            result.extend((original as u32).to_le_bytes());
+            result.extend(0u32.to_le_bytes());
+            result.extend(data);
+            result
+        };
+        let text = b"hello hello hello hello hello world";
+        let mut data = block(0x20, &lz4_flex::block::compress(text), text.len());
+        data.extend(block(0x10, b"!", 1));
+        data.extend(block(0x10, b"", 0));
+
+        let mut out = Vec::new();
+        decompress_lz4(&data, &mut out).unwrap();
+        assert_eq!(out, b"hello hello hello hello hello world!");
+
+        // sizes that can't be right are refused before anything is allocated
+        for data in [
+            block(0x20, b"\x10a", u32::MAX as usize),
+            block(0x20, b"\x10a", 1000),
+            block(0x10, b"abc", 2),
+        ] {
+            assert!(decompress_lz4(&data, &mut Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn external_chunk() {
+        let dir = temp_dir("external");
+        let path = dir.join("r.1.-1.mca");
+        let external = dir.join("c.33.-31.mcc");
+
+        // doesn't compress, so it doesn't fit in 255 sectors
+        let mut state = 1u64;
+        let big: Vec<u8> = (0..1_100_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let mut region = Region::create(&path).unwrap();
+        region.write_chunk(33, &big).unwrap();
+        assert!(external.exists());
+        let mut buffer = Vec::new();
+        assert!(region.get_chunk(&mut buffer, 33).unwrap() == big);
+
+        region.write_chunk(33, b"small").unwrap();
+        assert!(!external.exists());
+        assert_eq!(region.get_chunk(&mut buffer, 33).unwrap(), b"small");
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
 
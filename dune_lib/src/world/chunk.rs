@@ -1,205 +1,660 @@
 use std::collections::HashMap;
 
 use anyhow::Result;
-use bumpalo::Bump;
-use bumpalo::collections::Vec as BVec;
-use dune_common::nbt::{self, Tag};
+use dune_common::nbt::{self, Compound, OwnedTag};
+use serde_derive::Deserialize;
 
-use crate::chat::parse_chat;
+use crate::chat::{ChatComponent, parse_chat_nbt};
 use crate::events::PositionInt;
-use crate::{HashMapExt, Item};
+use crate::item::{
+    ItemStack, get_int, read_items, take_compound, take_int, take_list, take_string,
+};
+use crate::world::section::{BlockState, NO_SPANNING_DATA_VERSION, RawSection, Section, unpack};
 
 // https://minecraft.fandom.com/wiki/Chunk_format
 // Why is Fandom so annoying??
 
-#[derive(Debug)]
-pub struct Book<'x> {
-    pub title: Option<&'x str>,
-    pub author: Option<&'x str>,
-    pub pages: Vec<String>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignText {
+    /// With their formatting. `color` is for the parts that don't have one.
+    pub messages: [ChatComponent; 4],
+    /// Like `black`, the default.
+    pub color: &'static str,
+    pub glowing: bool,
 }
-
-#[derive(Debug)]
-pub enum ItemSlotExtra<'x> {
-    Book(Book<'x>),
-    Unknown(HashMap<&'x str, Tag<'x>>),
-}
-#[derive(Debug)]
-pub struct ItemSlot<'x> {
-    pub item: Item,
-    pub count: u8,
-    pub extra: Option<ItemSlotExtra<'x>>,
+impl SignText {
+    pub fn is_empty(&self) -> bool {
+        self.messages.iter().all(|x| x.to_string().is_empty())
+    }
 }
 
+/// Signs have text on the back since 1.20.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Sign {
-    pub text: [String; 4],
+    pub front: SignText,
+    pub back: SignText,
+    /// Can't be edited anymore.
+    pub waxed: bool,
+    pub hanging: bool,
 }
 // pub struct BrewingStand {
 //     pub fuel: i8,
 //     pub brew_time: i16,
 // }
-pub struct Storage<'x> {
-    pub items: Vec<ItemSlot<'x>>,
+pub struct Storage {
+    pub items: Vec<ItemStack>,
+}
+
+pub struct Lectern {
+    pub book: Option<ItemStack>,
+    /// The page it's open at.
+    pub page: i32,
+}
+
+pub struct BannerPattern {
+    /// Like `minecraft:stripe_top`, or `ts` before 1.20.5.
+    pub pattern: String,
+    /// Like `red`.
+    pub color: &'static str,
+}
+/// The base color is in the block id, like `minecraft:red_banner`.
+pub struct Banner {
+    pub custom_name: Option<String>,
+    pub patterns: Vec<BannerPattern>,
+}
+
+pub struct CommandBlock {
+    pub command: String,
+    pub last_output: Option<String>,
+    /// Runs without redstone.
+    pub auto: bool,
+    pub powered: bool,
+}
+
+pub struct Spawner {
+    /// Like `minecraft:zombie`.
+    pub entity: Option<String>,
+    /// The entity that spawns next, with its nbt.
+    pub spawn_data: Compound,
+    /// Ticks until the next spawn.
+    pub delay: i32,
+}
+
+pub struct Beehive {
+    /// The bees inside, the ones out flying aren't counted.
+    pub bees: usize,
 }
 
-pub enum BlockEntityKind<'x> {
-    Sign(Sign),
+pub struct Jukebox {
+    pub record: Option<ItemStack>,
+}
+
+pub struct Skull {
+    /// The name of the player whose head it is.
+    pub owner: Option<String>,
+}
+
+pub struct DecoratedPot {
+    /// Back, left, right and front, like `minecraft:brick`.
+    pub sherds: Vec<String>,
+    pub item: Option<ItemStack>,
+}
+
+pub enum BlockEntityKind {
+    Sign(Box<Sign>),
     // BrewingStand(BrewingStand),
-    Storage(Storage<'x>),
+    Storage(Storage),
+    Lectern(Box<Lectern>),
+    Banner(Banner),
+    CommandBlock(CommandBlock),
+    Spawner(Spawner),
+    Beehive(Beehive),
+    Jukebox(Box<Jukebox>),
+    Skull(Skull),
+    ChiseledBookshelf(Storage),
+    DecoratedPot(Box<DecoratedPot>),
     Bed,
     Bell,
+    /// What isn't decoded, with its nbt without the id and the position.
+    Unknown(Compound),
 }
-pub struct BlockEntity<'x> {
+impl BlockEntityKind {
+    /// The items kept in the block, without looking in the ones that hold more.
+    pub fn items(&self) -> Vec<&ItemStack> {
+        match self {
+            BlockEntityKind::Storage(x) | BlockEntityKind::ChiseledBookshelf(x) => {
+                x.items.iter().collect()
+            }
+            BlockEntityKind::Lectern(x) => x.book.iter().collect(),
+            BlockEntityKind::Jukebox(x) => x.record.iter().collect(),
+            BlockEntityKind::DecoratedPot(x) => x.item.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub struct BlockEntity {
+    /// Like `minecraft:chest`.
+    pub id: String,
     pub position: PositionInt,
-    pub kind: BlockEntityKind<'x>,
+    pub kind: BlockEntityKind,
+}
+
+/// For every column, the y right above the highest block of some kind.
+pub struct Heightmap {
+    data: Vec<i64>,
+    bits: u32,
+    spanning: bool,
+    min_y: i32,
+}
+impl Heightmap {
+    fn new(data: Vec<i64>, min_y: i32, data_version: i32) -> Option<Heightmap> {
+        let spanning = data_version < NO_SPANNING_DATA_VERSION;
+        // the bits depend on the height of the world
+        let bits = (1..=32).find(|bits| match spanning {
+            true => data.len() * 64 == 256 * *bits as usize,
+            false => data.len() == 256usize.div_ceil(64 / *bits as usize),
+        })?;
+        Some(Heightmap {
+            data,
+            bits,
+            spanning,
+            min_y,
+        })
+    }
+
+    /// `x` and `z` are taken modulo 16, so world coordinates work too.
+    pub fn height_at(&self, x: i32, z: i32) -> Option<i32> {
+        let index = ((z & 15) * 16 + (x & 15)) as usize;
+        Some(unpack(&self.data, self.bits, self.spanning, index)? as i32 + self.min_y)
+    }
 }
 
 pub struct Chunk<'x> {
-    pub block_entities: Vec<BlockEntity<'x>>,
-}
-fn read_item_extra<'x>(
-    mut nbt: HashMap<&'x str, Tag<'x>>,
-    item: Item,
-) -> Result<ItemSlotExtra<'x>> {
-    let r = match item {
-        Item::WritableBook | Item::WrittenBook => {
-            let pages_raw = nbt.remove_err("pages")?.list()?;
-            let mut pages = Vec::with_capacity(pages_raw.len());
-            let is_written = item == Item::WrittenBook;
-
-            for i in pages_raw {
-                let page = i.string()?;
-                let page = if is_written {
-                    parse_chat(page)?.to_string()
-                } else {
-                    page.to_string()
-                };
-                pages.push(page);
-            }
+    pub data_version: i32,
+    pub x: i32,
+    pub z: i32,
+    /// The lowest y, 0 before 1.18.
+    pub min_y: i32,
+    /// Like `WORLD_SURFACE` or `MOTION_BLOCKING`.
+    pub heightmaps: HashMap<&'x str, Heightmap>,
+    /// Only the sections with blocks in them, sorted by `y`.
+    pub sections: Vec<Section<'x>>,
+    pub block_entities: Vec<BlockEntity>,
+}
+impl<'x> Chunk<'x> {
+    pub fn section(&self, section_y: i32) -> Option<&Section<'x>> {
+        self.sections.iter().find(|x| x.y as i32 == section_y)
+    }
+    /// `y` is the world height, `x` and `z` are taken modulo 16 so world
+    /// coordinates work too.
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<&BlockState<'x>> {
+        self.section(y >> 4)?.block_at(x, y, z)
+    }
+    pub fn biome_at(&self, x: i32, y: i32, z: i32) -> Option<&'x str> {
+        self.section(y >> 4)?.biome_at(x, y, z)
+    }
+}
 
-            let (title, author) = if is_written {
-                let title = nbt.remove_err("title")?.string()?;
-                let author = nbt.remove_err("author")?.string()?;
-                (Some(title), Some(author))
-            } else {
-                (None, None)
-            };
+// the layout on disk
 
-            ItemSlotExtra::Book(Book {
-                title,
-                author,
-                pages,
-            })
+#[derive(Deserialize)]
+struct RawLevel<'x> {
+    #[serde(rename = "xPos")]
+    x: i32,
+    #[serde(rename = "zPos")]
+    z: i32,
+    #[serde(rename = "Sections", default, borrow)]
+    sections: Vec<RawSection<'x>>,
+    #[serde(rename = "TileEntities", default)]
+    tile_entities: Vec<Compound>,
+    #[serde(rename = "Heightmaps", default, borrow)]
+    heightmaps: HashMap<&'x str, Vec<i64>>,
+}
+#[derive(Deserialize)]
+struct RawChunk<'x> {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    // 2860 - 1.18
+    // no clue when the format changed actually
+    #[serde(rename = "xPos", default)]
+    x: i32,
+    #[serde(rename = "zPos", default)]
+    z: i32,
+    #[serde(rename = "yPos", default)]
+    y: i32,
+    #[serde(rename = "Heightmaps", default, borrow)]
+    heightmaps: HashMap<&'x str, Vec<i64>>,
+    #[serde(default, borrow)]
+    sections: Vec<RawSection<'x>>,
+    #[serde(default)]
+    block_entities: Vec<Compound>,
+    #[serde(rename = "Level", borrow)]
+    level: Option<RawLevel<'x>>,
+}
+
+/// The `Items` of a block or an entity that stores them.
+pub fn read_storage(items: Vec<OwnedTag>) -> Result<Storage> {
+    Ok(Storage {
+        items: read_items(items)?,
+    })
+}
+
+const DYE_COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+fn dye_color(tag: Option<OwnedTag>) -> &'static str {
+    // a number before 1.20.5
+    match tag {
+        Some(OwnedTag::String(x)) => DYE_COLORS.into_iter().find(|c| *c == x).unwrap_or("white"),
+        x => {
+            let index = x.as_ref().and_then(get_int).unwrap_or(0);
+            DYE_COLORS.get(index as usize).copied().unwrap_or("white")
         }
-        _ => ItemSlotExtra::Unknown(nbt),
-    };
-    Ok(r)
+    }
 }
-fn read_item<'x>(mut nbt: HashMap<&str, Tag<'x>>) -> Result<ItemSlot<'x>> {
-    let id = nbt.remove_err("id")?.string()?;
-    let item = Item::from_str_id(id)?;
-    let count = nbt.remove_err("Count")?.byte()?.try_into()?;
 
-    let extra = match nbt.remove("tag") {
-        Some(x) => Some(read_item_extra(x.compound()?, item)?),
-        None => None,
-    };
+fn read_text(tag: Option<OwnedTag>) -> Option<String> {
+    Some(parse_chat_nbt(&tag?).to_string())
+}
 
-    Ok(ItemSlot { item, count, extra })
+fn read_slot(tag: &mut Compound, key: &str) -> Result<Option<ItemStack>> {
+    match take_compound(tag, key) {
+        Some(x) => ItemStack::from_nbt(x),
+        None => Ok(None),
+    }
 }
-fn read_storage<'x>(mut nbt: HashMap<&str, Tag<'x>>, bump: &Bump) -> Result<BlockEntityKind<'x>> {
-    let items_in = match nbt.remove("Items") {
-        Some(x) => x.list()?,
-        None => BVec::new_in(bump),
-    };
-    let mut items = Vec::with_capacity(items_in.len());
-    for i in items_in {
-        let i = i.compound()?;
-        let item = read_item(i)?;
 
-        items.push(item);
+fn sign_color(tag: Option<OwnedTag>) -> &'static str {
+    match tag {
+        Some(x) => dye_color(Some(x)),
+        None => "black",
     }
+}
 
-    Ok(BlockEntityKind::Storage(Storage { items }))
+fn empty_messages() -> [ChatComponent; 4] {
+    std::array::from_fn(|_| ChatComponent::text(""))
 }
-pub fn read_block_entity<'x>(
-    id: &str,
-    mut nbt: HashMap<&str, Tag<'x>>,
-    bump: &Bump,
-) -> Result<Option<BlockEntityKind<'x>>> {
-    let id = match id.strip_prefix("minecraft:") {
-        Some(x) => x,
-        None => return Ok(None),
+
+/// A side of a sign, since 1.20.
+fn read_sign_text(mut tag: Compound) -> SignText {
+    let mut messages = empty_messages();
+    for (message, x) in messages.iter_mut().zip(take_list(&mut tag, "messages")) {
+        *message = parse_chat_nbt(&x);
+    }
+    SignText {
+        messages,
+        color: sign_color(tag.shift_remove("color")),
+        glowing: take_int(&mut tag, "has_glowing_text").unwrap_or(0) != 0,
+    }
+}
+
+/// `Text1` to `Text4`, with `Color` since 1.14 and `GlowingText` since 1.17.
+fn read_old_sign_text(tag: &mut Compound) -> SignText {
+    let mut messages = empty_messages();
+    for (index, message) in messages.iter_mut().enumerate() {
+        if let Some(x) = tag.shift_remove(&format!("Text{}", index + 1)) {
+            *message = parse_chat_nbt(&x);
+        }
+    }
+    SignText {
+        messages,
+        color: sign_color(tag.shift_remove("Color")),
+        glowing: take_int(tag, "GlowingText").unwrap_or(0) != 0,
+    }
+}
+
+/// The name in `SkullOwner` before 1.20.5, or in `profile`.
+fn skull_owner(tag: Option<OwnedTag>) -> Option<String> {
+    match tag? {
+        OwnedTag::String(x) => Some(x),
+        OwnedTag::Compound(mut x) => {
+            take_string(&mut x, "Name").or_else(|| take_string(&mut x, "name"))
+        }
+        _ => None,
+    }
+}
+
+fn read_block_entity(id: &str, mut tag: Compound) -> Result<BlockEntityKind> {
+    let Some(id) = id.strip_prefix("minecraft:") else {
+        return Ok(BlockEntityKind::Unknown(tag));
     };
     let r = match id {
-        "sign" => {
-            let mut get_text = |key: &str| -> Result<String> {
-                let json = nbt.remove_err(key)?.string()?;
-                let r = parse_chat(json)?.to_string();
-                Ok(r)
+        "sign" | "hanging_sign" => {
+            let hanging = id == "hanging_sign";
+            let sign = match take_compound(&mut tag, "front_text") {
+                Some(front) => Sign {
+                    front: read_sign_text(front),
+                    back: read_sign_text(take_compound(&mut tag, "back_text").unwrap_or_default()),
+                    waxed: take_int(&mut tag, "is_waxed").unwrap_or(0) != 0,
+                    hanging,
+                },
+                None => Sign {
+                    front: read_old_sign_text(&mut tag),
+                    back: read_sign_text(Compound::new()),
+                    waxed: false,
+                    hanging,
+                },
             };
-            let text = [
-                get_text("Text1")?,
-                get_text("Text2")?,
-                get_text("Text3")?,
-                get_text("Text4")?,
-            ];
-
-            BlockEntityKind::Sign(Sign { text })
+            BlockEntityKind::Sign(Box::new(sign))
         }
-        // "brewing_stand" => {
-        //     // TODO: items ignored
-        //     let fuel = nbt.remove_err("Fuel")?.byte()?;
-        //     let brew_time = nbt.remove_err("BrewTime")?.short()?;
-
-        //     BlockEntityKind::BrewingStand(BrewingStand { fuel, brew_time })
-        // }
         "chest" | "trapped_chest" | "barrel" | "hopper" | "dispenser" | "dropper" | "furnace"
-        | "blast_furnace" | "smoker" | "brewing_stand" => read_storage(nbt, bump)?,
+        | "blast_furnace" | "smoker" | "brewing_stand" | "crafter" => {
+            BlockEntityKind::Storage(read_storage(take_list(&mut tag, "Items"))?)
+        }
+        x if x.ends_with("shulker_box") => {
+            BlockEntityKind::Storage(read_storage(take_list(&mut tag, "Items"))?)
+        }
+        "lectern" => BlockEntityKind::Lectern(Box::new(Lectern {
+            book: read_slot(&mut tag, "Book")?,
+            page: take_int(&mut tag, "Page").unwrap_or(0),
+        })),
+        "banner" => {
+            let mut patterns = take_list(&mut tag, "patterns");
+            patterns.extend(take_list(&mut tag, "Patterns"));
+            let patterns = patterns
+                .into_iter()
+                .filter_map(|x| match x {
+                    OwnedTag::Compound(mut x) => Some(BannerPattern {
+                        pattern: take_string(&mut x, "pattern")
+                            .or_else(|| take_string(&mut x, "Pattern"))?,
+                        color: dye_color(x.shift_remove("color").or(x.shift_remove("Color"))),
+                    }),
+                    _ => None,
+                })
+                .collect();
+            BlockEntityKind::Banner(Banner {
+                custom_name: read_text(tag.shift_remove("CustomName")),
+                patterns,
+            })
+        }
+        "command_block" => BlockEntityKind::CommandBlock(CommandBlock {
+            command: take_string(&mut tag, "Command").unwrap_or_default(),
+            last_output: read_text(tag.shift_remove("LastOutput")),
+            auto: take_int(&mut tag, "auto").unwrap_or(0) != 0,
+            powered: take_int(&mut tag, "powered").unwrap_or(0) != 0,
+        }),
+        "mob_spawner" | "trial_spawner" => {
+            let spawn_data = take_compound(&mut tag, "spawn_data")
+                .or_else(|| take_compound(&mut tag, "SpawnData"))
+                .unwrap_or_default();
+            // the entity is in `entity` since 1.18
+            let entity = match spawn_data.get("entity") {
+                Some(OwnedTag::Compound(x)) => x.get("id").cloned(),
+                _ => spawn_data.get("id").cloned(),
+            };
+            BlockEntityKind::Spawner(Spawner {
+                entity: match entity {
+                    Some(OwnedTag::String(x)) => Some(x),
+                    _ => None,
+                },
+                spawn_data,
+                delay: take_int(&mut tag, "Delay").unwrap_or(0),
+            })
+        }
+        "beehive" | "bee_nest" => {
+            let mut bees = take_list(&mut tag, "bees");
+            bees.extend(take_list(&mut tag, "Bees"));
+            BlockEntityKind::Beehive(Beehive { bees: bees.len() })
+        }
+        "jukebox" => BlockEntityKind::Jukebox(Box::new(Jukebox {
+            record: read_slot(&mut tag, "RecordItem")?,
+        })),
+        "skull" => BlockEntityKind::Skull(Skull {
+            owner: skull_owner(
+                tag.shift_remove("profile")
+                    .or(tag.shift_remove("SkullOwner")),
+            ),
+        }),
+        "chiseled_bookshelf" => {
+            BlockEntityKind::ChiseledBookshelf(read_storage(take_list(&mut tag, "Items"))?)
+        }
+        "decorated_pot" => {
+            let mut sherds = take_list(&mut tag, "sherds");
+            sherds.extend(take_list(&mut tag, "shards"));
+            BlockEntityKind::DecoratedPot(Box::new(DecoratedPot {
+                sherds: sherds
+                    .into_iter()
+                    .filter_map(|x| match x {
+                        OwnedTag::String(x) => Some(x),
+                        _ => None,
+                    })
+                    .collect(),
+                item: read_slot(&mut tag, "item")?,
+            }))
+        }
         "bed" => BlockEntityKind::Bed,
         "bell" => BlockEntityKind::Bell,
-        _ => {
-            // warn!("unknown block entity `{}`", id);
-            return Ok(None);
-        }
+        _ => BlockEntityKind::Unknown(tag),
     };
-    Ok(Some(r))
-}
-
-pub fn read_chunk<'x>(buf: &'x [u8], bump: &'x Bump) -> Result<Chunk<'x>> {
-    let root = nbt::read(buf, bump)?;
-    let mut root = root.tag.compound()?;
-    let version = root.remove_err("DataVersion")?.int()?;
-
-    let block_entities_nbt = if version >= 2860 {
-        // 2860 - 1.18
-        // no clue when the format changed actually
-        root.remove_err("block_entities")?.list()?
-    } else {
-        let mut level = root.remove_err("Level")?.compound()?;
-        match level.remove("TileEntities") {
-            Some(x) => x.list()?,
-            None => BVec::new_in(bump),
-        }
+    Ok(r)
+}
+
+pub fn read_chunk(buf: &[u8]) -> Result<Chunk<'_>> {
+    let root: RawChunk = nbt::from_slice(buf, nbt::Format::Named)?;
+    let data_version = root.data_version;
+    let (x, z, min_y, sections, block_entities_nbt, heightmaps) = match root.level {
+        Some(level) if data_version < 2860 => (
+            level.x,
+            level.z,
+            0,
+            level.sections,
+            level.tile_entities,
+            level.heightmaps,
+        ),
+        _ => (
+            root.x,
+            root.z,
+            root.y * 16,
+            root.sections,
+            root.block_entities,
+            root.heightmaps,
+        ),
     };
+    let heightmaps = heightmaps
+        .into_iter()
+        .filter_map(|(name, data)| Some((name, Heightmap::new(data, min_y, data_version)?)))
+        .collect();
+
+    let mut sections = sections
+        .into_iter()
+        .filter_map(|x| x.decode(data_version).transpose())
+        .collect::<Result<Vec<_>>>()?;
+    sections.sort_by_key(|x| x.y);
 
     let mut block_entities = Vec::with_capacity(block_entities_nbt.len());
-    for i in block_entities_nbt {
-        let mut i = i.compound()?;
-        let id = i.remove_err("id")?.string()?;
+    for mut i in block_entities_nbt {
+        let id = take_string(&mut i, "id").unwrap_or_default();
+        let mut coordinate = |key| take_int(&mut i, key).unwrap_or(0);
+        let position = PositionInt {
+            x: coordinate("x"),
+            y: coordinate("y"),
+            z: coordinate("z"),
+        };
+        let kind = read_block_entity(&id, i)?;
+        block_entities.push(BlockEntity { id, position, kind });
+    }
+
+    Ok(Chunk {
+        data_version,
+        x,
+        z,
+        min_y,
+        heightmaps,
+        sections,
+        block_entities,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Item;
+    use crate::chat::ChatComponent;
+    use crate::item::Id;
+    use crate::tests::snbt_to_nbt;
+    use crate::world::chunk::{BlockEntityKind, read_chunk};
+
+    #[test]
+    fn block_entities() {
+        let snbt = r#"{
+            DataVersion: 3700, xPos: 0, zPos: 0,
+            block_entities: [
+                {id: "minecraft:banner", x: 1, y: 64, z: 2, CustomName: '{"text":"Flag"}',
+                    patterns: [{pattern: "minecraft:stripe_top", color: "red"}]},
+                {id: "minecraft:banner", x: 1, y: 65, z: 2, Patterns: [{Pattern: "bs", Color: 11}]},
+                {id: "minecraft:command_block", x: 0, y: 1, z: 0, Command: "say hi", auto: 1b, powered: 0b},
+                {id: "minecraft:mob_spawner", x: 3, y: 20, z: 3, Delay: 20s,
+                    SpawnData: {entity: {id: "minecraft:zombie"}}},
+                {id: "minecraft:beehive", x: 4, y: 70, z: 4, bees: [{}, {}]},
+                {id: "minecraft:jukebox", x: 5, y: 64, z: 5, RecordItem: {id: "minecraft:music_disc_cat", Count: 1b}},
+                {id: "minecraft:skull", x: 6, y: 64, z: 6, SkullOwner: {Name: "Notch"}},
+                {id: "minecraft:decorated_pot", x: 7, y: 64, z: 7,
+                    sherds: ["minecraft:brick", "minecraft:skull_pottery_sherd", "minecraft:brick", "minecraft:brick"]},
+                {id: "minecraft:conduit", x: 8, y: 50, z: 8, Target: [I; 0, 0, 0, 0]}
+            ]
+        }"#;
+        let data = snbt_to_nbt(snbt);
+
+        let chunk = read_chunk(&data).unwrap();
+        let kinds: Vec<_> = chunk.block_entities.iter().map(|x| &x.kind).collect();
+        let [
+            BlockEntityKind::Banner(banner),
+            BlockEntityKind::Banner(old_banner),
+            BlockEntityKind::CommandBlock(command),
+            BlockEntityKind::Spawner(spawner),
+            BlockEntityKind::Beehive(beehive),
+            BlockEntityKind::Jukebox(jukebox),
+            BlockEntityKind::Skull(skull),
+            BlockEntityKind::DecoratedPot(pot),
+            BlockEntityKind::Unknown(conduit),
+        ] = &kinds[..]
+        else {
+            panic!("unexpected block entities");
+        };
 
-        let x = i.remove_err("x")?.int()?;
-        let y = i.remove_err("y")?.int()?;
-        let z = i.remove_err("z")?.int()?;
+        assert_eq!(banner.custom_name.as_deref(), Some("Flag"));
+        assert_eq!(banner.patterns[0].pattern, "minecraft:stripe_top");
+        assert_eq!(banner.patterns[0].color, "red");
+        assert_eq!(
+            (
+                old_banner.patterns[0].pattern.as_str(),
+                old_banner.patterns[0].color
+            ),
+            ("bs", "blue")
+        );
+        assert_eq!(command.command, "say hi");
+        assert!(command.auto && !command.powered);
+        assert_eq!(spawner.entity.as_deref(), Some("minecraft:zombie"));
+        assert_eq!(spawner.delay, 20);
+        assert_eq!(beehive.bees, 2);
+        assert_eq!(jukebox.record.as_ref().unwrap().item, Item::MusicDiscCat);
+        assert_eq!(skull.owner.as_deref(), Some("Notch"));
+        assert_eq!(pot.sherds[1], "minecraft:skull_pottery_sherd");
+        assert!(conduit.contains_key("Target"));
+        assert_eq!(chunk.block_entities[8].id, "minecraft:conduit");
+        assert_eq!(chunk.block_entities[8].position.y, 50);
+    }
+
+    #[test]
+    fn unknown_ids() {
+        let snbt = r#"{
+            DataVersion: 3953, xPos: 0, zPos: 0,
+            block_entities: [{id: "minecraft:chest", x: 1, y: 64, z: 2, Items: [
+                {Slot: 0b, id: "minecraft:mace", count: 1, components: {
+                    "minecraft:enchantments": {levels: {"minecraft:density": 5}}
+                }},
+                {Slot: 1b, id: "minecraft:diamond", count: 3}
+            ]}]
+        }"#;
+        let data = snbt_to_nbt(snbt);
 
-        let kind = match read_block_entity(id, i, bump)? {
-            Some(x) => x,
-            None => continue,
+        let chunk = read_chunk(&data).unwrap();
+        let BlockEntityKind::Storage(chest) = &chunk.block_entities[0].kind else {
+            panic!("expected a chest");
         };
+        let [mace, diamond] = &chest.items[..] else {
+            panic!("expected 2 items");
+        };
+        assert_eq!(mace.item, Id::Unknown("minecraft:mace".to_string()));
+        assert_eq!(mace.to_string(), " 1x minecraft:mace (minecraft:density V)");
+        assert_eq!(diamond.item, Item::Diamond);
+    }
 
-        let position = PositionInt { x, y, z };
-        block_entities.push(BlockEntity { position, kind });
+    #[test]
+    fn old_sections() {
+        let chunk = |sections: &str| {
+            let snbt = format!(
+                "{{DataVersion: 1139, Level: {{xPos: 0, zPos: 0, Sections: [{}]}}}}",
+                sections
+            );
+            read_chunk(&snbt_to_nbt(&snbt)).map(|x| x.sections.len())
+        };
+        // light only
+        assert_eq!(chunk("{Y: 0b, BlockLight: [B; 0b]}").unwrap(), 0);
+        let error = chunk("{Y: 0b, Blocks: [B; 1b, 2b], Data: [B; 0b]}").unwrap_err();
+        assert_eq!(error.to_string(), "pre-1.13 sections are not supported");
     }
 
-    Ok(Chunk { block_entities })
+    #[test]
+    fn signs() {
+        let snbt = r#"{
+            DataVersion: 3700, xPos: 0, zPos: 0,
+            block_entities: [
+                {id: "minecraft:sign", x: 0, y: 64, z: 0, Color: "red", GlowingText: 1b,
+                    Text1: '{"text":"old"}', Text2: '""', Text3: '""', Text4: '""'},
+                {id: "minecraft:sign", x: 1, y: 64, z: 0, is_waxed: 1b,
+                    front_text: {messages: ['"front"', '""', '""', '""'], color: "black", has_glowing_text: 0b},
+                    back_text: {messages: ['""', '{"text":"back","bold":true}', '""', '""'], color: "blue", has_glowing_text: 1b}},
+                {id: "minecraft:hanging_sign", x: 2, y: 64, z: 0, is_waxed: 0b,
+                    front_text: {messages: [{text: "nbt"}, {text: ""}, {text: ""}, {text: ""}], color: "black", has_glowing_text: 0b},
+                    back_text: {messages: ["", "", "", ""], color: "black", has_glowing_text: 0b}}
+            ]
+        }"#;
+        let data = snbt_to_nbt(snbt);
+
+        let chunk = read_chunk(&data).unwrap();
+        let signs: Vec<_> = chunk
+            .block_entities
+            .iter()
+            .map(|x| match &x.kind {
+                BlockEntityKind::Sign(x) => x,
+                _ => panic!("expected a sign"),
+            })
+            .collect();
+        let [old, modern, hanging] = &signs[..] else {
+            panic!("expected 3 signs");
+        };
+
+        assert_eq!(old.front.messages[0].to_string(), "old");
+        assert_eq!((old.front.color, old.front.glowing), ("red", true));
+        assert!(old.back.is_empty() && !old.hanging);
+
+        assert!(modern.waxed);
+        assert_eq!(modern.front.messages[0].to_string(), "front");
+        let ChatComponent::Text { text, style, .. } = &modern.back.messages[1] else {
+            panic!("expected a text");
+        };
+        assert_eq!((text.as_str(), style.bold), ("back", Some(true)));
+        assert_eq!((modern.back.color, modern.back.glowing), ("blue", true));
+
+        assert!(hanging.hanging && !hanging.waxed);
+        let lines = hanging.front.messages.each_ref().map(|x| x.to_string());
+        assert_eq!(lines, ["nbt", "", "", ""]);
+    }
 }
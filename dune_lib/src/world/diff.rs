@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Result, bail};
+
+use crate::Item;
+use crate::item::{Id, ItemStack};
+use crate::world::anvil::{CHUNKS_PER_REGION, Region, region_coords};
+use crate::world::chunk::{BlockEntity, BlockEntityKind, Chunk, Sign, read_chunk};
+use crate::world::scan::{Dimension, RegionReport, for_each_region, region_files};
+use crate::world::section::{BLOCKS_PER_SECTION, Block, BlockState, Section};
+
+#[derive(Clone)]
+pub struct BlockChange {
+    pub position: [i32; 3],
+    /// Like `minecraft:oak_stairs[facing=east,half=bottom]`.
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Clone)]
+pub enum BlockEntityChange {
+    Added {
+        position: [i32; 3],
+        id: String,
+    },
+    Removed {
+        position: [i32; 3],
+        id: String,
+    },
+    /// How many of each item were added or taken, the ones in shulker boxes
+    /// and bundles too.
+    Items {
+        position: [i32; 3],
+        id: String,
+        deltas: Vec<(Id<Item>, i64)>,
+    },
+    Sign {
+        position: [i32; 3],
+        old: Box<Sign>,
+        new: Box<Sign>,
+    },
+}
+
+#[derive(Clone)]
+pub struct ChunkDiff {
+    pub x: i32,
+    pub z: i32,
+    pub changed_blocks: usize,
+    /// Only filled in for detailed diffs.
+    pub blocks: Vec<BlockChange>,
+    pub block_entities: Vec<BlockEntityChange>,
+}
+impl ChunkDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed_blocks == 0 && self.block_entities.is_empty()
+    }
+}
+
+#[derive(Clone)]
+pub struct RegionDiff {
+    pub x: i32,
+    pub z: i32,
+    /// Chunks with the same timestamp in both worlds, they weren't read.
+    pub unchanged: usize,
+    /// The coordinates of the chunks only in the new world.
+    pub added: Vec<(i32, i32)>,
+    pub removed: Vec<(i32, i32)>,
+    /// Chunks saved again since the old world, `changed` are the ones that differ.
+    pub compared: usize,
+    pub changed: Vec<ChunkDiff>,
+}
+impl RegionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn state_name(state: Option<&BlockState>) -> String {
+    state.map_or_else(Block::air, Block::from).to_string()
+}
+
+fn diff_sections(
+    chunk: &Chunk,
+    y: i32,
+    old: Option<&Section>,
+    new: Option<&Section>,
+    detailed: bool,
+    result: &mut ChunkDiff,
+) {
+    if let (Some(a), Some(b)) = (old, new)
+        && a.block_states.palette == b.block_states.palette
+        && a.block_states.data == b.block_states.data
+    {
+        return;
+    }
+    for index in 0..BLOCKS_PER_SECTION {
+        let a = old.and_then(|x| x.block_states.get(index));
+        let b = new.and_then(|x| x.block_states.get(index));
+        let air = |x: Option<&BlockState>| x.is_none_or(|x| x.name == "minecraft:air");
+        if a == b || (air(a) && air(b)) {
+            continue;
+        }
+        result.changed_blocks += 1;
+        if detailed {
+            let index = index as i32;
+            result.blocks.push(BlockChange {
+                position: [
+                    chunk.x * 16 + (index & 15),
+                    y * 16 + (index >> 8),
+                    chunk.z * 16 + ((index >> 4) & 15),
+                ],
+                old: state_name(a),
+                new: state_name(b),
+            });
+        }
+    }
+}
+
+fn count_items(items: &[&ItemStack], counts: &mut HashMap<Id<Item>, i64>, sign: i64) {
+    for i in items {
+        *counts.entry(i.item.clone()).or_default() += i.count as i64 * sign;
+        let contents: Vec<_> = i.data.contents.iter().collect();
+        count_items(&contents, counts, sign);
+    }
+}
+
+fn diff_block_entities(old: &BlockEntity, new: &BlockEntity) -> Option<BlockEntityChange> {
+    let position = [new.position.x, new.position.y, new.position.z];
+    if let (BlockEntityKind::Sign(a), BlockEntityKind::Sign(b)) = (&old.kind, &new.kind) {
+        if a == b {
+            return None;
+        }
+        return Some(BlockEntityChange::Sign {
+            position,
+            old: a.clone(),
+            new: b.clone(),
+        });
+    }
+
+    let mut counts = HashMap::new();
+    count_items(&old.kind.items(), &mut counts, -1);
+    count_items(&new.kind.items(), &mut counts, 1);
+    let mut deltas: Vec<_> = counts.into_iter().filter(|x| x.1 != 0).collect();
+    if deltas.is_empty() {
+        return None;
+    }
+    deltas.sort_by_cached_key(|x| x.0.to_string());
+    Some(BlockEntityChange::Items {
+        position,
+        id: new.id.clone(),
+        deltas,
+    })
+}
+
+/// Compares two versions of the same chunk. Block changes are only listed
+/// if `detailed`, they're always counted.
+pub fn diff_chunks(old: &Chunk, new: &Chunk, detailed: bool) -> ChunkDiff {
+    let mut result = ChunkDiff {
+        x: new.x,
+        z: new.z,
+        changed_blocks: 0,
+        blocks: Vec::new(),
+        block_entities: Vec::new(),
+    };
+
+    let mut ys: Vec<_> = old
+        .sections
+        .iter()
+        .chain(&new.sections)
+        .map(|x| x.y)
+        .collect();
+    ys.sort();
+    ys.dedup();
+    for y in ys {
+        let y = y as i32;
+        diff_sections(
+            new,
+            y,
+            old.section(y),
+            new.section(y),
+            detailed,
+            &mut result,
+        );
+    }
+
+    let position = |x: &BlockEntity| [x.position.x, x.position.y, x.position.z];
+    let mut old_entities: HashMap<_, _> = old
+        .block_entities
+        .iter()
+        .map(|x| (position(x), x))
+        .collect();
+    let changes = &mut result.block_entities;
+    for i in &new.block_entities {
+        match old_entities.remove(&position(i)) {
+            Some(old) if old.id == i.id => changes.extend(diff_block_entities(old, i)),
+            Some(old) => {
+                changes.push(BlockEntityChange::Removed {
+                    position: position(old),
+                    id: old.id.clone(),
+                });
+                changes.push(BlockEntityChange::Added {
+                    position: position(i),
+                    id: i.id.clone(),
+                });
+            }
+            None => changes.push(BlockEntityChange::Added {
+                position: position(i),
+                id: i.id.clone(),
+            }),
+        }
+    }
+    for (position, old) in old_entities {
+        changes.push(BlockEntityChange::Removed {
+            position,
+            id: old.id.clone(),
+        });
+    }
+    changes.sort_by_key(|x| match x {
+        BlockEntityChange::Added { position, .. }
+        | BlockEntityChange::Removed { position, .. }
+        | BlockEntityChange::Items { position, .. }
+        | BlockEntityChange::Sign { position, .. } => *position,
+    });
+    result
+}
+
+/// Compares a region of two worlds. A missing one counts as a region without
+/// chunks. Chunks saved at the same time in both are skipped.
+pub fn diff_region(
+    old: Option<&Path>,
+    new: Option<&Path>,
+    detailed: bool,
+    errors: &mut Vec<anyhow::Error>,
+) -> Result<RegionDiff> {
+    let Some(path) = new.or(old) else {
+        bail!("no region to compare");
+    };
+    let (x, z) = region_coords(path).unwrap_or_default();
+    let mut old = old.map(|x| Region::load(x, false)).transpose()?;
+    let mut new = new.map(|x| Region::load(x, false)).transpose()?;
+    let mut result = RegionDiff {
+        x,
+        z,
+        unchanged: 0,
+        added: Vec::new(),
+        removed: Vec::new(),
+        compared: 0,
+        changed: Vec::new(),
+    };
+
+    let (mut old_data, mut new_data) = (Vec::new(), Vec::new());
+    for index in 0..CHUNKS_PER_REGION {
+        let coords = (x * 32 + (index % 32) as i32, z * 32 + (index / 32) as i32);
+        let in_old = old.as_ref().is_some_and(|x| x.has_chunk(index));
+        let in_new = new.as_ref().is_some_and(|x| x.has_chunk(index));
+        let (old, new) = match (old.as_mut(), new.as_mut()) {
+            (Some(a), Some(b)) if in_old && in_new => (a, b),
+            _ => {
+                if in_old {
+                    result.removed.push(coords);
+                } else if in_new {
+                    result.added.push(coords);
+                }
+                continue;
+            }
+        };
+        if old.chunk_timestamp(index) == new.chunk_timestamp(index) {
+            result.unchanged += 1;
+            continue;
+        }
+
+        let diff = old.get_chunk(&mut old_data, index).and_then(|old| {
+            let old = read_chunk(old)?;
+            let new = read_chunk(new.get_chunk(&mut new_data, index)?)?;
+            Ok(diff_chunks(&old, &new, detailed))
+        });
+        match diff {
+            Ok(diff) => {
+                result.compared += 1;
+                if !diff.is_empty() {
+                    result.changed.push(diff);
+                }
+            }
+            Err(e) => errors.push(e.context(format!("chunk {}", index))),
+        }
+    }
+    Ok(result)
+}
+
+/// Compares the regions of two versions of a dimension, the ones without changes
+/// are left out. `on_region` is called for the regions of the new one, then
+/// for the ones that are only in the old one.
+pub fn diff_dimension(
+    old: &Dimension,
+    new: &Dimension,
+    detailed: bool,
+    threads: usize,
+    mut on_region: impl FnMut(RegionReport),
+) -> Result<Vec<RegionDiff>> {
+    let old_dir = old.region_dir();
+    let states = for_each_region(
+        std::slice::from_ref(new),
+        Dimension::region_dir,
+        threads,
+        Vec::new(),
+        |result: &mut Vec<RegionDiff>, _, path, errors| {
+            let old = old_dir.join(path.file_name().unwrap_or_default());
+            let old = old.is_file().then_some(old.as_path());
+            match diff_region(old, Some(path), detailed, errors) {
+                Ok(diff) => {
+                    let chunks = diff.compared;
+                    result.push(diff);
+                    chunks
+                }
+                Err(e) => {
+                    errors.push(e);
+                    0
+                }
+            }
+        },
+        &mut on_region,
+    )?;
+    let mut result: Vec<_> = states.into_iter().flatten().collect();
+
+    // the regions that aren't there anymore
+    if old_dir.is_dir() {
+        let new_dir = new.region_dir();
+        let mut gone = region_files(&old_dir)?;
+        gone.retain(|(path, _)| !new_dir.join(path.file_name().unwrap_or_default()).is_file());
+        for (index, (path, size)) in gone.iter().enumerate() {
+            let time = Instant::now();
+            let mut errors = Vec::new();
+            match diff_region(Some(path), None, detailed, &mut errors) {
+                Ok(diff) => result.push(diff),
+                Err(e) => errors.push(e),
+            }
+            on_region(RegionReport {
+                dimension: old,
+                path,
+                size: *size,
+                done: index + 1,
+                total: gone.len(),
+                chunks: 0,
+                errors,
+                elapsed: time.elapsed(),
+            });
+        }
+    }
+
+    result.retain(|x| !x.is_empty());
+    result.sort_by_key(|x| (x.x, x.z));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Item;
+    use crate::item::Id;
+    use crate::tests::{snbt_to_nbt, temp_dir};
+    use crate::world::anvil::Region;
+    use crate::world::chunk::read_chunk;
+    use crate::world::diff::{BlockEntityChange, diff_chunks, diff_dimension, diff_region};
+    use crate::world::scan::Dimension;
+
+    fn chunk_data(sections: &str, block_entities: &str) -> Vec<u8> {
+        snbt_to_nbt(&format!(
+            "{{DataVersion: 3700, xPos: 1, zPos: 0, yPos: -4, sections: {}, block_entities: {}}}",
+            sections, block_entities
+        ))
+    }
+
+    #[test]
+    fn chunks() {
+        let old = chunk_data(
+            r#"[{Y: 0b, block_states: {palette: [{Name: "minecraft:stone"}]}}]"#,
+            r#"[
+                {id: "minecraft:chest", x: 16, y: 1, z: 0, Items: [{Slot: 0b, id: "minecraft:diamond", Count: 5b}]},
+                {id: "minecraft:sign", x: 17, y: 1, z: 0, Text1: '"hi"', Text2: '""', Text3: '""', Text4: '""'},
+                {id: "minecraft:furnace", x: 18, y: 1, z: 0}
+            ]"#,
+        );
+        // the first block is dirt, the rest is still stone
+        let mut longs = vec!["0L".to_string(); 256];
+        longs[0] = "1L".to_string();
+        let new = chunk_data(
+            &format!(
+                r#"[{{Y: 0b, block_states: {{palette: [{{Name: "minecraft:stone"}}, {{Name: "minecraft:dirt"}}], data: [L; {}]}}}}]"#,
+                longs.join(", ")
+            ),
+            r#"[
+                {id: "minecraft:chest", x: 16, y: 1, z: 0, Items: [
+                    {Slot: 0b, id: "minecraft:diamond", Count: 2b},
+                    {Slot: 1b, id: "minecraft:shulker_box", Count: 1b, tag: {BlockEntityTag: {Items: [
+                        {Slot: 0b, id: "minecraft:diamond", Count: 10b}
+                    ]}}}
+                ]},
+                {id: "minecraft:sign", x: 17, y: 1, z: 0, Text1: '"bye"', Text2: '""', Text3: '""', Text4: '""'},
+                {id: "minecraft:barrel", x: 19, y: 1, z: 0}
+            ]"#,
+        );
+
+        let diff = diff_chunks(&read_chunk(&old).unwrap(), &read_chunk(&new).unwrap(), true);
+        assert_eq!(diff.changed_blocks, 1);
+        assert_eq!(diff.blocks[0].position, [16, 0, 0]);
+        assert_eq!(
+            (diff.blocks[0].old.as_str(), diff.blocks[0].new.as_str()),
+            ("minecraft:stone", "minecraft:dirt")
+        );
+
+        let [
+            BlockEntityChange::Items { deltas, .. },
+            BlockEntityChange::Sign { old, new, .. },
+            BlockEntityChange::Removed { id: removed, .. },
+            BlockEntityChange::Added { id: added, .. },
+        ] = &diff.block_entities[..]
+        else {
+            panic!("unexpected block entity changes");
+        };
+        let mut deltas = deltas.clone();
+        deltas.sort_by_key(|x| x.1);
+        assert_eq!(
+            deltas,
+            [
+                (Id::Known(Item::ShulkerBox), 1),
+                (Id::Known(Item::Diamond), 7)
+            ]
+        );
+        assert_eq!(old.front.messages[0].to_string(), "hi");
+        assert_eq!(new.front.messages[0].to_string(), "bye");
+        assert_eq!(removed, "minecraft:furnace");
+        assert_eq!(added, "minecraft:barrel");
+    }
+
+    #[test]
+    fn gone_regions() {
+        let dir = temp_dir("diff_gone");
+        let dimension = |name: &str| {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.join("region")).unwrap();
+            Dimension {
+                name: "minecraft:overworld".to_string(),
+                path,
+            }
+        };
+        let (old, new) = (dimension("old"), dimension("new"));
+        let mut region = Region::create(&old.region_dir().join("r.0.0.mca")).unwrap();
+        region.write_chunk(0, &chunk_data("[]", "[]")).unwrap();
+        std::fs::write(old.region_dir().join("r.1.0.mca"), [1; 100]).unwrap();
+
+        let mut errors = Vec::new();
+        let regions = diff_dimension(&old, &new, false, 1, |report| {
+            errors.extend(report.errors);
+        })
+        .unwrap();
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].removed, [(0, 0)]);
+        assert_eq!(errors.len(), 1);
+
+        assert!(diff_region(None, None, false, &mut Vec::new()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
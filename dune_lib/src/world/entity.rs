@@ -0,0 +1,383 @@
+use anyhow::{Result, bail};
+use dune_common::nbt::{self, Compound, OwnedTag};
+use serde_derive::Deserialize;
+
+use crate::chat::parse_chat_nbt;
+use crate::item::ItemStack;
+use crate::world::chunk::{Storage, read_storage};
+
+#[derive(Debug)]
+pub struct VillagerData<'x> {
+    /// Like `minecraft:librarian`.
+    pub profession: &'x str,
+    pub level: i32,
+    /// The biome it looks like it's from, like `minecraft:plains`.
+    pub kind: &'x str,
+}
+
+#[derive(Debug)]
+pub struct Offer {
+    pub buy: ItemStack,
+    pub buy_b: Option<ItemStack>,
+    pub sell: ItemStack,
+    pub uses: i32,
+    pub max_uses: i32,
+    pub xp: i32,
+    pub price_multiplier: f32,
+    pub special_price: i32,
+    pub demand: i32,
+}
+
+#[derive(Debug)]
+pub struct Villager<'x> {
+    pub data: VillagerData<'x>,
+    pub offers: Vec<Offer>,
+}
+
+#[derive(Debug)]
+pub struct ItemFrame {
+    pub item: Option<ItemStack>,
+    /// In 45 degree steps.
+    pub rotation: u8,
+    pub glowing: bool,
+}
+
+#[derive(Debug)]
+pub struct ArmorStand {
+    /// Feet, legs, chest and head.
+    pub armor: [Option<ItemStack>; 4],
+    /// Main hand and off hand.
+    pub hands: [Option<ItemStack>; 2],
+}
+
+pub enum EntityKind<'x> {
+    Villager(Villager<'x>),
+    ItemFrame(ItemFrame),
+    ArmorStand(Box<ArmorStand>),
+    /// A dropped item.
+    Item(ItemStack),
+    /// Minecarts with a chest or a hopper.
+    StorageMinecart(Storage),
+    Other,
+}
+
+pub struct Entity<'x> {
+    /// Like `minecraft:villager`.
+    pub id: &'x str,
+    pub position: [f64; 3],
+    pub uuid: Option<u128>,
+    pub custom_name: Option<String>,
+    pub kind: EntityKind<'x>,
+    /// The entities riding this one, like a villager in a boat.
+    pub passengers: Vec<Entity<'x>>,
+}
+
+/// The entities of a chunk, in `entities/r.X.Z.mca` since 1.17.
+pub struct EntityChunk<'x> {
+    pub data_version: i32,
+    pub x: i32,
+    pub z: i32,
+    pub entities: Vec<Entity<'x>>,
+}
+
+// the layout on disk
+
+#[derive(Deserialize)]
+struct RawVillagerData<'x> {
+    profession: &'x str,
+    level: i32,
+    #[serde(rename = "type")]
+    kind: &'x str,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawOffer {
+    buy: Compound,
+    #[serde(rename = "buyB")]
+    buy_b: Option<Compound>,
+    sell: Compound,
+    #[serde(default)]
+    uses: i32,
+    #[serde(default)]
+    max_uses: i32,
+    #[serde(default)]
+    xp: i32,
+    #[serde(default)]
+    price_multiplier: f32,
+    #[serde(default)]
+    special_price: i32,
+    #[serde(default)]
+    demand: i32,
+}
+#[derive(Deserialize)]
+struct RawOffers {
+    #[serde(rename = "Recipes", default)]
+    recipes: Vec<RawOffer>,
+}
+/// Since 1.21.5, instead of `ArmorItems` and `HandItems`.
+#[derive(Deserialize)]
+struct RawEquipment {
+    feet: Option<Compound>,
+    legs: Option<Compound>,
+    chest: Option<Compound>,
+    head: Option<Compound>,
+    mainhand: Option<Compound>,
+    offhand: Option<Compound>,
+}
+#[derive(Deserialize)]
+struct RawEntity<'x> {
+    id: &'x str,
+    #[serde(rename = "Pos", default)]
+    pos: Vec<f64>,
+    #[serde(rename = "UUID")]
+    uuid: Option<Vec<i32>>,
+    #[serde(rename = "CustomName")]
+    custom_name: Option<OwnedTag>,
+    #[serde(rename = "Item")]
+    item: Option<Compound>,
+    #[serde(rename = "ItemRotation", default)]
+    item_rotation: u8,
+    #[serde(rename = "Items", default)]
+    items: Vec<OwnedTag>,
+    #[serde(rename = "ArmorItems", default)]
+    armor_items: Vec<OwnedTag>,
+    #[serde(rename = "HandItems", default)]
+    hand_items: Vec<OwnedTag>,
+    equipment: Option<RawEquipment>,
+    #[serde(rename = "VillagerData", borrow)]
+    villager_data: Option<RawVillagerData<'x>>,
+    #[serde(rename = "Offers")]
+    offers: Option<RawOffers>,
+    #[serde(rename = "Passengers", default, borrow)]
+    passengers: Vec<RawEntity<'x>>,
+}
+#[derive(Deserialize)]
+struct RawEntityChunk<'x> {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    #[serde(rename = "Position", default)]
+    position: Vec<i32>,
+    #[serde(rename = "Entities", default, borrow)]
+    entities: Vec<RawEntity<'x>>,
+}
+
+fn read_slot(raw: Option<Compound>) -> Result<Option<ItemStack>> {
+    match raw {
+        Some(x) => ItemStack::from_nbt(x),
+        None => Ok(None),
+    }
+}
+fn read_slots<const N: usize>(raw: Vec<OwnedTag>) -> Result<[Option<ItemStack>; N]> {
+    let mut result = [const { None }; N];
+    for (slot, raw) in result.iter_mut().zip(raw) {
+        if let OwnedTag::Compound(x) = raw {
+            *slot = ItemStack::from_nbt(x)?;
+        }
+    }
+    Ok(result)
+}
+
+fn read_offer(raw: RawOffer) -> Result<Offer> {
+    let Some(buy) = read_slot(Some(raw.buy))? else {
+        bail!("trade offer without an item to buy");
+    };
+    let Some(sell) = read_slot(Some(raw.sell))? else {
+        bail!("trade offer without an item to sell");
+    };
+    Ok(Offer {
+        buy,
+        buy_b: read_slot(raw.buy_b)?,
+        sell,
+        uses: raw.uses,
+        max_uses: raw.max_uses,
+        xp: raw.xp,
+        price_multiplier: raw.price_multiplier,
+        special_price: raw.special_price,
+        demand: raw.demand,
+    })
+}
+
+fn read_kind<'x>(raw: &mut RawEntity<'x>) -> Result<EntityKind<'x>> {
+    let id = raw.id.strip_prefix("minecraft:").unwrap_or(raw.id);
+    let r = match id {
+        "villager" | "wandering_trader" => {
+            let data = match raw.villager_data.take() {
+                Some(x) => VillagerData {
+                    profession: x.profession,
+                    level: x.level,
+                    kind: x.kind,
+                },
+                // wandering traders don't have any
+                None => VillagerData {
+                    profession: "minecraft:none",
+                    level: 0,
+                    kind: "minecraft:plains",
+                },
+            };
+            let offers = raw
+                .offers
+                .take()
+                .map(|x| x.recipes)
+                .unwrap_or_default()
+                .into_iter()
+                .map(read_offer)
+                .collect::<Result<_>>()?;
+            EntityKind::Villager(Villager { data, offers })
+        }
+        "item_frame" | "glow_item_frame" => EntityKind::ItemFrame(ItemFrame {
+            item: read_slot(raw.item.take())?,
+            rotation: raw.item_rotation,
+            glowing: id == "glow_item_frame",
+        }),
+        "armor_stand" => {
+            let stand = match raw.equipment.take() {
+                Some(x) => ArmorStand {
+                    armor: [
+                        read_slot(x.feet)?,
+                        read_slot(x.legs)?,
+                        read_slot(x.chest)?,
+                        read_slot(x.head)?,
+                    ],
+                    hands: [read_slot(x.mainhand)?, read_slot(x.offhand)?],
+                },
+                None => ArmorStand {
+                    armor: read_slots(std::mem::take(&mut raw.armor_items))?,
+                    hands: read_slots(std::mem::take(&mut raw.hand_items))?,
+                },
+            };
+            EntityKind::ArmorStand(Box::new(stand))
+        }
+        "item" => match read_slot(raw.item.take())? {
+            Some(x) => EntityKind::Item(x),
+            None => EntityKind::Other,
+        },
+        "chest_minecart" | "hopper_minecart" => {
+            EntityKind::StorageMinecart(read_storage(std::mem::take(&mut raw.items))?)
+        }
+        _ => EntityKind::Other,
+    };
+    Ok(r)
+}
+
+fn read_entity(mut raw: RawEntity) -> Result<Entity> {
+    let kind = read_kind(&mut raw)?;
+    let position = match raw.pos[..] {
+        [x, y, z] => [x, y, z],
+        _ => [0.0; 3],
+    };
+    let uuid = match raw.uuid.as_deref() {
+        Some(&[a, b, c, d]) => Some(
+            [a, b, c, d]
+                .iter()
+                .fold(0, |acc, x| (acc << 32) | *x as u32 as u128),
+        ),
+        _ => None,
+    };
+    let passengers = raw
+        .passengers
+        .into_iter()
+        .map(read_entity)
+        .collect::<Result<_>>()?;
+
+    Ok(Entity {
+        id: raw.id,
+        position,
+        uuid,
+        custom_name: raw.custom_name.map(|x| parse_chat_nbt(&x).to_string()),
+        kind,
+        passengers,
+    })
+}
+
+/// Reads a chunk from `entities/r.X.Z.mca`.
+pub fn read_entities(buf: &[u8]) -> Result<EntityChunk<'_>> {
+    let root: RawEntityChunk = nbt::from_slice(buf, nbt::Format::Named)?;
+    let [x, z] = root.position[..] else {
+        bail!(
+            "expected 2 values in `Position`, found {}",
+            root.position.len()
+        );
+    };
+    let entities = root
+        .entities
+        .into_iter()
+        .map(read_entity)
+        .collect::<Result<_>>()?;
+
+    Ok(EntityChunk {
+        data_version: root.data_version,
+        x,
+        z,
+        entities,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Item;
+    use crate::tests::snbt_to_nbt;
+    use crate::world::entity::{EntityKind, read_entities};
+
+    #[test]
+    fn entities() {
+        let snbt = r#"{
+            DataVersion: 3465, Position: [I; 2, -3],
+            Entities: [
+                {
+                    id: "minecraft:boat", Pos: [33.5d, 64.0d, -47.5d],
+                    Passengers: [{
+                        id: "minecraft:villager", Pos: [33.5d, 64.3d, -47.5d],
+                        UUID: [I; 1, 2, 3, -1],
+                        CustomName: '{"text":"Bob"}',
+                        VillagerData: {profession: "minecraft:librarian", level: 2, type: "minecraft:taiga"},
+                        Offers: {Recipes: [{
+                            buy: {id: "minecraft:emerald", Count: 5b},
+                            buyB: {id: "minecraft:air", Count: 0b},
+                            sell: {id: "minecraft:bookshelf", Count: 1b},
+                            uses: 3, maxUses: 12, priceMultiplier: 0.05f, demand: 1
+                        }]}
+                    }]
+                },
+                {id: "minecraft:glow_item_frame", ItemRotation: 3b, Item: {id: "minecraft:diamond", Count: 1b}},
+                {id: "minecraft:armor_stand", ArmorItems: [{}, {}, {}, {id: "minecraft:carved_pumpkin", Count: 1b}], HandItems: [{}, {}]}
+            ]
+        }"#;
+        let data = snbt_to_nbt(snbt);
+
+        let chunk = read_entities(&data).unwrap();
+        assert_eq!((chunk.x, chunk.z), (2, -3));
+        let [boat, frame, stand] = &chunk.entities[..] else {
+            panic!("expected 3 entities");
+        };
+
+        assert_eq!(boat.position, [33.5, 64.0, -47.5]);
+        let villager = &boat.passengers[0];
+        assert_eq!(villager.custom_name.as_deref(), Some("Bob"));
+        assert_eq!(villager.uuid, Some(0x00000001_00000002_00000003_ffffffff));
+        let EntityKind::Villager(villager) = &villager.kind else {
+            panic!("expected a villager");
+        };
+        assert_eq!(villager.data.profession, "minecraft:librarian");
+        assert_eq!(villager.data.level, 2);
+        let offer = &villager.offers[0];
+        assert_eq!(offer.buy.item, Item::Emerald);
+        assert_eq!(offer.buy.count, 5);
+        assert!(offer.buy_b.is_none());
+        assert_eq!(offer.sell.item, Item::Bookshelf);
+        assert_eq!((offer.uses, offer.max_uses, offer.demand), (3, 12, 1));
+
+        let EntityKind::ItemFrame(frame) = &frame.kind else {
+            panic!("expected an item frame");
+        };
+        assert!(frame.glowing);
+        assert_eq!(frame.rotation, 3);
+        assert_eq!(frame.item.as_ref().unwrap().item, Item::Diamond);
+
+        let EntityKind::ArmorStand(stand) = &stand.kind else {
+            panic!("expected an armor stand");
+        };
+        assert!(stand.armor[..3].iter().all(Option::is_none));
+        assert_eq!(stand.armor[3].as_ref().unwrap().item, Item::CarvedPumpkin);
+        assert!(stand.hands.iter().all(Option::is_none));
+    }
+}
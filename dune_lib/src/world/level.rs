@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use anyhow::Result;
+use dune_common::nbt::{self, Compound, IntArray, OwnedTag};
+use serde_derive::Deserialize;
+
+use crate::item::get_int;
+
+/// The world settings, from `level.dat`.
+pub struct Level {
+    pub name: String,
+    /// Hidden in `WorldGenSettings` since 1.16.
+    pub seed: Option<i64>,
+    pub spawn: [i32; 3],
+    /// Sorted by name, the values as the game shows them, like `true` or `3`.
+    pub game_rules: Vec<(String, String)>,
+    /// Missing before 1.9.
+    pub data_version: Option<i32>,
+    /// Like `1.20.4`.
+    pub version: Option<String>,
+    /// In ticks, it goes on counting past the first day.
+    pub day_time: i64,
+    /// How many ticks the world was played.
+    pub time: i64,
+    pub game_mode: i32,
+    pub hardcore: bool,
+    /// Like `vanilla` or `file/my_pack.zip`.
+    pub enabled_datapacks: Vec<String>,
+    pub disabled_datapacks: Vec<String>,
+}
+
+/// The name of a `GameType`.
+pub fn game_mode_name(id: i32) -> &'static str {
+    match id {
+        0 => "survival",
+        1 => "creative",
+        2 => "adventure",
+        3 => "spectator",
+        _ => "unknown",
+    }
+}
+
+// the layout on disk
+
+#[derive(Deserialize)]
+struct RawVersion {
+    #[serde(rename = "Name")]
+    name: String,
+}
+#[derive(Deserialize)]
+struct RawDataPacks {
+    #[serde(rename = "Enabled", default)]
+    enabled: Vec<String>,
+    #[serde(rename = "Disabled", default)]
+    disabled: Vec<String>,
+}
+#[derive(Deserialize)]
+struct RawWorldGenSettings {
+    seed: i64,
+}
+#[derive(Deserialize)]
+struct RawSpawn {
+    pos: IntArray,
+}
+#[derive(Deserialize)]
+struct RawData {
+    #[serde(rename = "LevelName", default)]
+    name: String,
+    #[serde(rename = "RandomSeed")]
+    seed: Option<i64>,
+    #[serde(rename = "WorldGenSettings")]
+    world_gen_settings: Option<RawWorldGenSettings>,
+    #[serde(rename = "SpawnX", default)]
+    spawn_x: i32,
+    #[serde(rename = "SpawnY", default)]
+    spawn_y: i32,
+    #[serde(rename = "SpawnZ", default)]
+    spawn_z: i32,
+    /// Instead of `SpawnX` and the others in newer versions.
+    spawn: Option<RawSpawn>,
+    #[serde(rename = "GameRules", default)]
+    game_rules: Compound,
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+    #[serde(rename = "Version")]
+    version: Option<RawVersion>,
+    #[serde(rename = "DayTime", default)]
+    day_time: i64,
+    #[serde(rename = "Time", default)]
+    time: i64,
+    #[serde(rename = "GameType", default)]
+    game_mode: i32,
+    #[serde(default)]
+    hardcore: bool,
+    #[serde(rename = "DataPacks")]
+    datapacks: Option<RawDataPacks>,
+}
+#[derive(Deserialize)]
+struct RawLevel {
+    #[serde(rename = "Data")]
+    data: RawData,
+}
+
+fn game_rule_value(tag: OwnedTag) -> String {
+    // strings until 1.21.11, typed since
+    match tag {
+        OwnedTag::String(x) => x,
+        OwnedTag::Byte(x) => (x != 0).to_string(),
+        x => get_int(&x).map_or_else(|| format!("{:?}", x), |x| x.to_string()),
+    }
+}
+
+/// `buf` is the uncompressed nbt.
+pub fn read_level(buf: &[u8]) -> Result<Level> {
+    let raw: RawLevel = nbt::from_slice(buf, nbt::Format::Named)?;
+    let data = raw.data;
+    let spawn = match data.spawn {
+        Some(RawSpawn { pos: IntArray(pos) }) if pos.len() == 3 => [pos[0], pos[1], pos[2]],
+        _ => [data.spawn_x, data.spawn_y, data.spawn_z],
+    };
+    let mut game_rules: Vec<_> = data
+        .game_rules
+        .into_iter()
+        .map(|(name, value)| (name, game_rule_value(value)))
+        .collect();
+    game_rules.sort();
+    let datapacks = data.datapacks.unwrap_or(RawDataPacks {
+        enabled: Vec::new(),
+        disabled: Vec::new(),
+    });
+
+    Ok(Level {
+        name: data.name,
+        seed: data.world_gen_settings.map(|x| x.seed).or(data.seed),
+        spawn,
+        game_rules,
+        data_version: data.data_version,
+        version: data.version.map(|x| x.name),
+        day_time: data.day_time,
+        time: data.time,
+        game_mode: data.game_mode,
+        hardcore: data.hardcore,
+        enabled_datapacks: datapacks.enabled,
+        disabled_datapacks: datapacks.disabled,
+    })
+}
+
+/// Reads `level.dat` in the world folder.
+pub fn read_level_file(world: &Path) -> Result<Level> {
+    read_level(&nbt::read_file(world.join("level.dat"))?.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::snbt_to_nbt;
+    use crate::world::level::read_level;
+
+    #[test]
+    fn level() {
+        let snbt = r#"{Data: {
+            LevelName: "New World", DataVersion: 3700, Version: {Name: "1.20.4", Id: 3700},
+            SpawnX: 16, SpawnY: 70, SpawnZ: -32, DayTime: 30000L, Time: 123456L,
+            GameType: 1, hardcore: 0b,
+            GameRules: {keepInventory: "true", randomTickSpeed: "3"},
+            WorldGenSettings: {seed: -42L, dimensions: {}},
+            DataPacks: {Enabled: ["vanilla", "file/trees.zip"], Disabled: ["bundle"]}
+        }}"#;
+        let data = snbt_to_nbt(snbt);
+
+        let level = read_level(&data).unwrap();
+        assert_eq!(level.name, "New World");
+        assert_eq!(level.seed, Some(-42));
+        assert_eq!(level.spawn, [16, 70, -32]);
+        assert_eq!(level.version.as_deref(), Some("1.20.4"));
+        assert_eq!((level.day_time, level.time), (30000, 123456));
+        assert_eq!(level.game_mode, 1);
+        assert_eq!(
+            level.game_rules,
+            [
+                ("keepInventory".to_string(), "true".to_string()),
+                ("randomTickSpeed".to_string(), "3".to_string())
+            ]
+        );
+        assert_eq!(level.enabled_datapacks, ["vanilla", "file/trees.zip"]);
+        assert_eq!(level.disabled_datapacks, ["bundle"]);
+    }
+}
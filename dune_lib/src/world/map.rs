@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{Result, bail};
+
+use crate::world::anvil::{CHUNKS_PER_REGION, Region, region_coords};
+use crate::world::chunk::{BlockEntityKind, Chunk, read_chunk};
+use crate::world::scan::{Dimension, RegionReport, for_each_region};
+
+/// Blocks in a region on each side, and pixels in a tile.
+pub const TILE_SIZE: usize = 512;
+
+type Color = [u8; 3];
+
+// the colors the game uses on maps
+const GRASS: Color = [127, 178, 56];
+const SAND: Color = [247, 233, 163];
+const FIRE: Color = [255, 0, 0];
+const ICE: Color = [160, 160, 255];
+const METAL: Color = [167, 167, 167];
+const PLANT: Color = [0, 124, 0];
+const SNOW: Color = [255, 255, 255];
+const CLAY: Color = [164, 168, 184];
+const DIRT: Color = [151, 109, 77];
+const STONE: Color = [112, 112, 112];
+const WATER: Color = [64, 64, 255];
+const WOOD: Color = [143, 119, 72];
+const QUARTZ: Color = [255, 252, 245];
+const GOLD: Color = [250, 238, 77];
+const DIAMOND: Color = [92, 219, 213];
+const LAPIS: Color = [74, 128, 255];
+const EMERALD: Color = [0, 217, 58];
+const PODZOL: Color = [129, 86, 49];
+const NETHER: Color = [112, 2, 0];
+const CRIMSON: Color = [148, 63, 97];
+const WARPED: Color = [58, 142, 140];
+const DEEPSLATE: Color = [100, 100, 100];
+const TERRACOTTA: Color = [152, 94, 67];
+const CHERRY: Color = [209, 177, 161];
+
+// the dye colors
+const WHITE: Color = [255, 255, 255];
+const ORANGE: Color = [216, 127, 51];
+const MAGENTA: Color = [178, 76, 216];
+const LIGHT_BLUE: Color = [102, 153, 216];
+const YELLOW: Color = [229, 229, 51];
+const LIME: Color = [127, 204, 25];
+const PINK: Color = [242, 127, 165];
+const GRAY: Color = [76, 76, 76];
+const LIGHT_GRAY: Color = [153, 153, 153];
+const CYAN: Color = [76, 127, 153];
+const PURPLE: Color = [127, 63, 178];
+const BLUE: Color = [51, 76, 178];
+const BROWN: Color = [102, 76, 51];
+const GREEN: Color = [102, 127, 51];
+const RED: Color = [153, 51, 51];
+const BLACK: Color = [25, 25, 25];
+
+/// Longer names first, so `light_blue` isn't taken for `blue`.
+const DYES: [(&str, Color); 16] = [
+    ("light_blue", LIGHT_BLUE),
+    ("light_gray", LIGHT_GRAY),
+    ("white", WHITE),
+    ("orange", ORANGE),
+    ("magenta", MAGENTA),
+    ("yellow", YELLOW),
+    ("lime", LIME),
+    ("pink", PINK),
+    ("gray", GRAY),
+    ("cyan", CYAN),
+    ("purple", PURPLE),
+    ("blue", BLUE),
+    ("brown", BROWN),
+    ("green", GREEN),
+    ("red", RED),
+    ("black", BLACK),
+];
+
+const WOODS: [(&str, Color); 11] = [
+    ("dark_oak", BROWN),
+    ("oak", WOOD),
+    ("spruce", PODZOL),
+    ("birch", SAND),
+    ("jungle", DIRT),
+    ("acacia", ORANGE),
+    ("mangrove", RED),
+    ("cherry", CHERRY),
+    ("bamboo", YELLOW),
+    ("crimson", CRIMSON),
+    ("warped", WARPED),
+];
+
+const WATERS: [&str; 6] = [
+    "water",
+    "bubble_column",
+    "kelp",
+    "kelp_plant",
+    "seagrass",
+    "tall_seagrass",
+];
+
+fn is_water(name: &str) -> bool {
+    WATERS.contains(&name)
+}
+
+/// Blocks that can't be seen from above.
+const INVISIBLE: [&str; 26] = [
+    "air",
+    "cave_air",
+    "void_air",
+    "barrier",
+    "light",
+    "structure_void",
+    "glass",
+    "glass_pane",
+    "tripwire",
+    "tripwire_hook",
+    "lever",
+    "rail",
+    "powered_rail",
+    "detector_rail",
+    "activator_rail",
+    "scaffolding",
+    "torch",
+    "wall_torch",
+    "soul_torch",
+    "soul_wall_torch",
+    "redstone_torch",
+    "redstone_wall_torch",
+    "stone_button",
+    "polished_blackstone_button",
+    "ladder",
+    "end_rod",
+];
+
+const BLOCKS: &[(&str, Color)] = &[
+    ("lava", FIRE),
+    ("fire", FIRE),
+    ("tnt", FIRE),
+    ("redstone_wire", FIRE),
+    ("redstone_block", FIRE),
+    ("grass_block", GRASS),
+    ("short_grass", PLANT),
+    ("grass", PLANT),
+    ("tall_grass", PLANT),
+    ("fern", PLANT),
+    ("large_fern", PLANT),
+    ("vine", PLANT),
+    ("cactus", PLANT),
+    ("lily_pad", PLANT),
+    ("sugar_cane", PLANT),
+    ("bamboo", PLANT),
+    ("dandelion", PLANT),
+    ("poppy", PLANT),
+    ("azure_bluet", PLANT),
+    ("oxeye_daisy", PLANT),
+    ("cornflower", PLANT),
+    ("allium", PLANT),
+    ("blue_orchid", PLANT),
+    ("sunflower", PLANT),
+    ("lilac", PLANT),
+    ("rose_bush", PLANT),
+    ("peony", PLANT),
+    ("wheat", PLANT),
+    ("carrots", PLANT),
+    ("potatoes", PLANT),
+    ("beetroots", PLANT),
+    ("sweet_berry_bush", PLANT),
+    ("azalea", PLANT),
+    ("flowering_azalea", PLANT),
+    ("azalea_leaves", PLANT),
+    ("flowering_azalea_leaves", PLANT),
+    ("cherry_leaves", PINK),
+    ("snow", SNOW),
+    ("snow_block", SNOW),
+    ("powder_snow", SNOW),
+    ("ice", ICE),
+    ("packed_ice", ICE),
+    ("blue_ice", ICE),
+    ("frosted_ice", ICE),
+    ("sand", SAND),
+    ("sandstone", SAND),
+    ("smooth_sandstone", SAND),
+    ("cut_sandstone", SAND),
+    ("chiseled_sandstone", SAND),
+    ("end_stone", SAND),
+    ("end_stone_bricks", SAND),
+    ("glowstone", SAND),
+    ("bone_block", SAND),
+    ("red_sand", ORANGE),
+    ("red_sandstone", ORANGE),
+    ("dirt", DIRT),
+    ("coarse_dirt", DIRT),
+    ("farmland", DIRT),
+    ("dirt_path", DIRT),
+    ("rooted_dirt", DIRT),
+    ("granite", DIRT),
+    ("polished_granite", DIRT),
+    ("mud", DIRT),
+    ("packed_mud", DIRT),
+    ("mud_bricks", DIRT),
+    ("podzol", PODZOL),
+    ("mycelium", PURPLE),
+    ("clay", CLAY),
+    ("iron_block", METAL),
+    ("iron_bars", METAL),
+    ("iron_door", METAL),
+    ("anvil", METAL),
+    ("heavy_weighted_pressure_plate", METAL),
+    ("gold_block", GOLD),
+    ("diamond_block", DIAMOND),
+    ("lapis_block", LAPIS),
+    ("emerald_block", EMERALD),
+    ("netherrack", NETHER),
+    ("nether_bricks", NETHER),
+    ("nether_wart_block", NETHER),
+    ("magma_block", NETHER),
+    ("soul_sand", BROWN),
+    ("soul_soil", BROWN),
+    ("obsidian", BLACK),
+    ("crying_obsidian", BLACK),
+    ("basalt", BLACK),
+    ("polished_basalt", BLACK),
+    ("coal_block", BLACK),
+    ("blackstone", BLACK),
+    ("terracotta", TERRACOTTA),
+    ("pumpkin", ORANGE),
+    ("carved_pumpkin", ORANGE),
+    ("jack_o_lantern", ORANGE),
+    ("melon", LIME),
+    ("hay_block", YELLOW),
+    ("quartz_block", QUARTZ),
+    ("smooth_quartz", QUARTZ),
+    ("diorite", QUARTZ),
+    ("polished_diorite", QUARTZ),
+    ("deepslate", DEEPSLATE),
+    ("cobbled_deepslate", DEEPSLATE),
+    ("crimson_nylium", CRIMSON),
+    ("warped_nylium", WARPED),
+    ("stone", STONE),
+    ("cobblestone", STONE),
+    ("mossy_cobblestone", STONE),
+    ("stone_bricks", STONE),
+    ("smooth_stone", STONE),
+    ("andesite", STONE),
+    ("polished_andesite", STONE),
+    ("gravel", STONE),
+    ("bamboo_block", YELLOW),
+    ("bamboo_mosaic", YELLOW),
+];
+
+/// Blocks that come in every dye color, like `red_wool`.
+const DYED: [&str; 12] = [
+    "wool",
+    "carpet",
+    "concrete",
+    "concrete_powder",
+    "terracotta",
+    "glazed_terracotta",
+    "stained_glass",
+    "stained_glass_pane",
+    "shulker_box",
+    "bed",
+    "banner",
+    "candle",
+];
+
+/// Blocks that come in every wood, like `oak_planks`. The logs can be stripped
+/// too.
+const WOODEN: [&str; 15] = [
+    "planks",
+    "log",
+    "wood",
+    "stem",
+    "hyphae",
+    "slab",
+    "stairs",
+    "fence",
+    "fence_gate",
+    "door",
+    "trapdoor",
+    "pressure_plate",
+    "sign",
+    "hanging_sign",
+    "leaves",
+];
+
+fn colors() -> &'static HashMap<String, Option<Color>> {
+    static COLORS: OnceLock<HashMap<String, Option<Color>>> = OnceLock::new();
+    COLORS.get_or_init(|| {
+        let mut colors = HashMap::new();
+        for (dye, color) in DYES {
+            for kind in DYED {
+                colors.insert(format!("{}_{}", dye, kind), Some(color));
+            }
+        }
+        for (wood, color) in WOODS {
+            for kind in WOODEN {
+                let color = if kind == "leaves" { PLANT } else { color };
+                colors.insert(format!("{}_{}", wood, kind), Some(color));
+                if matches!(kind, "log" | "wood" | "stem" | "hyphae") {
+                    colors.insert(format!("stripped_{}_{}", wood, kind), Some(color));
+                }
+            }
+            colors.insert(format!("{}_sapling", wood), Some(PLANT));
+            colors.insert(format!("{}_button", wood), None);
+        }
+        for name in WATERS {
+            colors.insert(name.to_string(), Some(WATER));
+        }
+        for (name, color) in BLOCKS {
+            colors.insert(name.to_string(), Some(*color));
+        }
+        for name in INVISIBLE {
+            colors.insert(name.to_string(), None);
+        }
+        colors
+    })
+}
+
+/// How a block looks from above on a map. `None` for the ones that can't be seen,
+/// like air and glass. Blocks that aren't known are gray, like stone.
+pub fn block_color(name: &str) -> Option<Color> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    match colors().get(name) {
+        Some(color) => *color,
+        None => guess_color(name),
+    }
+}
+
+/// For the blocks missing from the table, from the words in their names.
+fn guess_color(name: &str) -> Option<Color> {
+    let color = match name {
+        x if x.ends_with("torch") || x.ends_with("_button") => return None,
+        x if x.ends_with("_leaves")
+            || x.ends_with("_sapling")
+            || x.ends_with("grass")
+            || x.ends_with("fern")
+            || x.ends_with("vine")
+            || x.ends_with("vines")
+            || x.ends_with("tulip") =>
+        {
+            PLANT
+        }
+        x if x.starts_with("red_sand") => ORANGE,
+        x if x.starts_with("quartz") || x.contains("_quartz") => QUARTZ,
+        x if x.contains("deepslate") => DEEPSLATE,
+        x if x.contains("blackstone") => BLACK,
+        x if x.contains("stone") || x.contains("cobble") || x.ends_with("_ore") => STONE,
+        x => {
+            if let Some((_, color)) = DYES.iter().find(|(dye, _)| x.starts_with(dye)) {
+                *color
+            } else if let Some((_, color)) = WOODS
+                .iter()
+                .find(|(wood, _)| x.trim_start_matches("stripped_").starts_with(wood))
+            {
+                *color
+            } else {
+                STONE
+            }
+        }
+    };
+    Some(color)
+}
+
+/// A pixel before it's shaded by the height of the one north of it.
+#[derive(Clone, Copy)]
+struct Column {
+    color: Color,
+    height: i32,
+    /// How much water is over the block, 0 if none.
+    water_depth: i32,
+}
+
+fn render_column(chunk: &Chunk, x: i32, z: i32) -> Option<Column> {
+    let top = match chunk.heightmaps.get("WORLD_SURFACE") {
+        Some(heightmap) => heightmap.height_at(x, z)?,
+        None => (chunk.sections.last()?.y as i32 + 1) * 16,
+    };
+    let mut water_depth = 0;
+    for y in (chunk.min_y..top).rev() {
+        let Some(block) = chunk.block_at(x, y, z) else {
+            continue;
+        };
+        let name = block.name.strip_prefix("minecraft:").unwrap_or(block.name);
+        if is_water(name) || block.property("waterlogged") == Some("true") {
+            water_depth += 1;
+            continue;
+        }
+        let Some(color) = block_color(name) else {
+            continue;
+        };
+        let height = y + water_depth;
+        return Some(Column {
+            color,
+            height,
+            water_depth,
+        });
+    }
+    None
+}
+
+fn shade(color: Color, brightness: u32) -> Color {
+    color.map(|x| (x as u32 * brightness / 255) as u8)
+}
+
+/// A region seen from above.
+pub struct Tile {
+    pub x: i32,
+    pub z: i32,
+    /// RGB, `TILE_SIZE` pixels on each side. Chunks that weren't generated are
+    /// black.
+    pub pixels: Vec<u8>,
+}
+
+impl Tile {
+    /// Every `scale`th pixel, for overviews.
+    pub fn scaled_down(&self, scale: usize) -> Vec<u8> {
+        let size = TILE_SIZE / scale;
+        let mut result = Vec::with_capacity(size * size * 3);
+        for z in 0..size {
+            for x in 0..size {
+                let index = (z * scale * TILE_SIZE + x * scale) * 3;
+                result.extend_from_slice(&self.pixels[index..index + 3]);
+            }
+        }
+        result
+    }
+}
+
+/// Draws the blocks from above, shaded like on the game's maps. With `markers`,
+/// signs are drawn as yellow squares and containers as magenta ones. Chunks that
+/// can't be read are left out and added to `errors`. Returns how many chunks
+/// were drawn too.
+pub fn render_region(
+    path: &Path,
+    markers: bool,
+    errors: &mut Vec<anyhow::Error>,
+) -> Result<(Tile, usize)> {
+    let Some((region_x, region_z)) = region_coords(path) else {
+        bail!("not a region file: {}", path.display());
+    };
+    let mut region = Region::load(path, false)?;
+    let mut columns = vec![None; TILE_SIZE * TILE_SIZE];
+    let mut marks = Vec::new();
+    let mut tmp = Vec::new();
+    let mut chunks = 0;
+    for index in 0..CHUNKS_PER_REGION {
+        let result = region.get_chunk(&mut tmp, index).and_then(|data| {
+            if data.is_empty() {
+                return Ok(false);
+            }
+            let chunk = read_chunk(data)?;
+            let (chunk_x, chunk_z) = (index % 32 * 16, index / 32 * 16);
+            for z in 0..16 {
+                for x in 0..16 {
+                    let column = render_column(&chunk, x as i32, z as i32);
+                    columns[(chunk_z + z) * TILE_SIZE + chunk_x + x] = column;
+                }
+            }
+            for i in &chunk.block_entities {
+                let color = match i.kind {
+                    BlockEntityKind::Sign(_) => [255, 255, 0],
+                    BlockEntityKind::Storage(_) => [255, 0, 255],
+                    _ => continue,
+                };
+                let (x, z) = (i.position.x & 511, i.position.z & 511);
+                marks.push((x as usize, z as usize, color));
+            }
+            Ok(true)
+        });
+        match result {
+            Ok(true) => chunks += 1,
+            Ok(false) => {}
+            Err(e) => errors.push(e.context(format!("chunk {}", index))),
+        }
+    }
+
+    let mut pixels = vec![0; TILE_SIZE * TILE_SIZE * 3];
+    for (index, column) in columns.iter().enumerate() {
+        let Some(column) = column else {
+            continue;
+        };
+        let color = match column.water_depth {
+            0 => {
+                // lit from the north, like the game's maps
+                let north = match index.checked_sub(TILE_SIZE) {
+                    Some(x) => columns[x].map_or(column.height, |x| x.height),
+                    None => column.height,
+                };
+                let brightness = match column.height.cmp(&north) {
+                    std::cmp::Ordering::Greater => 255,
+                    std::cmp::Ordering::Equal => 220,
+                    std::cmp::Ordering::Less => 180,
+                };
+                shade(column.color, brightness)
+            }
+            1..=2 => shade(WATER, 255),
+            3..=6 => shade(WATER, 220),
+            _ => shade(WATER, 180),
+        };
+        pixels[index * 3..index * 3 + 3].copy_from_slice(&color);
+    }
+    if markers {
+        for (x, z, color) in marks {
+            for z in z.saturating_sub(1)..(z + 2).min(TILE_SIZE) {
+                for x in x.saturating_sub(1)..(x + 2).min(TILE_SIZE) {
+                    let index = (z * TILE_SIZE + x) * 3;
+                    pixels[index..index + 3].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    let tile = Tile {
+        x: region_x,
+        z: region_z,
+        pixels,
+    };
+    Ok((tile, chunks))
+}
+
+pub fn write_png(path: &Path, width: usize, height: usize, rgb: &[u8]) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgb)?;
+    Ok(())
+}
+
+/// The most pixels the overview can have on a side.
+pub const MAX_OVERVIEW_SIZE: usize = 1 << 16;
+
+pub struct MapOptions {
+    /// Draws signs and containers, see [`render_region`].
+    pub markers: bool,
+    /// How many times smaller the overview is than the tiles. It has to divide
+    /// `TILE_SIZE`.
+    pub overview_scale: usize,
+    /// 0 for one per core.
+    pub threads: usize,
+}
+
+/// Writes a tile for every region of `dimension` to `out/r.X.Z.png`, and all of
+/// them together to `out/overview.png`.
+pub fn render_dimension(
+    dimension: &Dimension,
+    out: &Path,
+    options: &MapOptions,
+    on_region: impl FnMut(RegionReport),
+) -> Result<()> {
+    let scale = options.overview_scale;
+    if scale == 0 || !TILE_SIZE.is_multiple_of(scale) {
+        bail!("the overview scale has to divide {}", TILE_SIZE);
+    }
+    fs::create_dir_all(out)?;
+
+    let states = for_each_region(
+        std::slice::from_ref(dimension),
+        Dimension::region_dir,
+        options.threads,
+        Vec::new(),
+        |small_tiles, _, path, errors| {
+            let (tile, chunks) = match render_region(path, options.markers, errors) {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.push(e);
+                    return 0;
+                }
+            };
+            let name = format!("r.{}.{}.png", tile.x, tile.z);
+            if let Err(e) = write_png(&out.join(name), TILE_SIZE, TILE_SIZE, &tile.pixels) {
+                errors.push(e);
+            }
+            small_tiles.push((tile.x, tile.z, tile.scaled_down(scale)));
+            chunks
+        },
+        on_region,
+    )?;
+
+    let mut small_tiles: Vec<_> = states.into_iter().flatten().collect();
+    if small_tiles.is_empty() {
+        return Ok(());
+    }
+    let min_x = small_tiles.iter().map(|x| x.0).min().unwrap();
+    let max_x = small_tiles.iter().map(|x| x.0).max().unwrap();
+    let min_z = small_tiles.iter().map(|x| x.1).min().unwrap();
+    let max_z = small_tiles.iter().map(|x| x.1).max().unwrap();
+    let size = TILE_SIZE / scale;
+    let width = (max_x - min_x + 1) as usize * size;
+    let height = (max_z - min_z + 1) as usize * size;
+    if width.max(height) > MAX_OVERVIEW_SIZE {
+        bail!(
+            "the overview would be {}x{} pixels, use a bigger scale than {}",
+            width,
+            height,
+            scale
+        );
+    }
+
+    let file = BufWriter::new(File::create(out.join("overview.png"))?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?.into_stream_writer()?;
+    // a row of regions at a time, the whole overview can be too big to keep
+    small_tiles.sort_unstable_by_key(|x| (x.1, x.0));
+    let mut small_tiles = small_tiles.into_iter().peekable();
+    let mut band = vec![0; width * size * 3];
+    for z in min_z..=max_z {
+        band.fill(0);
+        while let Some((x, _, pixels)) = small_tiles.next_if(|x| x.1 == z) {
+            let left = (x - min_x) as usize * size;
+            for row in 0..size {
+                let start = (row * width + left) * 3;
+                band[start..start + size * 3]
+                    .copy_from_slice(&pixels[row * size * 3..(row + 1) * size * 3]);
+            }
+        }
+        writer.write_all(&band)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{snbt_to_nbt, temp_dir};
+    use crate::world::anvil::Region;
+    use crate::world::map::{
+        GRASS, MapOptions, PLANT, PURPLE, TILE_SIZE, WATER, YELLOW, block_color, render_dimension,
+        render_region, shade,
+    };
+    use crate::world::scan::Dimension;
+
+    #[test]
+    fn colors() {
+        assert_eq!(block_color("minecraft:air"), None);
+        assert_eq!(
+            block_color("minecraft:light_blue_wool"),
+            Some([102, 153, 216])
+        );
+        assert_eq!(
+            block_color("minecraft:stripped_birch_log"),
+            block_color("sand")
+        );
+        assert_eq!(block_color("minecraft:something_new"), block_color("stone"));
+        assert_eq!(block_color("minecraft:oak_leaves"), Some(PLANT));
+        assert_eq!(block_color("minecraft:oak_button"), None);
+        assert_eq!(
+            block_color("minecraft:stripped_warped_stem"),
+            block_color("warped_planks")
+        );
+        assert_eq!(
+            block_color("minecraft:red_glazed_terracotta"),
+            block_color("red_wool")
+        );
+        assert_eq!(block_color("minecraft:hay_block"), Some(YELLOW));
+        assert_eq!(block_color("minecraft:mycelium"), Some(PURPLE));
+        // not in the table
+        assert_eq!(block_color("minecraft:pink_tulip"), Some(PLANT));
+    }
+
+    #[test]
+    fn render() {
+        let dir = temp_dir("map");
+        let path = dir.join("r.0.0.mca");
+        let mut region = Region::create(&path).unwrap();
+        let chunks = [
+            (
+                0,
+                r#"[{Y: 4b, block_states: {palette: [{Name: "minecraft:grass_block"}]}}]"#,
+            ),
+            (
+                1,
+                r#"[{Y: 3b, block_states: {palette: [{Name: "minecraft:stone"}]}},
+                    {Y: 4b, block_states: {palette: [{Name: "minecraft:water"}]}}]"#,
+            ),
+        ];
+        for (index, sections) in chunks {
+            let snbt = format!(
+                "{{DataVersion: 3700, xPos: {}, zPos: 0, yPos: -4, sections: {}}}",
+                index, sections
+            );
+            region.write_chunk(index, &snbt_to_nbt(&snbt)).unwrap();
+        }
+
+        let mut errors = Vec::new();
+        let (tile, chunks) = render_region(&path, false, &mut errors).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(chunks, 2);
+        let pixel = |x: usize, z: usize| {
+            let index = (z * TILE_SIZE + x) * 3;
+            [
+                tile.pixels[index],
+                tile.pixels[index + 1],
+                tile.pixels[index + 2],
+            ]
+        };
+        assert_eq!(pixel(0, 0), shade(GRASS, 220));
+        assert_eq!(pixel(15, 15), shade(GRASS, 220));
+        assert_eq!(pixel(16, 0), shade(WATER, 180));
+        assert_eq!(pixel(0, 16), [0, 0, 0]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overview() {
+        let dir = temp_dir("map_overview");
+        std::fs::create_dir_all(dir.join("region")).unwrap();
+        let chunk = snbt_to_nbt(
+            r#"{DataVersion: 3700, xPos: 0, zPos: 0, yPos: -4,
+                sections: [{Y: 4b, block_states: {palette: [{Name: "minecraft:grass_block"}]}}]}"#,
+        );
+        // with a region missing between them
+        for name in ["r.0.0.mca", "r.0.2.mca"] {
+            let mut region = Region::create(&dir.join("region").join(name)).unwrap();
+            region.write_chunk(0, &chunk).unwrap();
+        }
+        let dimension = Dimension {
+            name: "minecraft:overworld".to_string(),
+            path: dir.clone(),
+        };
+        let options = MapOptions {
+            markers: false,
+            overview_scale: 8,
+            threads: 1,
+        };
+        let out = dir.join("map");
+        render_dimension(&dimension, &out, &options, |_| {}).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(out.join("overview.png")).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let size = TILE_SIZE / 8;
+        assert_eq!((reader.info().width, reader.info().height), (64, 192));
+        assert_eq!(pixels[..3], shade(GRASS, 220));
+        assert_eq!(pixels[size * size * 3..size * size * 3 + 3], [0, 0, 0]);
+        assert_eq!(
+            pixels[2 * size * size * 3..2 * size * size * 3 + 3],
+            shade(GRASS, 220)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,2 +1,10 @@
 pub mod anvil;
 pub mod chunk;
+pub mod diff;
+pub mod entity;
+pub mod level;
+pub mod map;
+pub mod player;
+pub mod scan;
+pub mod schematic;
+pub mod section;
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use dune_common::nbt::{self, Compound, OwnedTag};
+use serde_derive::Deserialize;
+
+use crate::item::{ItemStack, read_items};
+
+/// A player from `playerdata/<uuid>.dat`.
+pub struct Player {
+    pub uuid: Option<u128>,
+    pub position: [f64; 3],
+    /// Like `minecraft:overworld`.
+    pub dimension: String,
+    /// With the armor and the off hand, which are apart since 1.21.5.
+    pub inventory: Vec<ItemStack>,
+    pub ender_items: Vec<ItemStack>,
+    pub health: f32,
+    pub food_level: i32,
+    pub xp_level: i32,
+    /// All the points collected since the last death.
+    pub xp_total: i32,
+    /// How far into the current level, from 0 to 1.
+    pub xp_progress: f32,
+    /// See [`game_mode_name`](crate::world::level::game_mode_name).
+    pub game_mode: i32,
+    pub abilities: Abilities,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Abilities {
+    pub flying: bool,
+    #[serde(rename = "mayfly")]
+    pub may_fly: bool,
+    /// Breaks blocks at once, like in creative.
+    pub instabuild: bool,
+    pub invulnerable: bool,
+    #[serde(rename = "mayBuild")]
+    pub may_build: bool,
+    #[serde(rename = "flySpeed")]
+    pub fly_speed: f32,
+    #[serde(rename = "walkSpeed")]
+    pub walk_speed: f32,
+}
+
+// the layout on disk
+
+#[derive(Deserialize)]
+struct RawPlayer {
+    #[serde(rename = "Pos", default)]
+    pos: Vec<f64>,
+    #[serde(rename = "Dimension")]
+    dimension: Option<OwnedTag>,
+    #[serde(rename = "Inventory", default)]
+    inventory: Vec<OwnedTag>,
+    #[serde(rename = "EnderItems", default)]
+    ender_items: Vec<OwnedTag>,
+    equipment: Option<Compound>,
+    #[serde(rename = "Health", default)]
+    health: f32,
+    #[serde(rename = "foodLevel", default)]
+    food_level: i32,
+    #[serde(rename = "XpLevel", default)]
+    xp_level: i32,
+    #[serde(rename = "XpTotal", default)]
+    xp_total: i32,
+    #[serde(rename = "XpP", default)]
+    xp_progress: f32,
+    #[serde(rename = "playerGameType", default)]
+    game_mode: i32,
+    #[serde(default)]
+    abilities: Abilities,
+}
+
+#[derive(Deserialize)]
+struct RawCachedUser {
+    name: String,
+    uuid: String,
+}
+
+fn dimension_name(tag: Option<OwnedTag>) -> String {
+    // a number before 1.16
+    let name = match tag {
+        Some(OwnedTag::String(x)) => return x,
+        Some(OwnedTag::Int(-1)) => "the_nether",
+        Some(OwnedTag::Int(1)) => "the_end",
+        _ => "overworld",
+    };
+    format!("minecraft:{}", name)
+}
+
+/// `uuid` is usually taken from the file name.
+pub fn read_player(buf: &[u8], uuid: Option<u128>) -> Result<Player> {
+    let raw: RawPlayer = nbt::from_slice(buf, nbt::Format::Named)?;
+    let position = match raw.pos[..] {
+        [x, y, z] => [x, y, z],
+        _ => [0.0; 3],
+    };
+    let mut inventory = read_items(raw.inventory)?;
+    let equipment = raw.equipment.unwrap_or_default();
+    inventory.extend(read_items(equipment.into_values().collect())?);
+
+    Ok(Player {
+        uuid,
+        position,
+        dimension: dimension_name(raw.dimension),
+        inventory,
+        ender_items: read_items(raw.ender_items)?,
+        health: raw.health,
+        food_level: raw.food_level,
+        xp_level: raw.xp_level,
+        xp_total: raw.xp_total,
+        xp_progress: raw.xp_progress,
+        game_mode: raw.game_mode,
+        abilities: raw.abilities,
+    })
+}
+
+/// The `.dat` files in `playerdata`, with the uuids in their names.
+pub fn player_files(world: &Path) -> Result<Vec<(PathBuf, Option<u128>)>> {
+    let dir = world.join("playerdata");
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut result = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().unwrap_or_default() != "dat" {
+            continue;
+        }
+        let uuid = path
+            .file_stem()
+            .and_then(|x| parse_uuid(&x.to_string_lossy()));
+        result.push((path, uuid));
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// With or without the dashes.
+pub fn parse_uuid(s: &str) -> Option<u128> {
+    let s = s.replace('-', "");
+    if s.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&s, 16).ok()
+}
+
+/// Like `069a79f4-44e9-4726-a5be-fca90e38aaf5`.
+pub fn format_uuid(uuid: u128) -> String {
+    let x = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &x[..8],
+        &x[8..12],
+        &x[12..16],
+        &x[16..20],
+        &x[20..]
+    )
+}
+
+/// The names of the players who joined a server, from its `usercache.json`.
+pub fn read_user_cache(path: &Path) -> Result<HashMap<u128, String>> {
+    let users: Vec<RawCachedUser> = serde_json::from_slice(&fs::read(path)?)?;
+    Ok(users
+        .into_iter()
+        .filter_map(|x| Some((parse_uuid(&x.uuid)?, x.name)))
+        .collect())
+}
+
+/// `usercache.json` is next to the world folder on servers, singleplayer
+/// worlds don't have one.
+pub fn find_user_cache(world: &Path) -> Option<PathBuf> {
+    let parent = world.parent().filter(|x| !x.as_os_str().is_empty());
+    let parent = parent.unwrap_or(Path::new("."));
+    [world, parent]
+        .into_iter()
+        .map(|x| x.join("usercache.json"))
+        .find(|x| x.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Item;
+    use crate::item::Id;
+    use crate::tests::snbt_to_nbt;
+    use crate::world::player::{format_uuid, parse_uuid, read_player};
+
+    #[test]
+    fn player() {
+        let snbt = r#"{
+            Pos: [10.5d, 70.0d, -3.2d], Dimension: -1,
+            Inventory: [
+                {Slot: 0b, id: "minecraft:shulker_box", Count: 1b, tag: {BlockEntityTag: {Items: [
+                    {Slot: 0b, id: "minecraft:diamond", Count: 64b}
+                ]}}},
+                {Slot: 1b, id: "minecraft:torch", Count: 12b}
+            ],
+            EnderItems: [{Slot: 3b, id: "minecraft:elytra", Count: 1b}],
+            equipment: {head: {id: "minecraft:turtle_helmet", count: 1}},
+            Health: 17.5f, foodLevel: 20, XpLevel: 30, XpTotal: 1400, XpP: 0.25f, playerGameType: 0,
+            abilities: {flying: 0b, mayfly: 0b, instabuild: 0b, invulnerable: 0b, mayBuild: 1b,
+                flySpeed: 0.05f, walkSpeed: 0.1f}
+        }"#;
+        let data = snbt_to_nbt(snbt);
+
+        let player = read_player(&data, Some(7)).unwrap();
+        assert_eq!(player.dimension, "minecraft:the_nether");
+        assert_eq!(player.position, [10.5, 70.0, -3.2]);
+        let items: Vec<_> = player.inventory.iter().map(|x| x.item.clone()).collect();
+        assert_eq!(
+            items,
+            [Item::ShulkerBox, Item::Torch, Item::TurtleHelmet].map(Id::Known)
+        );
+        assert_eq!(player.inventory[0].data.contents[0].count, 64);
+        assert_eq!(player.ender_items[0].item, Item::Elytra);
+        assert_eq!(
+            (player.health, player.xp_level, player.xp_total),
+            (17.5, 30, 1400)
+        );
+        assert!(player.abilities.may_build && !player.abilities.may_fly);
+        assert_eq!(player.abilities.walk_speed, 0.1);
+    }
+
+    #[test]
+    fn uuids() {
+        let uuid = parse_uuid("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+        assert_eq!(parse_uuid("069a79f444e94726a5befca90e38aaf5"), Some(uuid));
+        assert_eq!(format_uuid(uuid), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(parse_uuid("Notch"), None);
+    }
+}
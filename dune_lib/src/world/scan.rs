@@ -0,0 +1,400 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+
+use crate::world::anvil::{CHUNKS_PER_REGION, Region, region_coords};
+use crate::world::chunk::{Chunk, read_chunk};
+use crate::world::entity::{EntityChunk, read_entities};
+
+/// A folder with the `region`, `entities` and `poi` folders of a dimension.
+#[derive(Debug, Clone)]
+pub struct Dimension {
+    /// Like `minecraft:overworld`.
+    pub name: String,
+    pub path: PathBuf,
+}
+impl Dimension {
+    pub fn region_dir(&self) -> PathBuf {
+        self.path.join("region")
+    }
+    /// Since 1.17 entities are saved apart from the chunks.
+    pub fn entities_dir(&self) -> PathBuf {
+        self.path.join("entities")
+    }
+
+    /// The vanilla dimensions and the ones added by datapacks, in
+    /// `dimensions/<namespace>/<name>`. Only the ones with regions are returned.
+    pub fn find_all(world: &Path) -> Result<Vec<Dimension>> {
+        let mut result = Vec::new();
+        let mut add = |name: String, path: PathBuf| {
+            if path.join("region").is_dir() {
+                result.push(Dimension { name, path });
+            }
+        };
+        for (name, dir) in [
+            ("minecraft:overworld", ""),
+            ("minecraft:the_nether", "DIM-1"),
+            ("minecraft:the_end", "DIM1"),
+        ] {
+            add(name.to_string(), world.join(dir));
+        }
+
+        let custom = world.join("dimensions");
+        if !custom.is_dir() {
+            return Ok(result);
+        }
+        for namespace in fs::read_dir(custom)? {
+            let namespace = namespace?;
+            if !namespace.file_type()?.is_dir() {
+                continue;
+            }
+            for dimension in fs::read_dir(namespace.path())? {
+                let dimension = dimension?;
+                let name = format!(
+                    "{}:{}",
+                    namespace.file_name().to_string_lossy(),
+                    dimension.file_name().to_string_lossy()
+                );
+                add(name, dimension.path());
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The `r.X.Z.mca` files in `dir` and their sizes, sorted by coordinates. Empty
+/// files are skipped, the game leaves them behind.
+pub fn region_files(dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let mut result = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(coords) = region_coords(&path) else {
+            continue;
+        };
+        if path.extension().unwrap_or_default() != "mca" {
+            continue;
+        }
+        let size = path.metadata()?.len();
+        if size != 0 {
+            result.push((coords, path, size));
+        }
+    }
+    result.sort_unstable_by_key(|x| x.0);
+    Ok(result
+        .into_iter()
+        .map(|(_, path, size)| (path, size))
+        .collect())
+}
+
+/// Gets every chunk of a scan. Each thread gets its own clone, they're merged at
+/// the end.
+pub trait ChunkVisitor: Clone + Send {
+    fn visit(&mut self, dimension: &Dimension, chunk: &Chunk) -> Result<()>;
+    fn merge(&mut self, other: Self);
+}
+/// Like [`ChunkVisitor`], for the chunks of the entities regions.
+pub trait EntityVisitor: Clone + Send {
+    fn visit(&mut self, dimension: &Dimension, chunk: &EntityChunk) -> Result<()>;
+    fn merge(&mut self, other: Self);
+}
+
+/// Decodes the chunks of a kind of region, so both kinds share the scanner.
+trait RegionVisitor: Clone + Send {
+    fn region_dir(dimension: &Dimension) -> PathBuf;
+    fn visit_data(&mut self, dimension: &Dimension, data: &[u8]) -> Result<()>;
+    fn merge_with(&mut self, other: Self);
+}
+
+#[derive(Clone)]
+struct Chunks<V>(V);
+impl<V: ChunkVisitor> RegionVisitor for Chunks<V> {
+    fn region_dir(dimension: &Dimension) -> PathBuf {
+        dimension.region_dir()
+    }
+    fn visit_data(&mut self, dimension: &Dimension, data: &[u8]) -> Result<()> {
+        self.0.visit(dimension, &read_chunk(data)?)
+    }
+    fn merge_with(&mut self, other: Self) {
+        self.0.merge(other.0);
+    }
+}
+
+#[derive(Clone)]
+struct Entities<V>(V);
+impl<V: EntityVisitor> RegionVisitor for Entities<V> {
+    fn region_dir(dimension: &Dimension) -> PathBuf {
+        dimension.entities_dir()
+    }
+    fn visit_data(&mut self, dimension: &Dimension, data: &[u8]) -> Result<()> {
+        self.0.visit(dimension, &read_entities(data)?)
+    }
+    fn merge_with(&mut self, other: Self) {
+        self.0.merge(other.0);
+    }
+}
+
+pub struct RegionReport<'a> {
+    pub dimension: &'a Dimension,
+    pub path: &'a Path,
+    pub size: u64,
+    /// How many regions are done, this one included, out of `total`.
+    pub done: usize,
+    pub total: usize,
+    pub chunks: usize,
+    /// For the whole region or for single chunks, the scan goes on after them.
+    pub errors: Vec<anyhow::Error>,
+    pub elapsed: Duration,
+}
+
+struct RegionDone {
+    index: usize,
+    chunks: usize,
+    errors: Vec<anyhow::Error>,
+    elapsed: Duration,
+}
+
+/// Returns how many chunks were visited.
+fn scan_region<V: RegionVisitor>(
+    visitor: &mut V,
+    dimension: &Dimension,
+    path: &Path,
+    tmp: &mut Vec<u8>,
+    errors: &mut Vec<anyhow::Error>,
+) -> usize {
+    let mut region = match Region::load(path, false) {
+        Ok(x) => x,
+        Err(e) => {
+            errors.push(e);
+            return 0;
+        }
+    };
+    let mut chunks = 0;
+    for index in 0..CHUNKS_PER_REGION {
+        let result = region.get_chunk(tmp, index).and_then(|data| {
+            if data.is_empty() {
+                return Ok(false);
+            }
+            visitor.visit_data(dimension, data)?;
+            Ok(true)
+        });
+        match result {
+            Ok(true) => chunks += 1,
+            Ok(false) => {}
+            Err(e) => errors.push(e.context(format!("chunk {}", index))),
+        }
+    }
+    chunks
+}
+
+/// Visits the chunks of every region in `dimensions` on `threads` threads, 0 for
+/// one per core. `on_region` is called on this thread as regions are finished.
+pub fn scan<V: ChunkVisitor>(
+    dimensions: &[Dimension],
+    threads: usize,
+    visitor: V,
+    on_region: impl FnMut(RegionReport),
+) -> Result<V> {
+    Ok(scan_impl(dimensions, threads, Chunks(visitor), on_region)?.0)
+}
+/// Like [`scan`], for the entities regions. Dimensions without them are skipped.
+pub fn scan_entities<V: EntityVisitor>(
+    dimensions: &[Dimension],
+    threads: usize,
+    visitor: V,
+    on_region: impl FnMut(RegionReport),
+) -> Result<V> {
+    Ok(scan_impl(dimensions, threads, Entities(visitor), on_region)?.0)
+}
+
+fn scan_impl<V: RegionVisitor>(
+    dimensions: &[Dimension],
+    threads: usize,
+    visitor: V,
+    on_region: impl FnMut(RegionReport),
+) -> Result<V> {
+    let states = for_each_region(
+        dimensions,
+        V::region_dir,
+        threads,
+        (visitor.clone(), Vec::new()),
+        |(visitor, tmp), dimension, path, errors| {
+            scan_region(visitor, dimension, path, tmp, errors)
+        },
+        on_region,
+    )?;
+    let mut result = visitor;
+    for (visitor, _) in states {
+        result.merge_with(visitor);
+    }
+    Ok(result)
+}
+
+/// Calls `work` for the regions in `region_dir` of every dimension, on `threads`
+/// threads, 0 for one per core. Each thread works on its own clone of `state`,
+/// they're returned at the end. `work` returns how many chunks it went through.
+pub(crate) fn for_each_region<S, W>(
+    dimensions: &[Dimension],
+    region_dir: fn(&Dimension) -> PathBuf,
+    threads: usize,
+    state: S,
+    work: W,
+    mut on_region: impl FnMut(RegionReport),
+) -> Result<Vec<S>>
+where
+    S: Clone + Send,
+    W: Fn(&mut S, &Dimension, &Path, &mut Vec<anyhow::Error>) -> usize + Sync,
+{
+    let mut jobs = Vec::new();
+    for dimension in dimensions {
+        let dir = region_dir(dimension);
+        if !dir.is_dir() {
+            continue;
+        }
+        for (path, size) in region_files(&dir)? {
+            jobs.push((dimension, path, size));
+        }
+    }
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |x| x.get()),
+        x => x,
+    };
+    let threads = threads.min(jobs.len()).max(1);
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                let (jobs, next, work, sender) = (&jobs, &next, &work, sender.clone());
+                let mut state = state.clone();
+                s.spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((dimension, path, _)) = jobs.get(index) else {
+                            break;
+                        };
+                        let start = Instant::now();
+                        let mut errors = Vec::new();
+                        let chunks = work(&mut state, dimension, path, &mut errors);
+                        let done = RegionDone {
+                            index,
+                            chunks,
+                            errors,
+                            elapsed: start.elapsed(),
+                        };
+                        if sender.send(done).is_err() {
+                            break;
+                        }
+                    }
+                    state
+                })
+            })
+            .collect();
+        drop(sender);
+
+        for (done, region) in receiver.iter().enumerate() {
+            let (dimension, path, size) = &jobs[region.index];
+            on_region(RegionReport {
+                dimension,
+                path,
+                size: *size,
+                done: done + 1,
+                total: jobs.len(),
+                chunks: region.chunks,
+                errors: region.errors,
+                elapsed: region.elapsed,
+            });
+        }
+
+        workers
+            .into_iter()
+            .map(|x| x.join().map_err(|_| anyhow!("a scan thread panicked")))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use anyhow::Result;
+    use dune_common::nbt;
+    use serde_derive::Serialize;
+
+    use crate::tests::temp_dir;
+    use crate::world::anvil::Region;
+    use crate::world::chunk::Chunk;
+    use crate::world::scan::{ChunkVisitor, Dimension, scan};
+
+    #[derive(Serialize)]
+    struct RawChunk {
+        #[serde(rename = "DataVersion")]
+        data_version: i32,
+        #[serde(rename = "xPos")]
+        x: i32,
+        #[serde(rename = "zPos")]
+        z: i32,
+    }
+
+    #[derive(Clone, Default)]
+    struct Positions(Vec<(String, i32, i32)>);
+    impl ChunkVisitor for Positions {
+        fn visit(&mut self, dimension: &Dimension, chunk: &Chunk) -> Result<()> {
+            self.0.push((dimension.name.clone(), chunk.x, chunk.z));
+            Ok(())
+        }
+        fn merge(&mut self, other: Self) {
+            self.0.extend(other.0);
+        }
+    }
+
+    #[test]
+    fn scan_world() {
+        let world = temp_dir("scan");
+        let chunks = [("", 0, 0, 3), ("", -1, 0, 31), ("DIM-1", 0, 1, 0)];
+        for (dir, region_x, region_z, index) in chunks {
+            let dir = world.join(dir).join("region");
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(format!("r.{}.{}.mca", region_x, region_z));
+            let mut region = Region::create(&path).unwrap();
+            let raw = RawChunk {
+                data_version: 3700,
+                x: region_x * 32 + index % 32,
+                z: region_z * 32 + index / 32,
+            };
+            let mut data = Vec::new();
+            nbt::to_writer(&mut data, "", &raw).unwrap();
+            region.write_chunk(index as usize, &data).unwrap();
+        }
+        // not a region, it's skipped
+        fs::write(world.join("region/r.0.0.mca.bak"), b"").unwrap();
+
+        let dimensions = Dimension::find_all(&world).unwrap();
+        let mut reports = 0;
+        let mut result = scan(&dimensions, 2, Positions::default(), |report| {
+            assert!(report.errors.is_empty());
+            assert_eq!(report.chunks, 1);
+            reports += 1;
+        })
+        .unwrap();
+        result.0.sort();
+
+        let overworld = "minecraft:overworld".to_string();
+        let nether = "minecraft:the_nether".to_string();
+        assert_eq!(reports, 3);
+        assert_eq!(
+            result.0,
+            [
+                (overworld.clone(), -1, 0),
+                (overworld, 3, 0),
+                (nether, 0, 32)
+            ]
+        );
+        fs::remove_dir_all(&world).unwrap();
+    }
+}
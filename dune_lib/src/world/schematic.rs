@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use dune_common::nbt::{self, Compound, OwnedRootTag, OwnedTag};
+use serde_derive::Deserialize;
+
+use crate::item::{get_int, take_string};
+use crate::world::anvil::Region;
+use crate::world::chunk::read_chunk;
+use crate::world::scan::Dimension;
+use crate::world::section::Block;
+
+/// The most blocks [`Schematic::from_world`] cuts out, 512 MiB of indices.
+pub const MAX_VOLUME: u64 = 1 << 27;
+/// The most blocks [`Schematic::to_structure`] writes. Every block, air too, is a
+/// compound of its own, a few hundred bytes each.
+pub const MAX_STRUCTURE_VOLUME: u64 = 1 << 20;
+
+/// How many blocks are between `from` and `to`, both included.
+pub fn volume(from: [i32; 3], to: [i32; 3]) -> u64 {
+    (0..3)
+        .map(|i| from[i].abs_diff(to[i]) as u64 + 1)
+        .fold(1, u64::saturating_mul)
+}
+
+/// A box of blocks cut out of a world, with its block entities and entities.
+pub struct Schematic {
+    pub data_version: i32,
+    /// The corner with the lowest coordinates, in the world.
+    pub origin: [i32; 3],
+    /// Width, height and length.
+    pub size: [i32; 3],
+    /// Air is always the first.
+    pub palette: Vec<Block>,
+    /// Indices into the palette, `x + z * width + y * width * length`.
+    pub blocks: Vec<u32>,
+    /// The positions are relative to the origin, the nbt has the id but not the
+    /// position.
+    pub block_entities: Vec<([i32; 3], Compound)>,
+    /// The positions are relative to the origin, the nbt is left as it is
+    /// without the uuid, so pasted entities get new ones.
+    pub entities: Vec<([f64; 3], Compound)>,
+}
+
+// the layout on disk, only what `read_chunk` doesn't keep
+
+#[derive(Deserialize)]
+struct RawLevel {
+    #[serde(rename = "TileEntities", default)]
+    tile_entities: Vec<Compound>,
+    #[serde(rename = "Entities", default)]
+    entities: Vec<Compound>,
+}
+#[derive(Deserialize)]
+struct RawChunk {
+    #[serde(default)]
+    block_entities: Vec<Compound>,
+    #[serde(rename = "Level")]
+    level: Option<RawLevel>,
+}
+#[derive(Deserialize)]
+struct RawEntityChunk {
+    #[serde(rename = "Entities", default)]
+    entities: Vec<Compound>,
+}
+
+/// The regions of a folder, loaded when first needed.
+struct Regions<'a> {
+    dir: &'a Path,
+    loaded: HashMap<(i32, i32), Option<Region>>,
+    buffer: Vec<u8>,
+}
+impl Regions<'_> {
+    /// The nbt of the chunk, empty if it was never generated.
+    fn chunk(&mut self, x: i32, z: i32) -> Result<&[u8]> {
+        let key = (x >> 5, z >> 5);
+        if !self.loaded.contains_key(&key) {
+            let path = self.dir.join(format!("r.{}.{}.mca", key.0, key.1));
+            let region = match path.is_file() {
+                true => Some(Region::load(&path, false)?),
+                false => None,
+            };
+            self.loaded.insert(key, region);
+        }
+        self.buffer.clear();
+        let index = ((z & 31) * 32 + (x & 31)) as usize;
+        match self.loaded.get_mut(&key).unwrap() {
+            Some(region) => region.get_chunk(&mut self.buffer, index),
+            None => Ok(&self.buffer),
+        }
+    }
+}
+
+fn entity_position(tag: &Compound) -> Option<[f64; 3]> {
+    let Some(OwnedTag::List(pos)) = tag.get("Pos") else {
+        return None;
+    };
+    match pos[..] {
+        [
+            OwnedTag::Double(x),
+            OwnedTag::Double(y),
+            OwnedTag::Double(z),
+        ] => Some([x, y, z]),
+        _ => None,
+    }
+}
+
+impl Schematic {
+    /// Cuts out the blocks between `from` and `to`, both included. Chunks that
+    /// were never generated are left as air.
+    pub fn from_world(dimension: &Dimension, from: [i32; 3], to: [i32; 3]) -> Result<Schematic> {
+        let min = [0, 1, 2].map(|i| from[i].min(to[i]));
+        let max = [0, 1, 2].map(|i| from[i].max(to[i]));
+        let size = [0, 1, 2].map(|i| max[i] as i64 - min[i] as i64 + 1);
+        if size.iter().any(|x| *x > u16::MAX as i64) {
+            bail!("the box is too big: {:?}", size);
+        }
+        let volume = volume(from, to);
+        if volume > MAX_VOLUME {
+            bail!(
+                "the box has {} blocks, more than the {} that can be cut out",
+                volume,
+                MAX_VOLUME
+            );
+        }
+        let size = size.map(|x| x as i32);
+        let [width, height, length] = size.map(|x| x as usize);
+        let inside = |x: i32, y: i32, z: i32| {
+            (min[0]..=max[0]).contains(&x)
+                && (min[1]..=max[1]).contains(&y)
+                && (min[2]..=max[2]).contains(&z)
+        };
+
+        let mut result = Schematic {
+            data_version: 0,
+            origin: min,
+            size,
+            palette: vec![Block::air()],
+            blocks: vec![0; width * height * length],
+            block_entities: Vec::new(),
+            entities: Vec::new(),
+        };
+        let mut indices = HashMap::from([(Block::air(), 0)]);
+
+        let region_dir = dimension.region_dir();
+        let entities_dir = dimension.entities_dir();
+        let mut regions = Regions {
+            dir: &region_dir,
+            loaded: HashMap::new(),
+            buffer: Vec::new(),
+        };
+        let mut entity_regions = Regions {
+            dir: &entities_dir,
+            loaded: HashMap::new(),
+            buffer: Vec::new(),
+        };
+        for chunk_x in min[0] >> 4..=max[0] >> 4 {
+            for chunk_z in min[2] >> 4..=max[2] >> 4 {
+                let data = regions.chunk(chunk_x, chunk_z)?;
+                if data.is_empty() {
+                    continue;
+                }
+                let chunk = read_chunk(data)?;
+                result.data_version = result.data_version.max(chunk.data_version);
+
+                let xs = (chunk_x * 16).max(min[0])..=(chunk_x * 16 + 15).min(max[0]);
+                let zs = (chunk_z * 16).max(min[2])..=(chunk_z * 16 + 15).min(max[2]);
+                for section in &chunk.sections {
+                    // the palette of the section to the one of the schematic
+                    let mut mapping = vec![None; section.block_states.palette.len()];
+                    let section_y = section.y as i32 * 16;
+                    for y in section_y.max(min[1])..=(section_y + 15).min(max[1]) {
+                        for z in zs.clone() {
+                            for x in xs.clone() {
+                                let local = ((y & 15) * 256 + (z & 15) * 16 + (x & 15)) as usize;
+                                let Some(state) = section.block_states.palette_index(local) else {
+                                    continue;
+                                };
+                                let Some(slot) = mapping.get_mut(state) else {
+                                    continue;
+                                };
+                                let index = *slot.get_or_insert_with(|| {
+                                    let block = Block::from(&section.block_states.palette[state]);
+                                    *indices.entry(block.clone()).or_insert_with(|| {
+                                        result.palette.push(block);
+                                        result.palette.len() as u32 - 1
+                                    })
+                                });
+                                let [x, y, z] =
+                                    [x - min[0], y - min[1], z - min[2]].map(|x| x as usize);
+                                result.blocks[x + z * width + y * width * length] = index;
+                            }
+                        }
+                    }
+                }
+
+                let raw: RawChunk = nbt::from_slice(data, nbt::Format::Named)?;
+                let (block_entities, mut entities) = match raw.level {
+                    Some(level) => (level.tile_entities, level.entities),
+                    None => (raw.block_entities, Vec::new()),
+                };
+                for mut i in block_entities {
+                    let mut coordinate = |key| i.shift_remove(key).as_ref().and_then(get_int);
+                    let (Some(x), Some(y), Some(z)) =
+                        (coordinate("x"), coordinate("y"), coordinate("z"))
+                    else {
+                        continue;
+                    };
+                    if inside(x, y, z) {
+                        let position = [x - min[0], y - min[1], z - min[2]];
+                        result.block_entities.push((position, i));
+                    }
+                }
+
+                // since 1.17 entities are in their own regions
+                let data = entity_regions.chunk(chunk_x, chunk_z)?;
+                if !data.is_empty() {
+                    let raw: RawEntityChunk = nbt::from_slice(data, nbt::Format::Named)?;
+                    entities.extend(raw.entities);
+                }
+                for mut i in entities {
+                    let Some([x, y, z]) = entity_position(&i) else {
+                        continue;
+                    };
+                    if inside(x.floor() as i32, y.floor() as i32, z.floor() as i32) {
+                        i.shift_remove("UUID");
+                        let position = [x - min[0] as f64, y - min[1] as f64, z - min[2] as f64];
+                        result.entities.push((position, i));
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// A [Sponge schematic](https://github.com/SpongePowered/Schematic-Specification)
+    /// v3, the `.schem` files of WorldEdit.
+    pub fn to_sponge(&self) -> OwnedRootTag {
+        let mut palette = Compound::new();
+        for (index, block) in self.palette.iter().enumerate() {
+            palette.insert(block.to_string(), OwnedTag::Int(index as i32));
+        }
+        let mut data = Vec::with_capacity(self.blocks.len());
+        for &(mut x) in &self.blocks {
+            // a varint
+            while x >= 0x80 {
+                data.push(x as u8 | 0x80);
+                x >>= 7;
+            }
+            data.push(x as u8);
+        }
+
+        let block_entities = self.block_entities.iter().map(|(position, tag)| {
+            let mut tag = tag.clone();
+            let id = take_string(&mut tag, "id").unwrap_or_default();
+            OwnedTag::Compound(Compound::from([
+                ("Pos".to_string(), OwnedTag::IntArray(position.to_vec())),
+                ("Id".to_string(), OwnedTag::String(id)),
+                ("Data".to_string(), OwnedTag::Compound(tag)),
+            ]))
+        });
+        let entities = self.entities.iter().map(|(position, tag)| {
+            let mut tag = tag.clone();
+            let id = take_string(&mut tag, "id").unwrap_or_default();
+            tag.shift_remove("Pos");
+            OwnedTag::Compound(Compound::from([
+                ("Pos".to_string(), doubles(position)),
+                ("Id".to_string(), OwnedTag::String(id)),
+                ("Data".to_string(), OwnedTag::Compound(tag)),
+            ]))
+        });
+
+        let [width, height, length] = self.size.map(|x| OwnedTag::Short(x as u16 as i16));
+        let blocks = Compound::from([
+            ("Palette".to_string(), OwnedTag::Compound(palette)),
+            ("Data".to_string(), OwnedTag::ByteArray(data)),
+            (
+                "BlockEntities".to_string(),
+                OwnedTag::List(block_entities.collect()),
+            ),
+        ]);
+        let schematic = Compound::from([
+            ("Version".to_string(), OwnedTag::Int(3)),
+            ("DataVersion".to_string(), OwnedTag::Int(self.data_version)),
+            ("Width".to_string(), width),
+            ("Height".to_string(), height),
+            ("Length".to_string(), length),
+            ("Offset".to_string(), OwnedTag::IntArray(vec![0; 3])),
+            ("Blocks".to_string(), OwnedTag::Compound(blocks)),
+            ("Entities".to_string(), OwnedTag::List(entities.collect())),
+        ]);
+        OwnedRootTag {
+            name: String::new(),
+            tag: OwnedTag::Compound(Compound::from([(
+                "Schematic".to_string(),
+                OwnedTag::Compound(schematic),
+            )])),
+        }
+    }
+
+    /// The `.nbt` files of structure blocks, in `generated/<namespace>/structures`.
+    pub fn to_structure(&self) -> Result<OwnedRootTag> {
+        let volume = self.blocks.len() as u64;
+        if volume > MAX_STRUCTURE_VOLUME {
+            bail!(
+                "the box has {} blocks, more than the {} a structure can have",
+                volume,
+                MAX_STRUCTURE_VOLUME
+            );
+        }
+        let palette = self.palette.iter().map(|block| {
+            let mut tag =
+                Compound::from([("Name".to_string(), OwnedTag::String(block.name.clone()))]);
+            if !block.properties.is_empty() {
+                let properties = block
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), OwnedTag::String(v.clone())))
+                    .collect();
+                tag.insert("Properties".to_string(), OwnedTag::Compound(properties));
+            }
+            OwnedTag::Compound(tag)
+        });
+
+        let nbt: HashMap<_, _> = self
+            .block_entities
+            .iter()
+            .map(|(position, tag)| (*position, tag))
+            .collect();
+        let [width, height, length] = self.size;
+        let mut blocks = Vec::with_capacity(self.blocks.len());
+        for y in 0..height {
+            for z in 0..length {
+                for x in 0..width {
+                    let state = self.blocks[(x + z * width + y * width * length) as usize];
+                    let mut block = Compound::from([
+                        ("state".to_string(), OwnedTag::Int(state as i32)),
+                        ("pos".to_string(), ints(&[x, y, z])),
+                    ]);
+                    if let Some(tag) = nbt.get(&[x, y, z]) {
+                        block.insert("nbt".to_string(), OwnedTag::Compound((*tag).clone()));
+                    }
+                    blocks.push(OwnedTag::Compound(block));
+                }
+            }
+        }
+
+        let entities = self.entities.iter().map(|(position, tag)| {
+            OwnedTag::Compound(Compound::from([
+                ("pos".to_string(), doubles(position)),
+                (
+                    "blockPos".to_string(),
+                    ints(&position.map(|x| x.floor() as i32)),
+                ),
+                ("nbt".to_string(), OwnedTag::Compound(tag.clone())),
+            ]))
+        });
+
+        let structure = Compound::from([
+            ("DataVersion".to_string(), OwnedTag::Int(self.data_version)),
+            ("size".to_string(), ints(&self.size)),
+            ("palette".to_string(), OwnedTag::List(palette.collect())),
+            ("blocks".to_string(), OwnedTag::List(blocks)),
+            ("entities".to_string(), OwnedTag::List(entities.collect())),
+        ]);
+        Ok(OwnedRootTag {
+            name: String::new(),
+            tag: OwnedTag::Compound(structure),
+        })
+    }
+}
+
+/// Structures use lists, not int arrays.
+fn ints(values: &[i32]) -> OwnedTag {
+    OwnedTag::List(values.iter().map(|x| OwnedTag::Int(*x)).collect())
+}
+fn doubles(values: &[f64]) -> OwnedTag {
+    OwnedTag::List(values.iter().map(|x| OwnedTag::Double(*x)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use dune_common::nbt::OwnedTag;
+
+    use crate::tests::{snbt_to_nbt, temp_dir};
+    use crate::world::anvil::Region;
+    use crate::world::scan::Dimension;
+    use crate::world::schematic::{Schematic, volume};
+
+    #[test]
+    fn export() {
+        let world = temp_dir("schematic");
+        let write = |dir: &str, snbt: &str| {
+            std::fs::create_dir_all(world.join(dir)).unwrap();
+            let mut region = Region::create(&world.join(dir).join("r.0.0.mca")).unwrap();
+            region.write_chunk(0, &snbt_to_nbt(snbt)).unwrap();
+        };
+        write(
+            "region",
+            r#"{DataVersion: 3700, xPos: 0, zPos: 0, yPos: -4,
+                sections: [{Y: 4b, block_states: {palette: [{Name: "minecraft:stone"}]}},
+                    {Y: 5b, block_states: {palette: [{Name: "minecraft:oak_stairs", Properties: {half: "bottom", facing: "east"}}]}}],
+                block_entities: [{id: "minecraft:chest", x: 1, y: 80, z: 1, Items: [{Slot: 0b, id: "minecraft:diamond", Count: 3b}]},
+                    {id: "minecraft:chest", x: 9, y: 80, z: 9}]}"#,
+        );
+        write(
+            "entities",
+            r#"{DataVersion: 3700, Position: [I; 0, 0],
+                Entities: [{id: "minecraft:pig", Pos: [1.5d, 79.0d, 0.5d], UUID: [I; 1, 2, 3, 4]}]}"#,
+        );
+
+        let dimension = Dimension {
+            name: "minecraft:overworld".to_string(),
+            path: world.clone(),
+        };
+        let schematic = Schematic::from_world(&dimension, [2, 81, 2], [0, 79, 0]).unwrap();
+        assert_eq!(schematic.size, [3, 3, 3]);
+        let names: Vec<_> = schematic.palette.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            names,
+            [
+                "minecraft:air",
+                "minecraft:stone",
+                "minecraft:oak_stairs[facing=east,half=bottom]"
+            ]
+        );
+        // y 79 is stone, the rest is stairs
+        assert_eq!(schematic.blocks[..9], [1; 9]);
+        assert_eq!(schematic.blocks[9..], [2; 18]);
+        assert_eq!(schematic.block_entities.len(), 1);
+        assert_eq!(schematic.block_entities[0].0, [1, 1, 1]);
+        assert_eq!(schematic.entities[0].0, [1.5, 0.0, 0.5]);
+        assert!(!schematic.entities[0].1.contains_key("UUID"));
+
+        let OwnedTag::Compound(sponge) = schematic.to_sponge().tag else {
+            panic!("expected a compound");
+        };
+        let Some(OwnedTag::Compound(sponge)) = sponge.get("Schematic") else {
+            panic!("expected the schematic");
+        };
+        assert_eq!(sponge.get("Width"), Some(&OwnedTag::Short(3)));
+        let OwnedTag::Compound(structure) = schematic.to_structure().unwrap().tag else {
+            panic!("expected a compound");
+        };
+        let Some(OwnedTag::List(blocks)) = structure.get("blocks") else {
+            panic!("expected the blocks");
+        };
+        assert_eq!(blocks.len(), 27);
+
+        // every side fits but not all of them together
+        let result = Schematic::from_world(&dimension, [0, 0, 0], [9999, 383, 9999]);
+        assert!(result.is_err());
+        let result = Schematic::from_world(&dimension, [i32::MIN; 3], [i32::MAX; 3]);
+        assert!(result.is_err());
+
+        // fine as a sponge schematic, too big for a structure
+        assert_eq!(volume([128, 63, 127], [0, 0, 0]), 129 * 64 * 128);
+        let schematic = Schematic::from_world(&dimension, [0, 0, 0], [128, 63, 127]).unwrap();
+        assert!(schematic.to_structure().is_err());
+        std::fs::remove_dir_all(&world).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::{Result, bail};
+use serde::de::IgnoredAny;
+use serde_derive::Deserialize;
+
+/// Since 1.16 (2529) a packed value never spans two longs, the leftover bits of
+/// every long are padding.
+pub const NO_SPANNING_DATA_VERSION: i32 = 2529;
+
+pub const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+/// Biomes are stored in 4x4x4 cells since 1.18.
+pub const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct BlockState<'x> {
+    #[serde(rename = "Name")]
+    pub name: &'x str,
+    #[serde(rename = "Properties", default, borrow)]
+    pub properties: HashMap<&'x str, &'x str>,
+}
+impl<'x> BlockState<'x> {
+    pub fn property(&self, name: &str) -> Option<&'x str> {
+        self.properties.get(name).copied()
+    }
+}
+
+/// An owned [`BlockState`], with the properties sorted so equal states compare
+/// and print the same.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Block {
+    pub name: String,
+    pub properties: Vec<(String, String)>,
+}
+impl Block {
+    pub fn air() -> Block {
+        Block {
+            name: "minecraft:air".to_string(),
+            properties: Vec::new(),
+        }
+    }
+}
+impl From<&BlockState<'_>> for Block {
+    fn from(state: &BlockState) -> Block {
+        let mut properties: Vec<_> = state
+            .properties
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        properties.sort();
+        Block {
+            name: state.name.to_string(),
+            properties,
+        }
+    }
+}
+/// Like `minecraft:oak_stairs[facing=east,half=bottom]`.
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for (index, (k, v)) in self.properties.iter().enumerate() {
+            let separator = if index == 0 { '[' } else { ',' };
+            write!(f, "{}{}={}", separator, k, v)?;
+        }
+        if !self.properties.is_empty() {
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+/// A palette and the indices into it, bit-packed into longs.
+#[derive(Debug)]
+pub struct Paletted<T> {
+    pub palette: Vec<T>,
+    /// Empty when the palette has a single entry.
+    pub data: Vec<i64>,
+    bits: u32,
+    spanning: bool,
+}
+
+fn bits_for(palette_len: usize) -> u32 {
+    if palette_len <= 1 {
+        0
+    } else {
+        usize::BITS - (palette_len - 1).leading_zeros()
+    }
+}
+
+/// The `index`th value of `bits` bits in `data`, `None` if the data is too short.
+pub(super) fn unpack(data: &[i64], bits: u32, spanning: bool, index: usize) -> Option<u64> {
+    let bits = bits as usize;
+    let mask = (1u64 << bits) - 1;
+
+    let value = if spanning {
+        let bit_index = index * bits;
+        let (long_index, offset) = (bit_index / 64, bit_index % 64);
+        let mut value = *data.get(long_index)? as u64 >> offset;
+        if offset + bits > 64 {
+            value |= (*data.get(long_index + 1)? as u64) << (64 - offset);
+        }
+        value
+    } else {
+        let per_long = 64 / bits;
+        let long = *data.get(index / per_long)? as u64;
+        long >> (index % per_long * bits)
+    };
+    Some(value & mask)
+}
+
+impl<T> Paletted<T> {
+    pub(super) fn new(palette: Vec<T>, data: Vec<i64>, min_bits: u32, data_version: i32) -> Self {
+        let bits = if data.is_empty() {
+            0
+        } else {
+            bits_for(palette.len()).max(min_bits)
+        };
+        Paletted {
+            palette,
+            data,
+            bits,
+            spanning: data_version < NO_SPANNING_DATA_VERSION,
+        }
+    }
+
+    /// The palette index of the `index`th value, `None` if the data is too short.
+    pub fn palette_index(&self, index: usize) -> Option<usize> {
+        if self.bits == 0 {
+            return Some(0);
+        }
+        Some(unpack(&self.data, self.bits, self.spanning, index)? as usize)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.palette.get(self.palette_index(index)?)
+    }
+}
+
+/// A 16x16x16 part of a chunk.
+#[derive(Debug)]
+pub struct Section<'x> {
+    pub y: i8,
+    pub block_states: Paletted<BlockState<'x>>,
+    /// `None` before 1.18, when biomes were stored per chunk, by numeric id.
+    pub biomes: Option<Paletted<&'x str>>,
+}
+impl<'x> Section<'x> {
+    /// `x`, `y` and `z` are taken modulo 16, so world coordinates work too.
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<&BlockState<'x>> {
+        let index = ((y & 15) * 256 + (z & 15) * 16 + (x & 15)) as usize;
+        self.block_states.get(index)
+    }
+    pub fn biome_at(&self, x: i32, y: i32, z: i32) -> Option<&'x str> {
+        let (x, y, z) = ((x & 15) >> 2, (y & 15) >> 2, (z & 15) >> 2);
+        let index = (y * 16 + z * 4 + x) as usize;
+        self.biomes.as_ref()?.get(index).copied()
+    }
+}
+
+// the layout on disk
+
+#[derive(Deserialize)]
+pub(super) struct RawPaletted<T> {
+    palette: Vec<T>,
+    #[serde(default)]
+    data: Vec<i64>,
+}
+
+/// Both layouts: `block_states` and `biomes` since 1.18, `Palette` and
+/// `BlockStates` before.
+#[derive(Deserialize)]
+pub(super) struct RawSection<'x> {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(borrow)]
+    block_states: Option<RawPaletted<BlockState<'x>>>,
+    #[serde(borrow)]
+    biomes: Option<RawPaletted<&'x str>>,
+    #[serde(rename = "Palette", borrow)]
+    palette: Option<Vec<BlockState<'x>>>,
+    #[serde(rename = "BlockStates", default)]
+    block_states_old: Vec<i64>,
+    /// Numeric ids before 1.13, only looked for to refuse them.
+    #[serde(rename = "Blocks")]
+    blocks: Option<IgnoredAny>,
+}
+
+impl<'x> RawSection<'x> {
+    /// `None` for sections with nothing but light in them.
+    pub(super) fn decode(self, data_version: i32) -> Result<Option<Section<'x>>> {
+        let block_states = match (self.block_states, self.palette) {
+            (Some(x), _) => Paletted::new(x.palette, x.data, 4, data_version),
+            (None, Some(palette)) => Paletted::new(palette, self.block_states_old, 4, data_version),
+            (None, None) if self.blocks.is_some() => {
+                bail!("pre-1.13 sections are not supported")
+            }
+            (None, None) => return Ok(None),
+        };
+        let biomes = self
+            .biomes
+            .map(|x| Paletted::new(x.palette, x.data, 0, data_version));
+        Ok(Some(Section {
+            y: self.y,
+            block_states,
+            biomes,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::section::Paletted;
+
+    #[test]
+    fn packed() {
+        // 5 bits, the 13th value starts at bit 60 and goes into the second long
+        let values: Vec<u64> = (0..26).map(|x| x % 32).collect();
+
+        let mut spanning = vec![0u64; 3];
+        for (index, x) in values.iter().enumerate() {
+            let bit = index * 5;
+            spanning[bit / 64] |= x << (bit % 64);
+            if bit % 64 + 5 > 64 {
+                spanning[bit / 64 + 1] |= x >> (64 - bit % 64);
+            }
+        }
+        let mut padded = vec![0u64; 3];
+        for (index, x) in values.iter().enumerate() {
+            padded[index / 12] |= x << (index % 12 * 5);
+        }
+
+        let palette: Vec<_> = (0..32).collect();
+        let to_data = |x: Vec<u64>| x.into_iter().map(|x| x as i64).collect();
+        let spanning = Paletted::new(palette.clone(), to_data(spanning), 4, 2000);
+        let padded = Paletted::new(palette, to_data(padded), 4, 3000);
+        for (index, x) in values.iter().enumerate() {
+            assert_eq!(spanning.get(index), Some(&(*x as i32)));
+            assert_eq!(padded.get(index), Some(&(*x as i32)));
+        }
+    }
+}